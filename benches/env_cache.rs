@@ -0,0 +1,49 @@
+//! Compares repeated `Cargo::out_dir` calls, served from the cache of environment variables,
+//! with reading `OUT_DIR` from the environment on every call, as the getters do with the `no-cache` feature.
+//!
+//! Run with `cargo bench --bench env_cache`.
+
+use std::{
+    hint::black_box,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use build_instructions::Cargo;
+
+const CALLS: u32 = 200_000;
+
+/// Returns the average duration of a call to `f` over `CALLS` calls.
+fn measure<T>(mut f: impl FnMut() -> T) -> Duration {
+    for _ in 0..CALLS / 10 {
+        black_box(f());
+    }
+
+    let start = Instant::now();
+    for _ in 0..CALLS {
+        black_box(f());
+    }
+    start.elapsed() / CALLS
+}
+
+fn main() {
+    std::env::set_var(
+        "OUT_DIR",
+        "/tmp/build-instructions-bench/target/debug/build/bench-0123456789abcdef/out",
+    );
+    // A realistic environment holds a few hundred variables, which uncached reads scan through
+    for i in 0..200 {
+        std::env::set_var(format!("BUILD_INSTRUCTIONS_BENCH_{i}"), "value");
+    }
+    Cargo::invalidate_cache();
+
+    let uncached = measure(|| std::env::var_os("OUT_DIR").map(PathBuf::from));
+    let cached = measure(Cargo::out_dir);
+
+    println!("uncached OUT_DIR read: {uncached:?} per call");
+    println!("cached Cargo::out_dir: {cached:?} per call");
+    println!(
+        "speedup: {:.1}x",
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
@@ -0,0 +1,176 @@
+use std::{convert::Infallible, path::PathBuf};
+
+use crate::{vars, ArtifactError, FromEnvVar};
+
+/// Provides access to the files of artifact dependencies, declared with the `artifact` key
+/// such as `tool = { version = "1", artifact = "bin" }`. Artifact dependencies are unstable
+/// and need `-Z bindeps` on a nightly compiler.
+///
+/// The dependency name is the one the dependency is declared under, which is its package name unless renamed,
+/// uppercased with `-` replaced by `_` in the variable name. Binaries are also found under a variable suffixed
+/// with `_` and the name of their binary target, which is kept as is, so the binary `alt-name` of `my-tool` is read
+/// from `CARGO_BIN_FILE_MY_TOOL_alt-name`. Libraries get no such suffix, and neither does the `target`
+/// key of the dependency, which selects the platform an artifact is built for without changing the variable names.
+pub struct Artifact(Infallible);
+
+impl Artifact {
+    /// Returns the path to a binary of the artifact dependency `dep`, the one named `name`
+    /// or the one named like the package when `name` is `None`,
+    /// from `CARGO_BIN_FILE_<DEP>_<name>` or `CARGO_BIN_FILE_<DEP>`.
+    pub fn bin(dep: &str, name: Option<&str>) -> Result<PathBuf, ArtifactError> {
+        read(vars::CARGO_BIN_FILE_PREFIX, dep, name, "bin")
+    }
+
+    /// Returns the path to the dynamic library of the artifact dependency `dep`, from `CARGO_CDYLIB_FILE_<DEP>`.
+    pub fn cdylib(dep: &str) -> Result<PathBuf, ArtifactError> {
+        read(vars::CARGO_CDYLIB_FILE_PREFIX, dep, None, "cdylib")
+    }
+
+    /// Returns the path to the static library of the artifact dependency `dep`, from `CARGO_STATICLIB_FILE_<DEP>`.
+    pub fn staticlib(dep: &str) -> Result<PathBuf, ArtifactError> {
+        read(vars::CARGO_STATICLIB_FILE_PREFIX, dep, None, "staticlib")
+    }
+}
+
+/// Maps a dependency name and an optional artifact name to the variable Cargo sets for them.
+fn env_var_name(prefix: &str, dep: &str, name: Option<&str>) -> String {
+    let dep = dep.to_uppercase().replace('-', "_");
+    match name {
+        Some(name) => format!("{prefix}{dep}_{name}"),
+        None => format!("{prefix}{dep}"),
+    }
+}
+
+/// Reads the path to an artifact of the kind `kind`, explaining how to declare the dependency if it's missing.
+fn read(prefix: &str, dep: &str, name: Option<&str>, kind: &str) -> Result<PathBuf, ArtifactError> {
+    let var = env_var_name(prefix, dep, name);
+
+    match PathBuf::read_env_var(var.clone()) {
+        Err(err) if err.is_not_present() => {
+            let artifact = match name {
+                Some(name) => format!("{kind}:{name}"),
+                None => kind.to_owned(),
+            };
+
+            Err(ArtifactError::NotDeclared {
+                var,
+                declaration: format!("{dep} = {{ ..., artifact = \"{artifact}\" }}"),
+            })
+        }
+        path => Ok(path?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::TestEnv;
+
+    #[test]
+    fn names_are_mangled() {
+        let cases = [
+            (
+                vars::CARGO_BIN_FILE_PREFIX,
+                "mytool",
+                None,
+                "CARGO_BIN_FILE_MYTOOL",
+            ),
+            (
+                vars::CARGO_BIN_FILE_PREFIX,
+                "my-tool",
+                None,
+                "CARGO_BIN_FILE_MY_TOOL",
+            ),
+            (
+                vars::CARGO_BIN_FILE_PREFIX,
+                "mytool",
+                Some("mytool"),
+                "CARGO_BIN_FILE_MYTOOL_mytool",
+            ),
+            (
+                vars::CARGO_BIN_FILE_PREFIX,
+                "my-tool",
+                Some("alt-name"),
+                "CARGO_BIN_FILE_MY_TOOL_alt-name",
+            ),
+            (
+                vars::CARGO_BIN_FILE_PREFIX,
+                "my_tool",
+                Some("Alt_Name"),
+                "CARGO_BIN_FILE_MY_TOOL_Alt_Name",
+            ),
+            (
+                vars::CARGO_CDYLIB_FILE_PREFIX,
+                "plugin",
+                None,
+                "CARGO_CDYLIB_FILE_PLUGIN",
+            ),
+            (
+                vars::CARGO_CDYLIB_FILE_PREFIX,
+                "my-plugin",
+                None,
+                "CARGO_CDYLIB_FILE_MY_PLUGIN",
+            ),
+            (
+                vars::CARGO_STATICLIB_FILE_PREFIX,
+                "native",
+                None,
+                "CARGO_STATICLIB_FILE_NATIVE",
+            ),
+            (
+                vars::CARGO_STATICLIB_FILE_PREFIX,
+                "native-sys",
+                None,
+                "CARGO_STATICLIB_FILE_NATIVE_SYS",
+            ),
+        ];
+
+        for (prefix, dep, name, var) in cases {
+            assert_eq!(env_var_name(prefix, dep, name), var, "{dep} {name:?}");
+        }
+    }
+
+    #[test]
+    fn artifacts_are_read() {
+        let mut env = TestEnv::new();
+        env.set(
+            "CARGO_BIN_FILE_MY_TOOL_alt-name",
+            "/target/debug/deps/artifact/alt-name",
+        )
+        .set(
+            "CARGO_STATICLIB_FILE_NATIVE",
+            "/target/debug/deps/artifact/libnative.a",
+        );
+
+        assert_eq!(
+            Artifact::bin("my-tool", Some("alt-name")).unwrap(),
+            PathBuf::from("/target/debug/deps/artifact/alt-name")
+        );
+        assert_eq!(
+            Artifact::staticlib("native").unwrap(),
+            PathBuf::from("/target/debug/deps/artifact/libnative.a")
+        );
+    }
+
+    #[test]
+    fn missing_artifacts_explain_the_declaration() {
+        let mut env = TestEnv::new();
+        env.remove("CARGO_BIN_FILE_MY_TOOL_alt-name")
+            .remove("CARGO_CDYLIB_FILE_PLUGIN");
+
+        assert_eq!(
+            Artifact::bin("my-tool", Some("alt-name")).unwrap_err(),
+            ArtifactError::NotDeclared {
+                var: "CARGO_BIN_FILE_MY_TOOL_alt-name".to_owned(),
+                declaration: "my-tool = { ..., artifact = \"bin:alt-name\" }".to_owned(),
+            }
+        );
+        assert_eq!(
+            Artifact::cdylib("plugin").unwrap_err(),
+            ArtifactError::NotDeclared {
+                var: "CARGO_CDYLIB_FILE_PLUGIN".to_owned(),
+                declaration: "plugin = { ..., artifact = \"cdylib\" }".to_owned(),
+            }
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::{convert::Infallible, path::Path};
+
+use crate::{vars, Cargo};
+
+/// Detects the tools a build script may be running under, to let it skip expensive work that's useless there.
+///
+/// Every detector registers the environment variables it reads with `rerun-if-env-changed`.
+/// Keep in mind that emitting any `rerun-if-*` instruction stops Cargo from re-running the build script
+/// on every change to the package, so scripts using these should declare their other inputs too.
+pub struct BuildEnv(Infallible);
+
+impl BuildEnv {
+    /// Checks if the package is being built by docs.rs, which sets the `DOCS_RS` environment variable.
+    /// Same as [`Cargo::is_docs_rs`].
+    pub fn is_docs_rs() -> bool {
+        Cargo::is_docs_rs()
+    }
+
+    /// Checks if the package is being checked by Clippy, which is indicated by either the `clippy` cfg
+    /// being reported through `CARGO_CFG_CLIPPY` or the `CLIPPY_ARGS` variable set by `cargo clippy`.
+    pub fn is_clippy() -> bool {
+        // Not short-circuiting, so that both variables are always registered
+        is_set(vars::CARGO_CFG_CLIPPY) | is_set(vars::CLIPPY_ARGS)
+    }
+
+    /// Checks if the package is being built for Miri, which reports the `miri` cfg through `CARGO_CFG_MIRI`.
+    pub fn is_miri() -> bool {
+        is_set(vars::CARGO_CFG_MIRI)
+    }
+
+    /// Checks if the package is being built by rust-analyzer, which is detected by `RUSTC_WRAPPER`
+    /// pointing to a binary with `rust-analyzer` in its file name. The directories the binary is in don't count,
+    /// so that other wrappers installed next to rust-analyzer aren't mistaken for it.
+    pub fn is_rust_analyzer() -> bool {
+        Cargo::rerun_if_env_changed(vars::RUSTC_WRAPPER);
+        std::env::var_os(vars::RUSTC_WRAPPER).is_some_and(|wrapper| {
+            Path::new(&wrapper)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().contains("rust-analyzer"))
+        })
+    }
+}
+
+/// Checks whether the environment variable `var` is set, registering it with `rerun-if-env-changed`.
+fn is_set(var: &str) -> bool {
+    Cargo::rerun_if_env_changed(var);
+    std::env::var_os(var).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::TestEnv;
+
+    #[test]
+    fn docs_rs() {
+        let mut env = TestEnv::new();
+        env.remove(vars::DOCS_RS);
+        assert!(!BuildEnv::is_docs_rs());
+
+        env.set(vars::DOCS_RS, "1");
+        assert!(BuildEnv::is_docs_rs());
+    }
+
+    #[test]
+    fn clippy() {
+        let mut env = TestEnv::new();
+        env.remove(vars::CARGO_CFG_CLIPPY).remove(vars::CLIPPY_ARGS);
+        assert!(!BuildEnv::is_clippy());
+
+        env.set(vars::CARGO_CFG_CLIPPY, "");
+        assert!(BuildEnv::is_clippy());
+
+        env.remove(vars::CARGO_CFG_CLIPPY).set(
+            vars::CLIPPY_ARGS,
+            "-D__CLIPPY_HACKERY__warnings__CLIPPY_HACKERY__",
+        );
+        assert!(BuildEnv::is_clippy());
+    }
+
+    #[test]
+    fn miri() {
+        let mut env = TestEnv::new();
+        env.remove(vars::CARGO_CFG_MIRI);
+        assert!(!BuildEnv::is_miri());
+
+        env.set(vars::CARGO_CFG_MIRI, "");
+        assert!(BuildEnv::is_miri());
+    }
+
+    #[test]
+    fn rust_analyzer() {
+        let mut env = TestEnv::new();
+        env.remove(vars::RUSTC_WRAPPER);
+        assert!(!BuildEnv::is_rust_analyzer());
+
+        env.set(
+            vars::RUSTC_WRAPPER,
+            "/home/user/.vscode/extensions/rust-analyzer/server/rust-analyzer",
+        );
+        assert!(BuildEnv::is_rust_analyzer());
+
+        env.set(vars::RUSTC_WRAPPER, "/opt/rust-analyzer/bin/sccache");
+        assert!(!BuildEnv::is_rust_analyzer());
+    }
+}
@@ -0,0 +1,660 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::{Display, Formatter},
+    marker::PhantomData,
+};
+
+use crate::{
+    instruction::{self, quote, Instruction},
+    Instructions,
+};
+
+/// Represents a cfg configured with several distinct values by one build script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgConflict {
+    /// The cfg name
+    pub key: String,
+    /// Every distinct value the cfg was configured with, `None` for the bare flag,
+    /// along with the 1-based position of its first configuration among all cfgs
+    pub values: Vec<(usize, Option<String>)>,
+}
+
+impl Display for CfgConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cfg `{}` is configured with conflicting values:",
+            self.key
+        )?;
+
+        for (i, (position, value)) in self.values.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            match value {
+                Some(value) => write!(f, "{separator}{} (#{position})", quote(value))?,
+                None => write!(f, "{separator}no value (#{position})")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for CfgConflict {}
+
+/// Represents instructions of one build script that contradict each other, as found by [`BuildScript::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstructionConflict {
+    /// A cfg configured with several distinct values
+    Cfg(CfgConflict),
+    /// A library linked with several distinct kinds, such as both `static` and `dylib`
+    LinkKind {
+        /// The library name
+        name: String,
+        /// Every distinct kind the library was linked with, `None` when no kind was given
+        kinds: Vec<Option<String>>,
+    },
+    /// An environment variable set for the compiler to several distinct values
+    Env {
+        /// The variable name
+        var: String,
+        /// Every distinct value the variable was set to
+        values: Vec<String>,
+    },
+}
+
+impl Display for InstructionConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstructionConflict::Cfg(conflict) => write!(f, "{conflict}"),
+            InstructionConflict::LinkKind { name, kinds } => {
+                let kinds = kinds
+                    .iter()
+                    .map(|kind| kind.as_deref().unwrap_or("no kind"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "library `{name}` is linked with conflicting kinds: {kinds}"
+                )
+            }
+            InstructionConflict::Env { var, values } => {
+                let values = values
+                    .iter()
+                    .map(|value| quote(value))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "environment variable `{var}` is set to conflicting values: {values}"
+                )
+            }
+        }
+    }
+}
+
+impl Error for InstructionConflict {}
+
+/// Represents a group of link instructions, moved as a whole by a [`LinkOrder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkGroup {
+    /// `rustc-link-search`
+    Search,
+    /// `rustc-link-lib`
+    Libs,
+    /// `rustc-link-arg` and its variants for specific targets, including `rustc-cdylib-link-arg`
+    Args,
+}
+
+/// Represents the order in which a buffered [`BuildScript`] flushes groups of link instructions,
+/// for linkers sensitive to the order of search paths, libraries and arguments.
+///
+/// Instructions keep their order within a group, and link instructions as a whole keep the positions
+/// they had among the other instructions. Groups left out of the order are flushed after the listed ones.
+/// The default order, search paths, then libraries, then arguments, is the one linkers expect:
+/// a library is only found in the search paths given before it, and arguments such as `--gc-sections`
+/// or `-Wl,--as-needed` often need to follow the libraries they apply to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LinkOrder(Vec<LinkGroup>);
+
+impl LinkOrder {
+    /// Creates an order flushing the groups in the order given.
+    pub fn new(groups: impl IntoIterator<Item = LinkGroup>) -> Self {
+        Self(groups.into_iter().collect())
+    }
+
+    /// Returns the position of the group of `instruction` in the order, or `None` if it isn't a link instruction.
+    fn rank(&self, instruction: &Instruction) -> Option<usize> {
+        let group = match instruction {
+            Instruction::LinkSearch { .. } => LinkGroup::Search,
+            Instruction::LinkLib(_) => LinkGroup::Libs,
+            Instruction::LinkArg(_)
+            | Instruction::LinkArgBin { .. }
+            | Instruction::LinkArgBins(_)
+            | Instruction::LinkArgTests(_)
+            | Instruction::LinkArgExamples(_)
+            | Instruction::CdylibLinkArg(_) => LinkGroup::Args,
+            _ => return None,
+        };
+
+        let rank = self.0.iter().position(|listed| *listed == group);
+        Some(rank.unwrap_or(self.0.len()))
+    }
+}
+
+impl Default for LinkOrder {
+    fn default() -> Self {
+        Self::new([LinkGroup::Search, LinkGroup::Libs, LinkGroup::Args])
+    }
+}
+
+/// Provides control over how the instructions of a build script are emitted.
+///
+/// A buffered `BuildScript` holds back the instructions emitted on the current thread until it's flushed or dropped.
+/// On flush, repeated identical cfgs among the flushed instructions are collapsed into one,
+/// and cfgs configured with several distinct values are reported with a warning, or an error in strict mode.
+/// Cfgs configured with several values on purpose, through [`Rustc::cfg_values`](crate::Rustc::cfg_values)
+/// or [`Rustc::cfg_table`](crate::Rustc::cfg_table), aren't reported.
+#[derive(Debug)]
+pub struct BuildScript {
+    depth: usize,
+    strict: bool,
+    dedup_links: bool,
+    link_order: Option<LinkOrder>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl BuildScript {
+    /// Runs `f`, recording the instructions it emits on the current thread instead of printing them to Cargo.
+    /// Useful for testing build script logic and for tools inspecting what a build script would emit.
+    pub fn collect(f: impl FnOnce()) -> Instructions {
+        instruction::collect(f)
+    }
+
+    /// Starts buffering the instructions emitted on the current thread.
+    pub fn buffered() -> Self {
+        Self {
+            depth: instruction::push_collector(),
+            strict: false,
+            dedup_links: false,
+            link_order: None,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Sets whether conflicting cfgs fail the build with an error rather than a warning.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets whether repeated identical `rustc-link-arg`, `rustc-link-lib` and `rustc-link-search` instructions
+    /// are emitted only once, keeping the first of each.
+    ///
+    /// Don't enable this when a duplicate is intended, such as a static library listed twice to resolve
+    /// circular dependencies between archives, or a linker argument that must be repeated positionally.
+    pub fn dedup_links(mut self, dedup: bool) -> Self {
+        self.dedup_links = dedup;
+        self
+    }
+
+    /// Sets the order in which groups of link instructions are flushed, or `None` to flush them as emitted,
+    /// which is the default.
+    pub fn link_order(mut self, order: impl Into<Option<LinkOrder>>) -> Self {
+        self.link_order = order.into();
+        self
+    }
+
+    /// Checks the instructions buffered so far for ones contradicting each other, without emitting them.
+    ///
+    /// The following are reported, in this order:
+    /// - a cfg configured with several distinct values, unless through the multi-value APIs of [`Rustc`](crate::Rustc);
+    /// - a library linked with several distinct kinds, such as `static=foo` and `dylib=foo`,
+    ///   where a link without a kind counts as a kind of its own;
+    /// - an environment variable set with `rustc-env` to several distinct values.
+    pub fn verify(&self) -> Vec<InstructionConflict> {
+        let instructions = instruction::peek_collected(self.depth);
+        let (_, cfg_conflicts) = dedup_cfgs(instructions.clone());
+
+        let mut link_kinds = BTreeMap::<String, Vec<Option<String>>>::new();
+        let mut env_values = BTreeMap::<String, Vec<String>>::new();
+        for instruction in instructions {
+            match instruction {
+                Instruction::LinkLib(lib) => {
+                    let (kind, name) = split_link_lib(&lib);
+                    let kinds = link_kinds.entry(name.to_owned()).or_default();
+                    let kind = kind.map(str::to_owned);
+                    if !kinds.contains(&kind) {
+                        kinds.push(kind);
+                    }
+                }
+                Instruction::Env { var, value } => {
+                    let values = env_values.entry(var).or_default();
+                    if !values.contains(&value) {
+                        values.push(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let link_conflicts = link_kinds
+            .into_iter()
+            .filter(|(_, kinds)| kinds.len() > 1)
+            .map(|(name, kinds)| InstructionConflict::LinkKind { name, kinds });
+        let env_conflicts = env_values
+            .into_iter()
+            .filter(|(_, values)| values.len() > 1)
+            .map(|(var, values)| InstructionConflict::Env { var, values });
+
+        cfg_conflicts
+            .into_iter()
+            .map(InstructionConflict::Cfg)
+            .chain(link_conflicts)
+            .chain(env_conflicts)
+            .collect()
+    }
+
+    /// Emits the instructions buffered so far, returning the conflicting cfgs found among them.
+    /// In strict mode an error is emitted for each conflict and they're returned as `Err`.
+    pub fn flush(&mut self) -> Result<(), Vec<CfgConflict>> {
+        let mut instructions = instruction::take_collected(self.depth);
+        if self.dedup_links {
+            instructions = dedup_links(instructions);
+        }
+        if let Some(order) = &self.link_order {
+            instructions = reorder_links(instructions, order);
+        }
+        let (instructions, conflicts) = dedup_cfgs(instructions);
+
+        for instruction in instructions {
+            instruction::emit_into(self.depth - 1, instruction);
+        }
+        for conflict in &conflicts {
+            let message = conflict.to_string();
+            let instruction = if self.strict {
+                Instruction::Error(message)
+            } else {
+                Instruction::Warning(message)
+            };
+            instruction::emit_into(self.depth - 1, instruction);
+        }
+
+        if self.strict && !conflicts.is_empty() {
+            Err(conflicts)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Flushes the buffered instructions, then prints `summary` as a final warning, such as the native backend selected.
+    /// Each line of a multi-line summary is printed as a warning of its own.
+    ///
+    /// Finishing is optional: dropping the `BuildScript` flushes it just the same, without a summary.
+    pub fn finish(mut self, summary: impl Display) -> Result<(), Vec<CfgConflict>> {
+        let result = self.flush();
+
+        for line in summary.to_string().lines() {
+            instruction::emit_into(self.depth - 1, Instruction::Warning(line.to_owned()));
+        }
+
+        result
+    }
+}
+
+impl Drop for BuildScript {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        instruction::pop_collector();
+    }
+}
+
+/// Removes repeated identical linker arguments, linked libraries and library search paths, keeping the first of each.
+fn dedup_links(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut seen = BTreeSet::new();
+
+    instructions
+        .into_iter()
+        .filter(|instruction| match instruction {
+            Instruction::LinkArg(_) | Instruction::LinkLib(_) | Instruction::LinkSearch { .. } => {
+                seen.insert(instruction.to_string())
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// Sorts the link instructions by their group in `order`, keeping the positions of link instructions as a whole.
+fn reorder_links(mut instructions: Vec<Instruction>, order: &LinkOrder) -> Vec<Instruction> {
+    let positions = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(position, instruction)| order.rank(instruction).map(|_| position))
+        .collect::<Vec<_>>();
+
+    let mut links = positions
+        .iter()
+        .map(|position| instructions[*position].clone())
+        .collect::<Vec<_>>();
+    links.sort_by_key(|instruction| order.rank(instruction));
+
+    for (position, link) in positions.into_iter().zip(links) {
+        instructions[position] = link;
+    }
+
+    instructions
+}
+
+/// Splits a `rustc-link-lib` value in the `[KIND[:MODIFIERS]=]NAME[:RENAME]` form into its kind and name.
+fn split_link_lib(lib: &str) -> (Option<&str>, &str) {
+    let (kind, name) = match lib.split_once('=') {
+        Some((kind, name)) => (Some(kind.split(':').next().unwrap_or(kind)), name),
+        None => (None, lib),
+    };
+
+    (kind, name.split(':').next().unwrap_or(name))
+}
+
+thread_local! {
+    /// Keys of the cfgs configured with several values on purpose on the current thread.
+    static MULTI_VALUED_CFGS: RefCell<BTreeSet<String>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Marks the cfg `key` as configured with several values on purpose, so that they aren't reported as conflicting.
+pub(crate) fn allow_multiple_values(key: &str) {
+    MULTI_VALUED_CFGS.with_borrow_mut(|keys| keys.insert(key.to_owned()));
+}
+
+/// Removes repeated identical cfgs, keeping the first of each, and finds the cfgs set with several distinct values,
+/// except for the ones marked with [`allow_multiple_values`].
+fn dedup_cfgs(instructions: Vec<Instruction>) -> (Vec<Instruction>, Vec<CfgConflict>) {
+    let mut values = BTreeMap::<String, Vec<(usize, Option<String>)>>::new();
+    let mut position = 0;
+
+    let instructions = instructions
+        .into_iter()
+        .filter(|instruction| {
+            let Instruction::Cfg { key, value } = instruction else {
+                return true;
+            };
+            position += 1;
+
+            let values = values.entry(key.clone()).or_default();
+            if values.iter().any(|(_, seen)| seen == value) {
+                return false;
+            }
+            values.push((position, value.clone()));
+            true
+        })
+        .collect();
+
+    let conflicts = values
+        .into_iter()
+        .filter(|(key, values)| {
+            values.len() > 1 && !MULTI_VALUED_CFGS.with_borrow(|keys| keys.contains(key))
+        })
+        .map(|(key, values)| CfgConflict { key, values })
+        .collect();
+
+    (instructions, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_env::TestEnv, Cargo, InstructionKind, Rustc};
+
+    #[test]
+    fn repeated_flags_are_collapsed() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            let mut script = BuildScript::buffered().strict(true);
+            Rustc::cfg("fast_path", None);
+            Rustc::cfg("fast_path", None);
+            assert_eq!(script.flush(), Ok(()));
+        });
+
+        assert_eq!(instructions.cfgs(), [("fast_path", None)]);
+        assert!(instructions
+            .filter_kind(InstructionKind::Warning)
+            .is_empty());
+    }
+
+    #[test]
+    fn repeated_values_are_collapsed() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            let mut script = BuildScript::buffered();
+            Rustc::cfg("io_backend", "epoll");
+            Rustc::cfg("io_backend", "epoll");
+            assert_eq!(script.flush(), Ok(()));
+        });
+
+        assert_eq!(instructions.cfgs(), [("io_backend", Some("epoll"))]);
+    }
+
+    #[test]
+    fn conflicting_values_are_reported_in_order() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            let mut script = BuildScript::buffered();
+            Rustc::cfg("io_backend", "epoll");
+            Rustc::cfg("unrelated", None);
+            Rustc::cfg("io_backend", "uring");
+            assert_eq!(script.flush(), Ok(()));
+        });
+
+        assert_eq!(
+            instructions.filter_kind(InstructionKind::Warning),
+            [&Instruction::Warning(
+                "cfg `io_backend` is configured with conflicting values: \"epoll\" (#1), \"uring\" (#3)"
+                    .to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn flag_and_value_conflict() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            let mut script = BuildScript::buffered();
+            Rustc::cfg("io_backend", None);
+            Rustc::cfg("io_backend", "epoll");
+            assert_eq!(script.flush(), Ok(()));
+        });
+
+        assert_eq!(
+            instructions.filter_kind(InstructionKind::Warning),
+            [&Instruction::Warning(
+                "cfg `io_backend` is configured with conflicting values: no value (#1), \"epoll\" (#2)"
+                    .to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn strict_mode_fails_on_conflicts() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            let mut script = BuildScript::buffered().strict(true);
+            Rustc::cfg("io_backend", "epoll");
+            Rustc::cfg("io_backend", "uring");
+
+            let conflicts = script.flush().unwrap_err();
+            assert_eq!(
+                conflicts,
+                [CfgConflict {
+                    key: "io_backend".to_owned(),
+                    values: vec![(1, Some("epoll".to_owned())), (2, Some("uring".to_owned()))],
+                }]
+            );
+        });
+
+        assert_eq!(instructions.filter_kind(InstructionKind::Error).len(), 1);
+    }
+
+    #[test]
+    fn multi_valued_cfgs_are_not_conflicts() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            let mut script = BuildScript::buffered().strict(true);
+            Rustc::cfg_values("simd", ["sse2", "avx2"]);
+            Rustc::cfg_table(&[("backend", Some("gl")), ("backend", Some("vulkan"))]);
+            assert_eq!(script.flush(), Ok(()));
+        });
+
+        assert_eq!(instructions.cfgs().len(), 4);
+        assert!(instructions.filter_kind(InstructionKind::Error).is_empty());
+    }
+
+    #[test]
+    fn duplicate_links_are_dropped() {
+        let instructions = BuildScript::collect(|| {
+            let _script = BuildScript::buffered().dedup_links(true);
+            Rustc::link_arg("-Wl,--as-needed");
+            Rustc::link_lib("static=z");
+            Rustc::link_search("/opt/lib", None);
+            Cargo::warning("between");
+            Rustc::link_arg("-Wl,--as-needed");
+            Rustc::link_lib("static=z");
+            Rustc::link_lib("dylib=z");
+            Rustc::link_search("/opt/lib", None);
+            Rustc::link_search("/usr/lib", None);
+        });
+
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-link-arg=-Wl,--as-needed\n\
+             cargo::rustc-link-lib=static=z\n\
+             cargo::rustc-link-search=/opt/lib\n\
+             cargo::warning=between\n\
+             cargo::rustc-link-lib=dylib=z\n\
+             cargo::rustc-link-search=/usr/lib\n"
+        );
+    }
+
+    #[test]
+    fn verify_reports_link_kind_conflicts() {
+        let _env = TestEnv::new();
+        let mut conflicts = Vec::new();
+        let instructions = BuildScript::collect(|| {
+            let script = BuildScript::buffered();
+            Rustc::link_lib("static=ssl");
+            Rustc::link_lib("static=ssl");
+            Rustc::link_lib("static=z");
+            Rustc::link_lib("dylib=ssl");
+            conflicts = script.verify();
+        });
+
+        assert_eq!(
+            conflicts,
+            [InstructionConflict::LinkKind {
+                name: "ssl".to_owned(),
+                kinds: vec![Some("static".to_owned()), Some("dylib".to_owned())],
+            }]
+        );
+        assert_eq!(
+            conflicts[0].to_string(),
+            "library `ssl` is linked with conflicting kinds: static, dylib"
+        );
+        // Verifying doesn't consume the buffered instructions
+        assert_eq!(instructions.link_libs(), ["ssl", "ssl", "z", "ssl"]);
+    }
+
+    #[test]
+    fn verify_accepts_consistent_links() {
+        let _env = TestEnv::new();
+        BuildScript::collect(|| {
+            let script = BuildScript::buffered();
+            Rustc::link_lib("static=ssl");
+            Rustc::link_lib("static=ssl");
+            Rustc::link_lib("dylib=z");
+            Rustc::link_lib("crypto");
+            assert!(script.verify().is_empty());
+        });
+    }
+
+    #[test]
+    fn finish_flushes_before_the_summary() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            let script = BuildScript::buffered();
+            Rustc::cfg("io_backend", "epoll");
+            Rustc::cfg("io_backend", "epoll");
+            assert_eq!(script.finish("backend: epoll\nfeatures: none"), Ok(()));
+        });
+
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-cfg=io_backend=\"epoll\"\n\
+             cargo::warning=backend: epoll\n\
+             cargo::warning=features: none\n"
+        );
+
+        let instructions = BuildScript::collect(|| {
+            let script = BuildScript::buffered().strict(true);
+            Rustc::cfg("io_backend", "epoll");
+            Rustc::cfg("io_backend", "uring");
+            assert_eq!(script.finish("backend: unknown").unwrap_err().len(), 1);
+        });
+
+        let kinds: Vec<_> = instructions.iter().map(Instruction::kind).collect();
+        assert_eq!(kinds.last(), Some(&InstructionKind::Warning));
+        assert_eq!(instructions.filter_kind(InstructionKind::Error).len(), 1);
+        assert_eq!(instructions.filter_kind(InstructionKind::Warning).len(), 1);
+    }
+
+    #[test]
+    fn link_groups_are_flushed_in_order() {
+        let emit = || {
+            Rustc::link_arg("-Wl,--as-needed");
+            Rustc::link_lib("static=z");
+            Cargo::warning("between");
+            Rustc::link_search("/opt/lib", None);
+            Rustc::link_lib("dylib=ssl");
+            Rustc::link_arg("-Wl,--gc-sections");
+            Rustc::link_search("/usr/lib", None);
+        };
+
+        let instructions = BuildScript::collect(|| {
+            let _script = BuildScript::buffered().link_order(LinkOrder::default());
+            emit();
+        });
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-link-search=/opt/lib\n\
+             cargo::rustc-link-search=/usr/lib\n\
+             cargo::warning=between\n\
+             cargo::rustc-link-lib=static=z\n\
+             cargo::rustc-link-lib=dylib=ssl\n\
+             cargo::rustc-link-arg=-Wl,--as-needed\n\
+             cargo::rustc-link-arg=-Wl,--gc-sections\n"
+        );
+
+        let instructions = BuildScript::collect(|| {
+            let _script = BuildScript::buffered()
+                .link_order(LinkOrder::new([LinkGroup::Args, LinkGroup::Search]));
+            emit();
+        });
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-link-arg=-Wl,--as-needed\n\
+             cargo::rustc-link-arg=-Wl,--gc-sections\n\
+             cargo::warning=between\n\
+             cargo::rustc-link-search=/opt/lib\n\
+             cargo::rustc-link-search=/usr/lib\n\
+             cargo::rustc-link-lib=static=z\n\
+             cargo::rustc-link-lib=dylib=ssl\n"
+        );
+
+        let instructions = BuildScript::collect(|| {
+            let _script = BuildScript::buffered().link_order(None);
+            emit();
+        });
+        assert_eq!(
+            instructions.to_string(),
+            BuildScript::collect(emit).to_string()
+        );
+    }
+}
@@ -1,114 +1,2549 @@
 use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
     convert::Infallible,
     env::VarError,
+    ffi::{OsStr, OsString},
+    hash::{Hash, Hasher},
+    io::{self, IsTerminal},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, PoisonError},
 };
 
-macro_rules! define_env_getter {
-    ($($(#[$meta: meta])* $name: ident: $result: ty => $env: literal;)*) => {
-        $(
-            $(#[$meta])*
-            pub fn $name() -> Result<$result, std::env::VarError> {
-                std::env::var($env).map(Into::into)
-            }
-        )*
-    };
-}
+#[cfg(feature = "toml")]
+use crate::ManifestError;
+use crate::{
+    cargo_env::optional,
+    env_var, features,
+    instruction::{self, emit, Instruction},
+    manifest, package, validation, vars, Author, EnvVarError, EnvVarErrorKind, FromEnvVar,
+    InstructionError, LicenseError, LicenseExpr, OptLevel, PackageInfo, Profile, RustcInfo,
+    RustcVersion, TargetCfg, Triple, Version, VersionError,
+};
+
+/// Every environment variable declared with [`Cargo::rerun_if_env_changed`] so far.
+static DECLARED_ENVS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// The keys of every warning printed with [`Cargo::warning_once`] so far.
+static WARNED_KEYS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
 
 /// The `Cargo` struct serves as a utility for interacting with Cargo-specific environment variables and commands.
 pub struct Cargo(Infallible);
 
 impl Cargo {
     /// Specifies to Cargo that a build script should be re-run if the specified file changes.
+    ///
+    /// Cargo reads build script output as UTF-8 and skips lines that aren't, so paths that aren't valid unicode
+    /// can't be watched: on Unix, where paths are arbitrary bytes, and on Windows, where they may contain unpaired
+    /// surrogates, such a path is emitted with invalid sequences replaced by `U+FFFD`.
+    /// Cargo then sees a file that doesn't exist and re-runs the script on every build.
+    /// Use [`Cargo::try_rerun_if_changed`] to detect these paths and watch a parent directory instead.
     pub fn rerun_if_changed(path: impl AsRef<Path>) {
-        let path = path.as_ref().display();
-        println!("cargo::rerun-if-changed={path}");
+        let path = path.as_ref();
+        validation::debug_check(|| validation::line(&path.display().to_string()));
+        emit(Instruction::RerunIfChanged(path.to_owned()));
+    }
+
+    /// Fallible version of [`Cargo::rerun_if_changed`] that rejects paths containing line breaks
+    /// or that aren't valid unicode.
+    pub fn try_rerun_if_changed(path: impl AsRef<Path>) -> Result<(), InstructionError> {
+        let path = path.as_ref();
+        validation::path(path)?;
+        Self::rerun_if_changed(path);
+        Ok(())
+    }
+
+    /// Specifies to Cargo that a build script should be re-run if `build.rs` itself changes.
+    ///
+    /// By default Cargo re-runs a build script whenever any file in the package changes,
+    /// but emitting any `rerun-if-*` instruction replaces that default with the listed inputs only,
+    /// so the script stops being re-run when it's edited unless it declares itself as an input.
+    /// The path is relative to the package root, so packages with a custom `build` path
+    /// should pass it to [`Cargo::rerun_if_changed`] instead.
+    pub fn rerun_if_changed_build_rs() {
+        Self::rerun_if_changed("build.rs");
     }
 
     /// Specifies to Cargo that a build script should be re-run if the specified environment variable changes.
+    /// Each variable is declared only once per process, including by the methods that declare the variables they read,
+    /// such as [`Cargo::tracked_env`].
     pub fn rerun_if_env_changed(env: impl AsRef<str>) {
         let env = env.as_ref();
-        println!("cargo::rerun-if-env-changed={env}");
+        validation::debug_check(|| validation::key(env));
+
+        let newly_declared = DECLARED_ENVS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(env.to_owned());
+        if newly_declared {
+            emit(Instruction::RerunIfEnvChanged(env.to_owned()));
+        }
+    }
+
+    /// Fallible version of [`Cargo::rerun_if_env_changed`] that rejects invalid variable names.
+    pub fn try_rerun_if_env_changed(env: impl AsRef<str>) -> Result<(), InstructionError> {
+        let env = env.as_ref();
+        validation::key(env)?;
+        Self::rerun_if_env_changed(env);
+        Ok(())
     }
 
     /// Prints a warning message during the build process.
     pub fn warning(message: impl AsRef<str>) {
         let message = message.as_ref();
-        println!("cargo::warning={message}");
+        validation::debug_check(|| validation::line(message));
+        emit(Instruction::Warning(message.to_owned()));
+    }
+
+    /// Fallible version of [`Cargo::warning`] that rejects messages containing line breaks.
+    pub fn try_warning(message: impl AsRef<str>) -> Result<(), InstructionError> {
+        let message = message.as_ref();
+        validation::line(message)?;
+        Self::warning(message);
+        Ok(())
+    }
+
+    /// Prints a warning message, unless a warning with the same `key` was already printed by this process.
+    ///
+    /// The keys are only remembered within the process: each run of the build script, such as for another target
+    /// or after a rerun, prints the warning again. Cargo itself shows a build script's warnings once per package
+    /// and replays them when the script isn't rerun, so keying is only needed within a run.
+    pub fn warning_once(key: impl AsRef<str>, message: impl AsRef<str>) {
+        let newly_warned = WARNED_KEYS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.as_ref().to_owned());
+        if newly_warned {
+            Self::warning(message);
+        }
+    }
+
+    /// Prints an error message and makes the build fail once the build script finishes.
+    pub fn error(message: impl AsRef<str>) {
+        let message = message.as_ref();
+        validation::debug_check(|| validation::line(message));
+        emit(Instruction::Error(message.to_owned()));
+    }
+
+    /// Fallible version of [`Cargo::error`] that rejects messages containing line breaks.
+    pub fn try_error(message: impl AsRef<str>) -> Result<(), InstructionError> {
+        let message = message.as_ref();
+        validation::line(message)?;
+        Self::error(message);
+        Ok(())
     }
 
     /// Sets metadata that can be accessed by downstream tools or build scripts.
+    ///
+    /// Cargo only passes metadata to dependents of packages with a `links` key,
+    /// which [`Cargo::check_metadata_links`] checks.
     pub fn metadata(key: impl AsRef<str>, value: impl AsRef<str>) {
         let key = key.as_ref();
         let value = value.as_ref();
-        println!("cargo::metadata={key}={value}");
+        validation::debug_check(|| validation::key(key).and_then(|_| validation::line(value)));
+
+        emit(Instruction::Metadata {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        });
+    }
+
+    /// Fallible version of [`Cargo::metadata`] that rejects invalid keys and values containing line breaks.
+    pub fn try_metadata(
+        key: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<(), InstructionError> {
+        let key = key.as_ref();
+        let value = value.as_ref();
+        validation::key(key)?;
+        validation::line(value)?;
+        Self::metadata(key, value);
+        Ok(())
+    }
+
+    /// Sets metadata to `value` serialized as compact, single-line JSON, to pass structured data to dependent packages.
+    #[cfg(feature = "serde")]
+    pub fn metadata_json(
+        key: impl AsRef<str>,
+        value: &impl serde::Serialize,
+    ) -> Result<(), InstructionError> {
+        let value = serde_json::to_string(value)
+            .map_err(|err| InstructionError::Unserializable(err.to_string()))?;
+        Self::try_metadata(key, value)
     }
 
     /// Fetches the path to the binary executable for a specified binary name from the environment variables.
-    pub fn binary_executable_path(binary_name: impl AsRef<str>) -> Result<PathBuf, VarError> {
+    /// The error names the `CARGO_BIN_EXE_<name>` variable that was looked up.
+    pub fn binary_executable_path(binary_name: impl AsRef<str>) -> Result<PathBuf, EnvVarError> {
         let binary_name = binary_name.as_ref();
-        std::env::var(format!("CARGO_BIN_EXE_{binary_name}")).map(Into::into)
+        PathBuf::read_env_var(format!("{}{binary_name}", vars::CARGO_BIN_EXE_PREFIX))
+    }
+
+    /// Returns the paths to the binary executables of the package by binary name,
+    /// from the `CARGO_BIN_EXE_<name>` environment variables, whose names keep the binary names as is.
+    /// Useful for test harnesses discovering the binaries instead of naming each one.
+    pub fn binary_executables() -> BTreeMap<String, PathBuf> {
+        std::env::vars_os()
+            .filter_map(|(var, path)| {
+                let name = var.to_str()?.strip_prefix(vars::CARGO_BIN_EXE_PREFIX)?;
+                Some((name.to_owned(), PathBuf::from(path)))
+            })
+            .collect()
     }
 
     /// Checks if the package being built is the primary package.
     pub fn is_primary_package() -> bool {
-        std::env::var("CARGO_PRIMARY_PACKAGE").is_ok()
+        std::env::var(vars::CARGO_PRIMARY_PACKAGE).is_ok()
+    }
+
+    /// Returns the `links` key of the package being built from `CARGO_MANIFEST_LINKS`,
+    /// or `None` if the package has none, in which case Cargo doesn't set the variable.
+    /// The key names the native library the package links and prefixes the `DEP_<links>_*` variables
+    /// through which dependents read its metadata.
+    pub fn manifest_links() -> Result<Option<String>, EnvVarError> {
+        match String::read_env_var_opt(vars::CARGO_MANIFEST_LINKS) {
+            Err(err) if err.is_not_present() => Ok(None),
+            links => links,
+        }
+    }
+
+    /// Checks whether the metadata set by the build script is passed to dependents, which requires the package
+    /// to have a `links` key, printing a warning once when it has none. Call it from build scripts setting metadata
+    /// to catch a missing key early, as dependents otherwise only find out that the `DEP_*` variables aren't set.
+    pub fn check_metadata_links() -> Result<bool, EnvVarError> {
+        let has_links = Self::manifest_links()?.is_some();
+        if !has_links {
+            Self::warning_once(
+                "build_instructions::metadata_without_links",
+                "metadata is set, but the package has no `links` key, so it isn't passed to any dependent",
+            );
+        }
+
+        Ok(has_links)
+    }
+
+    /// Checks if the package being built is named `name`, from `CARGO_PKG_NAME`.
+    ///
+    /// Useful for build helpers shared across a workspace to run logic for a single package,
+    /// and combined with [`Cargo::is_primary_package`] to only run it when that package is built directly,
+    /// rather than as a dependency of another one.
+    pub fn is_package(name: &str) -> Result<bool, EnvVarError> {
+        Self::pkg_name().map(|pkg_name| pkg_name == name)
+    }
+
+    /// Checks if the package is being built by docs.rs, which sets the `DOCS_RS` environment variable.
+    /// Useful for skipping native dependencies that can't be built there.
+    ///
+    /// The variable is registered with `rerun-if-env-changed`, like the other detectors of [`BuildEnv`](crate::BuildEnv).
+    ///
+    /// Note that a regular `cargo doc` invocation can't be reliably detected, as Cargo doesn't tell build scripts
+    /// whether the crate is being documented.
+    pub fn is_docs_rs() -> bool {
+        Self::rerun_if_env_changed(vars::DOCS_RS);
+        std::env::var_os(vars::DOCS_RS).is_some()
+    }
+
+    /// Checks if the build script's own auxiliary output, such as progress printed to stderr, should be colored.
+    /// Follows `CARGO_TERM_COLOR`: `always` and `never` are honored as is, while `auto`, an unset variable
+    /// or any other value fall back to checking whether stderr is a terminal.
+    ///
+    /// Instructions such as [`Cargo::warning`] are rendered by Cargo, which applies its own coloring,
+    /// so messages passed to them shouldn't contain ANSI codes.
+    pub fn color_enabled() -> bool {
+        match std::env::var(vars::CARGO_TERM_COLOR).as_deref() {
+            Ok("always") => true,
+            Ok("never") => false,
+            _ => io::stderr().is_terminal(),
+        }
+    }
+
+    /// Reads a scalar value from the `[package.metadata]` table of the package's `Cargo.toml`,
+    /// with `path` being a dotted key relative to that table, such as `mytool.mode` for `[package.metadata.mytool]`.
+    /// Returns `None` if the value isn't present. The build script is re-run when the manifest changes.
+    ///
+    /// The manifest is scanned line by line rather than fully parsed, so only a subset of TOML is supported:
+    /// values must be single-line strings, numbers or booleans, and arrays, inline tables,
+    /// multi-line strings and arrays of tables are ignored.
+    pub fn metadata_value(path: &str) -> io::Result<Option<String>> {
+        let manifest_dir =
+            Self::manifest_dir().map_err(|err| io::Error::new(io::ErrorKind::NotFound, err))?;
+        let manifest_path = manifest_dir.join("Cargo.toml");
+        Self::rerun_if_changed(&manifest_path);
+
+        let contents = std::fs::read_to_string(manifest_path)?;
+        Ok(manifest::find_metadata_value(&contents, path))
+    }
+
+    /// Returns the root directory of the workspace the package belongs to, or the directory of the package
+    /// when it isn't part of a workspace, to reach files shared by a workspace such as a `rust-toolchain.toml`.
+    ///
+    /// Nightly Cargo reports the directory in `CARGO_RUSTC_CURRENT_DIR`, which is used when it's set.
+    /// Otherwise the closest ancestor of `CARGO_MANIFEST_DIR`, including itself, whose `Cargo.toml` has
+    /// a `[workspace]` table is taken, which is what Cargo does unless the manifest sets `package.workspace`.
+    /// Packages inside `CARGO_HOME`, such as registry and git dependencies, are never part of a workspace.
+    pub fn workspace_root() -> Result<PathBuf, EnvVarError> {
+        if let Some(root) = Self::rustc_current_dir_opt()? {
+            return Ok(root);
+        }
+
+        let manifest_dir = Self::manifest_dir()?;
+        if Self::home().is_ok_and(|home| manifest_dir.starts_with(home)) {
+            return Ok(manifest_dir);
+        }
+
+        let root = manifest_dir.ancestors().find(|dir| {
+            std::fs::read_to_string(dir.join("Cargo.toml"))
+                .is_ok_and(|contents| manifest::has_workspace_table(&contents))
+        });
+        Ok(root.unwrap_or(&manifest_dir).to_path_buf())
+    }
+
+    /// Returns the `[package.metadata]` table of the manifest, or an empty table if it has none,
+    /// for packages configuring their build script there. The manifest is read from `CARGO_MANIFEST_PATH`,
+    /// or from `Cargo.toml` in `CARGO_MANIFEST_DIR` with Cargo versions that don't set it,
+    /// and registered with `rerun-if-changed`.
+    #[cfg(feature = "toml")]
+    pub fn package_metadata() -> Result<toml::Table, ManifestError> {
+        read_package_metadata().map(|(_, metadata)| metadata)
+    }
+
+    /// Returns the `[package.metadata.<key>]` table of the manifest, such as `[package.metadata.mytool]`,
+    /// or an empty table if it has none. See [`Cargo::package_metadata`].
+    #[cfg(feature = "toml")]
+    pub fn package_metadata_for(key: &str) -> Result<toml::Table, ManifestError> {
+        let (manifest_path, mut metadata) = read_package_metadata()?;
+
+        match metadata.remove(key) {
+            Some(toml::Value::Table(table)) => Ok(table),
+            Some(_) => Err(ManifestError::NotATable {
+                path: manifest_path,
+                key: format!("package.metadata.{key}"),
+            }),
+            None => Ok(toml::Table::new()),
+        }
+    }
+
+    /// Returns the version of the package, parsed from `CARGO_PKG_VERSION`.
+    pub fn pkg_version_parsed() -> Result<Version, VersionError> {
+        Self::pkg_version()?.parse()
+    }
+
+    /// Returns the version of the package as a [`semver::Version`], parsed from `CARGO_PKG_VERSION`.
+    #[cfg(feature = "semver")]
+    pub fn pkg_version_semver() -> Result<semver::Version, VersionError> {
+        let version = Self::pkg_version()?;
+        semver::Version::parse(&version).map_err(|_| VersionError::Invalid(version))
+    }
+
+    /// Checks whether the version of the package matches the [`semver::VersionReq`] `req`, such as `>=1.2, <2`.
+    #[cfg(feature = "semver")]
+    pub fn pkg_version_matches(req: &str) -> Result<bool, VersionError> {
+        let req = semver::VersionReq::parse(req)
+            .map_err(|_| VersionError::InvalidRequirement(req.to_owned()))?;
+        Ok(req.matches(&Self::pkg_version_semver()?))
+    }
+
+    /// Returns the minimum supported Rust version of the package, parsed from `CARGO_PKG_RUST_VERSION`,
+    /// or `None` when the manifest has no `rust-version`. A missing patch version, as in `1.70`, is treated as `0`.
+    pub fn pkg_rust_version_parsed() -> Result<Option<RustcVersion>, VersionError> {
+        let Some(version) = Self::pkg_rust_version_opt()? else {
+            return Ok(None);
+        };
+
+        RustcVersion::parse(&version)
+            .map(Some)
+            .ok_or(VersionError::Invalid(version))
+    }
+
+    /// Fails the build with an error naming both versions if the Rust compiler, detected through `RUSTC`,
+    /// is older than the minimum supported Rust version of the package, instead of letting it fail
+    /// on the first unsupported syntax. Does nothing if the package doesn't declare one or the compiler's version
+    /// can't be determined. Nightly and beta builds count as the release they precede.
+    ///
+    /// Cargo checks `rust-version` itself, but only for the package being built and not with `--ignore-rust-version`.
+    pub fn assert_msrv() {
+        let msrv = match Self::pkg_rust_version_parsed() {
+            Ok(Some(msrv)) => msrv,
+            Ok(None) => return,
+            Err(err) => {
+                Self::warning(format!(
+                    "couldn't check the minimum supported Rust version: {err}"
+                ));
+                return;
+            }
+        };
+
+        let package = Self::pkg_name().unwrap_or_else(|_| "this package".to_owned());
+        if let Some(message) = msrv_error(&package, msrv, RustcInfo::version()) {
+            instruction::fail(message);
+        }
+    }
+
+    /// Returns the major version of the package, parsed from `CARGO_PKG_VERSION_MAJOR`.
+    pub fn pkg_version_major_parsed() -> Result<u64, VersionError> {
+        parse_version_number(Self::pkg_version_major()?)
+    }
+
+    /// Returns the minor version of the package, parsed from `CARGO_PKG_VERSION_MINOR`.
+    pub fn pkg_version_minor_parsed() -> Result<u64, VersionError> {
+        parse_version_number(Self::pkg_version_minor()?)
+    }
+
+    /// Returns the patch version of the package, parsed from `CARGO_PKG_VERSION_PATCH`.
+    pub fn pkg_version_patch_parsed() -> Result<u64, VersionError> {
+        parse_version_number(Self::pkg_version_patch()?)
+    }
+
+    /// Returns the pre-release version of the package from `CARGO_PKG_VERSION_PRE`, or `None` for a release.
+    pub fn pkg_version_pre_parsed() -> Result<Option<String>, EnvVarError> {
+        Self::pkg_version_pre().map(|pre| Some(pre).filter(|pre| !pre.is_empty()))
+    }
+
+    /// Returns the dot-separated identifiers of the pre-release version of the package from `CARGO_PKG_VERSION_PRE`,
+    /// such as `["rc", "1"]` for `rc.1`, or an empty list for a release.
+    pub fn pkg_version_pre_parts() -> Result<Vec<String>, EnvVarError> {
+        Self::pkg_version_pre_parsed().map(|pre| {
+            pre.map(|pre| pre.split('.').map(str::to_owned).collect())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Returns the authors of the package, parsed from `CARGO_PKG_AUTHORS`.
+    pub fn pkg_authors_parsed() -> Result<Vec<Author>, EnvVarError> {
+        Self::pkg_authors().map(|authors| package::parse_authors(&authors))
+    }
+
+    /// Reads every `CARGO_PKG_*` variable into a [`PackageInfo`], with the version and the authors parsed,
+    /// failing if the name or the version isn't set or the version isn't valid.
+    /// Fields omitted from the manifest, which Cargo sets to an empty string, are `None`.
+    pub fn package_info() -> Result<PackageInfo, VersionError> {
+        Ok(PackageInfo {
+            name: Self::pkg_name()?,
+            version: Self::pkg_version_parsed()?,
+            authors: optional(Self::pkg_authors_opt())?
+                .map(|authors| package::parse_authors(&authors))
+                .unwrap_or_default(),
+            description: optional(Self::pkg_description_opt())?,
+            homepage: optional(Self::pkg_homepage_opt())?,
+            repository: optional(Self::pkg_repository_opt())?,
+            license: optional(Self::pkg_license_opt())?,
+            license_file: optional(Self::pkg_license_file_opt())?,
+            readme: optional(Self::pkg_readme_opt())?,
+            rust_version: optional(Self::pkg_rust_version_opt())?,
+        })
+    }
+
+    /// Returns the license expression of the package, parsed from `CARGO_PKG_LICENSE`,
+    /// or `None` when the manifest has no `license`.
+    pub fn pkg_license_parsed() -> Result<Option<LicenseExpr>, LicenseError> {
+        Self::pkg_license_opt()?
+            .map(|license| license.parse())
+            .transpose()
+    }
+
+    /// Returns the triple of the host the build script runs on, from the `HOST` environment variable.
+    /// Unlike the target, the host isn't described by `CARGO_CFG_*` variables, so the triple is the only source.
+    pub fn host_triple_parsed() -> Result<Triple, EnvVarError> {
+        Self::host().map(|host| Triple::parse(&host))
+    }
+
+    /// Returns the triple of the target being compiled for, from the `TARGET` environment variable.
+    pub fn target_triple_parsed() -> Result<Triple, EnvVarError> {
+        Self::target().map(|target| Triple::parse(&target))
+    }
+
+    /// Returns the target triple, falling back to the host triple when `TARGET` isn't set,
+    /// as some invocations outside of Cargo only set one of them.
+    pub fn target_or_host() -> Result<String, EnvVarError> {
+        match Self::target() {
+            Err(err) if err.is_not_present() => Self::host(),
+            target => target,
+        }
+    }
+
+    /// Returns the runner configured for the target through `CARGO_TARGET_<triple>_RUNNER`, with the triple uppercased
+    /// and `-` and `.` replaced by `_`, as a program followed by its leading arguments, such as `["qemu-aarch64", "-L", "/usr"]`.
+    /// The value is split into words like a POSIX shell does, without expansions: single and double quotes group words
+    /// with spaces, such as `wine "C:/Program Files/app.exe"`, and a backslash escapes the next character,
+    /// so Windows paths need forward slashes or single quotes. A quote left open is reported as an invalid value.
+    /// The variable is registered with `rerun-if-env-changed`.
+    ///
+    /// Runners configured in `.cargo/config.toml` rather than the environment aren't visible to build scripts.
+    pub fn target_runner() -> Result<Option<Vec<String>>, EnvVarError> {
+        let target = Self::target()?.to_uppercase().replace(['-', '.'], "_");
+        let var = format!("CARGO_TARGET_{target}_RUNNER");
+        Self::rerun_if_env_changed(&var);
+
+        let runner = match String::read_env_var_opt(var.clone()) {
+            Err(err) if err.is_not_present() => None,
+            runner => runner?,
+        };
+
+        runner
+            .map(|runner| {
+                split_shell_words(&runner).ok_or_else(|| EnvVarError {
+                    name: var.into(),
+                    kind: EnvVarErrorKind::Invalid {
+                        value: runner,
+                        reason: "it has an unterminated quote or escape".to_owned(),
+                    },
+                })
+            })
+            .transpose()
+    }
+
+    /// Creates a command running `program`, a binary compiled for the target, through [`Cargo::target_runner`]
+    /// when one is configured, like `cargo run` does, or directly otherwise.
+    pub fn target_runner_command(program: impl AsRef<OsStr>) -> Result<Command, EnvVarError> {
+        let runner = Self::target_runner()?.unwrap_or_default();
+        let Some((runner, args)) = runner.split_first() else {
+            return Ok(Command::new(program));
+        };
+
+        let mut command = Command::new(runner);
+        command.args(args).arg(program);
+        Ok(command)
+    }
+
+    /// Returns the operating system of the target, such as `linux` or `windows`.
+    /// Shortcut for [`TargetCfg::target_os`], which reads the other `CARGO_CFG_*` variables.
+    pub fn target_os() -> Result<String, EnvVarError> {
+        TargetCfg::target_os()
+    }
+
+    /// Returns the CPU architecture of the target, such as `x86_64` or `aarch64`.
+    /// Shortcut for [`TargetCfg::target_arch`].
+    pub fn target_arch() -> Result<String, EnvVarError> {
+        TargetCfg::target_arch()
+    }
+
+    /// Returns the file descriptors of Cargo's jobserver pipe as `(read, write)`,
+    /// parsed from the `--jobserver-auth` (or legacy `--jobserver-fds`) argument in [`Cargo::makeflags`].
+    ///
+    /// This only applies to Unix, where the jobserver is a pipe inherited by the build script.
+    /// On Windows the argument names a semaphore instead, and newer `make` versions may use a named FIFO
+    /// (`fifo:PATH`); in both cases `None` is returned. Every token read from the pipe must be written back,
+    /// even when the build script fails, or the whole build can deadlock.
+    pub fn jobserver_fds() -> Option<(i32, i32)> {
+        parse_jobserver_fds(&Self::makeflags().ok()??)
+    }
+
+    /// Returns the flags for `make`-compatible tools from `CARGO_MAKEFLAGS`, including the `--jobserver-auth` argument
+    /// of Cargo's jobserver, or `None` if Cargo doesn't share a jobserver with the build script.
+    pub fn makeflags() -> Result<Option<String>, EnvVarError> {
+        match String::read_env_var(vars::CARGO_MAKEFLAGS) {
+            Err(err) if err.is_not_present() => Ok(None),
+            makeflags => makeflags.map(Some),
+        }
+    }
+
+    /// Sets `MAKEFLAGS` for `command` to [`Cargo::makeflags`], so that a child build system such as `make`
+    /// shares the job slots of Cargo's jobserver instead of running its own jobs on top. Returns whether it was set.
+    ///
+    /// The jobserver is only reachable if its pipe is inherited by the child, which is the case for the descriptors
+    /// Cargo passes to the build script. With the `jobserver` feature, prefer `configure_make` of `Cargo::jobserver`,
+    /// which also takes care of that, or [`Cargo::command`].
+    pub fn inherit_makeflags(command: &mut Command) -> bool {
+        match Self::makeflags() {
+            Ok(Some(makeflags)) => {
+                command.env("MAKEFLAGS", makeflags);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Creates a command running `program` from the package directory, `CARGO_MANIFEST_DIR`,
+    /// that shares the job slots of Cargo's jobserver, for child build tools such as `make`, `ninja` or code generators.
+    ///
+    /// The jobserver is passed through `MAKEFLAGS` like [`Cargo::inherit_makeflags`] does. With the `jobserver` feature,
+    /// it's configured through `Cargo::jobserver` instead, which also keeps its descriptors open in the child.
+    pub fn command(program: impl AsRef<OsStr>) -> Command {
+        let mut command = Command::new(program);
+        if let Ok(manifest_dir) = Self::manifest_dir() {
+            command.current_dir(manifest_dir);
+        }
+
+        #[cfg(feature = "jobserver")]
+        if let Ok(client) = Self::jobserver() {
+            client.configure_make(&mut command);
+            return command;
+        }
+
+        Self::inherit_makeflags(&mut command);
+        command
+    }
+
+    /// Connects to Cargo's jobserver, to acquire a token before running each job beyond the first one
+    /// the build script implicitly holds. The connection is made once per process and shared by every call.
+    ///
+    /// Fails if Cargo doesn't share a jobserver with the build script or its descriptors weren't inherited,
+    /// such as when the build script is run outside of Cargo.
+    #[cfg(feature = "jobserver")]
+    pub fn jobserver() -> io::Result<jobserver::Client> {
+        static CLIENT: std::sync::OnceLock<Result<jobserver::Client, String>> =
+            std::sync::OnceLock::new();
+
+        CLIENT
+            .get_or_init(|| {
+                // SAFETY: the client is created once per process from the descriptors Cargo passed to the build script,
+                // which this crate never closes
+                let from_env = unsafe { jobserver::Client::from_env_ext(true) };
+                from_env.client.map_err(|err| err.to_string())
+            })
+            .clone()
+            .map_err(|err| {
+                io::Error::other(format!("couldn't connect to Cargo's jobserver: {err}"))
+            })
+    }
+
+    /// Reads the environment variable `var` and converts its value to `T`, such as a number or a [`bool`].
+    /// A value that can't be converted is reported with the variable name, the value and the reason.
+    /// Unlike [`Cargo::tracked_env`], the variable isn't registered with `rerun-if-env-changed`.
+    pub fn var<T: FromEnvVar>(var: impl AsRef<str>) -> Result<T, EnvVarError> {
+        T::read_env_var(var.as_ref().to_owned())
+    }
+
+    /// Like [`Cargo::var`], but returns `None` when the variable isn't set.
+    pub fn var_opt<T: FromEnvVar>(var: impl AsRef<str>) -> Result<Option<T>, EnvVarError> {
+        match Self::var(var) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.is_not_present() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the number of jobs the build script may run in parallel, from the `NUM_JOBS` environment variable,
+    /// with `0` treated as `1`.
+    ///
+    /// This is only a hint matching the `-j` setting of Cargo: it isn't coordinated with Cargo's jobserver,
+    /// so other build scripts and compiler invocations may be running at the same time.
+    /// See [`Cargo::jobserver_fds`] to share Cargo's job slots instead.
+    pub fn num_jobs() -> Result<usize, EnvVarError> {
+        usize::read_env_var(vars::NUM_JOBS).map(|jobs| jobs.max(1))
+    }
+
+    /// Like [`Cargo::num_jobs`], but falls back to [`std::thread::available_parallelism`] when `NUM_JOBS` isn't set
+    /// or is invalid, such as when the build script is run outside of Cargo for debugging, and to `1` if that fails too.
+    pub fn num_jobs_or_available() -> usize {
+        Self::num_jobs()
+            .unwrap_or_else(|_| std::thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+
+    /// Checks if debug info is enabled for the profile the package is built with, from the `DEBUG` environment variable.
+    /// Accepts `true` and `false` as well as the debug info levels, with any level other than `0` or `none` enabling it.
+    pub fn debug_info() -> Result<bool, EnvVarError> {
+        let debug = String::read_env_var(vars::DEBUG)?;
+
+        match debug.as_str() {
+            "true"
+            | "1"
+            | "2"
+            | "limited"
+            | "full"
+            | "line-tables-only"
+            | "line-directives-only" => Ok(true),
+            "false" | "0" | "none" => Ok(false),
+            _ => Err(EnvVarError {
+                name: vars::DEBUG.into(),
+                kind: EnvVarErrorKind::Invalid {
+                    value: debug,
+                    reason: "expected `true`, `false` or a debug info level".to_owned(),
+                },
+            }),
+        }
+    }
+
+    /// Clears the values of environment variables cached by the getters, by [`FromEnvVar::read_env_var`] and by
+    /// [`Features::enabled`](crate::Features::enabled), so that they're read again on next access.
+    /// Only needed when the process changes the variables itself, such as in tests; has no effect with the `no-cache` feature.
+    pub fn invalidate_cache() {
+        env_var::invalidate_cache();
+        features::invalidate_cache();
+    }
+
+    /// Returns the extra flags the crate is compiled with, decoded from `CARGO_ENCODED_RUSTFLAGS`,
+    /// whose flags are separated by `0x1f` so that they can contain spaces. An empty variable yields no flags.
+    pub fn encoded_rustflags() -> Result<Vec<String>, EnvVarError> {
+        String::read_env_var(vars::CARGO_ENCODED_RUSTFLAGS).map(|flags| {
+            flags
+                .split('\x1f')
+                .filter(|flag| !flag.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+    }
+
+    /// Like [`Cargo::encoded_rustflags`], but falls back to splitting `RUSTFLAGS` on whitespace
+    /// when `CARGO_ENCODED_RUSTFLAGS` isn't set, such as when the build script is run outside of Cargo.
+    pub fn rustflags() -> Result<Vec<String>, EnvVarError> {
+        match Self::encoded_rustflags() {
+            Err(err) if err.is_not_present() => String::read_env_var(vars::RUSTFLAGS)
+                .map(|flags| flags.split_whitespace().map(str::to_owned).collect()),
+            flags => flags,
+        }
+    }
+
+    /// Returns the target features enabled or disabled by `-C target-feature` in `flags`, such as `+avx2` or `-sse4.1`,
+    /// in order. Both the `-C target-feature=...` and `-Ctarget-feature=...` forms are recognized.
+    pub fn rustflags_target_features(flags: &[String]) -> Vec<String> {
+        option_values(flags, "-C", "-C")
+            .filter_map(|value| value.strip_prefix("target-feature="))
+            .flat_map(|features| features.split(','))
+            .filter(|feature| !feature.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Returns the cfgs set by `--cfg` in `flags`, such as `tokio_unstable` or `feature="foo"`, in order.
+    /// Both the `--cfg value` and `--cfg=value` forms are recognized.
+    pub fn rustflags_cfgs(flags: &[String]) -> Vec<String> {
+        option_values(flags, "--cfg", "--cfg=")
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Returns the wrapper Cargo runs the Rust compiler through, from `RUSTC_WRAPPER`, or `None` if there's none.
+    pub fn rustc_wrapper() -> Option<PathBuf> {
+        non_empty_path(vars::RUSTC_WRAPPER)
+    }
+
+    /// Returns the wrapper Cargo runs the Rust compiler through for workspace members, from `RUSTC_WORKSPACE_WRAPPER`,
+    /// or `None` if there's none. Cargo only sets it for build scripts of workspace members.
+    pub fn rustc_workspace_wrapper() -> Option<PathBuf> {
+        non_empty_path(vars::RUSTC_WORKSPACE_WRAPPER)
     }
 
-    define_env_getter! {
+    /// Returns the linker the Rust compiler is configured to use for the target, from `RUSTC_LINKER`,
+    /// or `None` if the default linker is used.
+    pub fn rustc_linker() -> Option<PathBuf> {
+        non_empty_path(vars::RUSTC_LINKER)
+    }
+
+    /// Returns the raw value of the environment variable `var`, or `None` if it isn't set.
+    /// Prefer it over the string getters for values that needn't be valid unicode, such as paths on Unix,
+    /// which are passed on to other programs or the file system without being inspected.
+    pub fn env_os(var: impl AsRef<str>) -> Option<OsString> {
+        std::env::var_os(var.as_ref())
+    }
+
+    /// Like [`Cargo::env_os`], but returns the value as a path.
+    pub fn env_os_path(var: impl AsRef<str>) -> Option<PathBuf> {
+        Self::env_os(var).map(Into::into)
+    }
+
+    /// Returns Cargo's home directory, where it keeps the registry cache and installed binaries.
+    ///
+    /// `CARGO_HOME` is used when it's set, see [`Cargo::home_explicit`]. Otherwise the default is derived like Cargo does:
+    /// `.cargo` in the home directory of the user, from `USERPROFILE` on Windows and `HOME` elsewhere,
+    /// failing with the error of that variable if it isn't set either.
+    pub fn home() -> Result<PathBuf, EnvVarError> {
+        if let Some(home) = Self::home_explicit()? {
+            return Ok(home);
+        }
+
+        let user_home = if cfg!(windows) {
+            vars::USERPROFILE
+        } else {
+            vars::HOME
+        };
+        Ok(PathBuf::read_env_var(user_home)?.join(".cargo"))
+    }
+
+    /// Returns Cargo's home directory from `CARGO_HOME` if it's set and non-empty, and `None` when [`Cargo::home`]
+    /// falls back to the default. Cargo passes the variable through to build scripts unchanged,
+    /// so a relative path is relative to the directory Cargo was invoked from, not to the package.
+    pub fn home_explicit() -> Result<Option<PathBuf>, EnvVarError> {
+        match PathBuf::read_env_var_opt(vars::CARGO_HOME) {
+            Err(err) if err.is_not_present() => Ok(None),
+            home => home,
+        }
+    }
+
+    /// Reads the environment variable `var` and registers it with `rerun-if-env-changed`,
+    /// so that the build script is re-run when its value changes.
+    pub fn tracked_env(var: impl AsRef<str>) -> Result<String, EnvVarError> {
+        let var = var.as_ref();
+        Self::rerun_if_env_changed(var);
+        String::read_env_var(var.to_owned())
+    }
+
+    /// Returns the value of the environment variable `var`, or `default` if it isn't set.
+    /// A value that isn't valid unicode is replaced by `default` too, with a warning.
+    pub fn env_or(var: impl AsRef<str>, default: impl Into<String>) -> String {
+        let var = var.as_ref();
+
+        match std::env::var(var) {
+            Ok(value) => value,
+            Err(VarError::NotPresent) => default.into(),
+            Err(VarError::NotUnicode(_)) => {
+                let default = default.into();
+                Self::warning(format!(
+                    "`{var}` isn't valid unicode, using the default {default:?}"
+                ));
+                default
+            }
+        }
+    }
+
+    /// Like [`Cargo::env_or`], but also registers `var` with `rerun-if-env-changed`.
+    pub fn tracked_env_or(var: impl AsRef<str>, default: impl Into<String>) -> String {
+        let var = var.as_ref();
+        Self::rerun_if_env_changed(var);
+        Self::env_or(var, default)
+    }
+
+    /// Reads the list of paths in the environment variable `var`, such as `PKG_CONFIG_PATH`,
+    /// split with the platform separator (`:` on Unix, `;` on Windows). An empty variable yields an empty list.
+    /// Like [`Cargo::tracked_env`], the variable is registered with `rerun-if-env-changed`.
+    pub fn env_path_list(var: impl AsRef<str>) -> Result<Vec<PathBuf>, EnvVarError> {
+        let var = var.as_ref();
+        Self::rerun_if_env_changed(var);
+
+        let paths = std::env::var_os(var)
+            .ok_or_else(|| EnvVarError::new(var.to_owned(), VarError::NotPresent))?;
+        if paths.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Ok(std::env::split_paths(&paths).collect())
+    }
+
+    /// Returns `OUT_DIR` canonicalized, with symlinks and relative components resolved.
+    ///
+    /// Unlike [`Cargo::out_dir`], which returns the path exactly as Cargo passed it, this touches the filesystem
+    /// and fails if the directory doesn't exist. Prefer it when comparing `OUT_DIR` against other canonicalized paths,
+    /// since an unresolved symlink makes otherwise equal paths compare as different.
+    pub fn out_dir_canonical() -> io::Result<PathBuf> {
+        let out_dir =
+            Self::out_dir().map_err(|err| io::Error::new(io::ErrorKind::NotFound, err))?;
+        out_dir.canonicalize()
+    }
+
+    /// Computes a fingerprint of `inputs` from the size and the modification time of each file,
+    /// with missing files fingerprinted as such. See [`Cargo::inputs_changed`].
+    pub fn fingerprint(inputs: &[&Path]) -> io::Result<u64> {
+        let mut hasher = DefaultHasher::new();
+
+        for input in inputs {
+            input.hash(&mut hasher);
+            match std::fs::metadata(input) {
+                Ok(metadata) => Some((metadata.len(), metadata.modified()?)).hash(&mut hasher),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => None::<()>.hash(&mut hasher),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(hasher.finish())
+    }
+
+    /// Checks whether `inputs` changed since the last call with the same list of paths, comparing their
+    /// [`Cargo::fingerprint`] against the one stored under `OUT_DIR/.build_instructions/fingerprints`,
+    /// to skip expensive regeneration when they didn't. The first call for a list of paths reports a change.
+    ///
+    /// This is best-effort: a file rewritten with the same size within the granularity of modification times,
+    /// which is as coarse as 2 seconds on some file systems, isn't detected.
+    pub fn inputs_changed(inputs: &[&Path]) -> io::Result<bool> {
+        let out_dir =
+            Self::out_dir().map_err(|err| io::Error::new(io::ErrorKind::NotFound, err))?;
+        let mut hasher = DefaultHasher::new();
+        inputs.hash(&mut hasher);
+        let marker_path = out_dir
+            .join(".build_instructions")
+            .join("fingerprints")
+            .join(format!("{:016x}", hasher.finish()));
+
+        let fingerprint = format!("{:016x}", Self::fingerprint(inputs)?);
+        if std::fs::read_to_string(&marker_path).is_ok_and(|stored| stored == fingerprint) {
+            return Ok(false);
+        }
+
+        if let Some(parent) = marker_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(marker_path, fingerprint)?;
+        Ok(true)
+    }
+
+    /// Returns the name of the directory of the profile being built, recovered from the path of `OUT_DIR`.
+    /// Unlike `PROFILE`, which is only ever `debug` or `release`, it names custom profiles,
+    /// such as `bench-lto` for `[profile.bench-lto]`, while the `dev` profile is still named `debug`.
+    ///
+    /// This is a heuristic: it relies on `OUT_DIR` following Cargo's `<profile>/build/<package>-<hash>/out` layout,
+    /// which isn't guaranteed, and fails with [`EnvVarErrorKind::Invalid`] when it doesn't.
+    pub fn profile_dir_name() -> Result<String, EnvVarError> {
+        let out_dir = Self::out_dir()?;
+
+        profile_dir(&out_dir)
+            .and_then(|profile_dir| profile_dir.file_name()?.to_str())
+            .map(str::to_owned)
+            .ok_or_else(|| out_dir_layout_error(&out_dir))
+    }
+
+    /// Returns the directory of the profile being built, where Cargo places the final artifacts,
+    /// recovered from the path of `OUT_DIR` like [`Cargo::profile_dir_name`]. It's `<target-dir>/<profile>`,
+    /// or `<target-dir>/<triple>/<profile>` when building with `--target`.
+    ///
+    /// Cargo doesn't support build scripts writing outside of `OUT_DIR`, but placing files next to the final binary,
+    /// such as a bundled `.dll`, is a common need. Such files aren't cleaned up or tracked by Cargo,
+    /// and the layout may change, in which case an error is returned rather than a wrong path.
+    pub fn target_profile_dir() -> Result<PathBuf, EnvVarError> {
+        let out_dir = Self::out_dir()?;
+
+        profile_dir(&out_dir)
+            .map(Path::to_path_buf)
+            .ok_or_else(|| out_dir_layout_error(&out_dir))
+    }
+
+    /// Returns the target directory when the user set it through `CARGO_TARGET_DIR` or `CARGO_BUILD_TARGET_DIR`,
+    /// in this order of precedence, and `None` otherwise. Both variables are registered with `rerun-if-env-changed`.
+    ///
+    /// Unlike [`Cargo::target_dir_guess`], the value is authoritative when set. Target directories configured
+    /// in `.cargo/config.toml` or with `--target-dir` aren't visible to build scripts, though.
+    pub fn target_dir_env() -> Option<PathBuf> {
+        [vars::CARGO_TARGET_DIR, vars::CARGO_BUILD_TARGET_DIR]
+            .into_iter()
+            .find_map(|var| {
+                Self::rerun_if_env_changed(var);
+                non_empty_path(var)
+            })
+    }
+
+    /// Guesses the target directory from the path of `OUT_DIR`, as the parent of [`Cargo::target_profile_dir`],
+    /// skipping the directory named after the target triple when building with `--target`.
+    ///
+    /// This is a heuristic relying on Cargo's layout of the target directory, which isn't guaranteed.
+    /// Prefer [`Cargo::target_dir_env`] when it returns a value.
+    pub fn target_dir_guess() -> Result<PathBuf, EnvVarError> {
+        let profile_dir = Self::target_profile_dir()?;
+        let parent = profile_dir
+            .parent()
+            .ok_or_else(|| out_dir_layout_error(&Self::out_dir().unwrap_or_default()))?;
+
+        let is_triple_dir = parent
+            .file_name()
+            .is_some_and(|name| Self::target().is_ok_and(|target| name == target.as_str()));
+        match parent.parent() {
+            Some(target_dir) if is_triple_dir => Ok(target_dir.to_path_buf()),
+            _ => Ok(parent.to_path_buf()),
+        }
+    }
+
+    /// Returns `CARGO_MANIFEST_DIR` canonicalized, with symlinks and relative components resolved.
+    ///
+    /// Cargo already passes an absolute path, but not necessarily a canonical one, such as when the package is reached
+    /// through a symlink. Prefer it when checking whether canonicalized paths lie inside the package,
+    /// as [`Path::starts_with`] compares components literally. Unlike [`Cargo::manifest_dir`],
+    /// this touches the filesystem and fails if the directory can't be resolved.
+    pub fn manifest_dir_abs() -> io::Result<PathBuf> {
+        let manifest_dir =
+            Self::manifest_dir().map_err(|err| io::Error::new(io::ErrorKind::NotFound, err))?;
+        manifest_dir.canonicalize()
+    }
+
+    /// Returns a path in `OUT_DIR` named after a hash of `inputs`, to give generated files stable names
+    /// derived from what they're generated from, so that unchanged inputs can reuse an existing file.
+    ///
+    /// The name is 16 hexadecimal digits of a [`DefaultHasher`] hash, which isn't cryptographic
+    /// and may change between Rust versions, so it's only suited to caching within a build directory.
+    /// Each input is hashed with its length, so `[b"ab", b"c"]` and `[b"a", b"bc"]` yield different names.
+    /// The file isn't created.
+    pub fn out_dir_hash(inputs: &[&[u8]]) -> Result<PathBuf, EnvVarError> {
+        let mut hasher = DefaultHasher::new();
+        inputs.hash(&mut hasher);
+
+        Ok(Self::out_dir()?.join(format!("{:016x}", hasher.finish())))
+    }
+
+    env_getters! {
         /// Path to the `cargo` binary performing the build
-        binary_path: PathBuf => "CARGO";
+        binary_path: PathBuf => vars::CARGO;
         /// The directory containing the manifest of your package
-        manifest_dir: PathBuf => "CARGO_MANIFEST_DIR";
+        manifest_dir: PathBuf => vars::CARGO_MANIFEST_DIR;
         /// The path to the manifest of your package
-        manifest_path: PathBuf => "CARGO_MANIFEST_PATH";
+        manifest_path: PathBuf => vars::CARGO_MANIFEST_PATH;
         /// The full version of your package
-        pkg_version: String => "CARGO_PKG_VERSION";
+        pkg_version: String => vars::CARGO_PKG_VERSION;
         /// The major version of your package
-        pkg_version_major: String => "CARGO_PKG_VERSION_MAJOR";
+        pkg_version_major: String => vars::CARGO_PKG_VERSION_MAJOR;
         /// The minor version of your package
-        pkg_version_minor: String => "CARGO_PKG_VERSION_MINOR";
+        pkg_version_minor: String => vars::CARGO_PKG_VERSION_MINOR;
         /// The patch version of your package
-        pkg_version_patch: String => "CARGO_PKG_VERSION_PATCH";
+        pkg_version_patch: String => vars::CARGO_PKG_VERSION_PATCH;
         /// The pre-release version of your package
-        pkg_version_pre: String => "CARGO_PKG_VERSION_PRE";
+        pkg_version_pre: String => vars::CARGO_PKG_VERSION_PRE;
         /// Colon separated list of authors from the manifest of your package
-        pkg_authors: String => "CARGO_PKG_AUTHORS";
+        pkg_authors | pkg_authors_opt: String => vars::CARGO_PKG_AUTHORS;
         /// The name of your package
-        pkg_name: String => "CARGO_PKG_NAME";
+        pkg_name: String => vars::CARGO_PKG_NAME;
         /// The description from the manifest of your package
-        pkg_description: String => "CARGO_PKG_DESCRIPTION";
+        pkg_description | pkg_description_opt: String => vars::CARGO_PKG_DESCRIPTION;
         /// The home page from the manifest of your package
-        pkg_homepage: String => "CARGO_PKG_HOMEPAGE";
+        pkg_homepage | pkg_homepage_opt: String => vars::CARGO_PKG_HOMEPAGE;
         /// The repository from the manifest of your package
-        pkg_repository: String => "CARGO_PKG_REPOSITORY";
+        pkg_repository | pkg_repository_opt: String => vars::CARGO_PKG_REPOSITORY;
         /// The license from the manifest of your package
-        pkg_license: String => "CARGO_PKG_LICENSE";
+        pkg_license | pkg_license_opt: String => vars::CARGO_PKG_LICENSE;
         /// The license file from the manifest of your package
-        pkg_license_file: PathBuf => "CARGO_PKG_LICENSE_FILE";
+        pkg_license_file | pkg_license_file_opt: PathBuf => vars::CARGO_PKG_LICENSE_FILE;
         /// The Rust version from the manifest of your package.
         /// Note that this is the minimum Rust version supported by the package, not the current Rust version
-        pkg_rust_version: String => "CARGO_PKG_RUST_VERSION";
+        pkg_rust_version | pkg_rust_version_opt: String => vars::CARGO_PKG_RUST_VERSION;
         /// Path to the README file of your package
-        pkg_readme: PathBuf => "CARGO_PKG_README";
+        pkg_readme | pkg_readme_opt: PathBuf => vars::CARGO_PKG_README;
         /// The name of the crate that is currently being compiled.
         /// It is the name of the Cargo target with - converted to _,
         /// such as the name of the library, binary, example, integration test, or benchmark
-        crate_name: String => "CARGO_CRATE_NAME";
+        crate_name: String => vars::CARGO_CRATE_NAME;
         /// The name of the binary that is currently being compiled.
         /// Only set for binaries or binary examples.
         /// This name does not include any file extension, such as .exe
-        bin_name: String => "CARGO_BIN_NAME";
+        bin_name ? bin_name_opt: String => vars::CARGO_BIN_NAME;
         /// If the package has a build script, this is set to the folder where the build script should place its output.
         /// See below for more information. (Only set during compilation.)
-        out_dir: PathBuf => "OUT_DIR";
+        out_dir ? out_dir_opt: PathBuf => vars::OUT_DIR;
+        /// The target triple being compiled for, such as `x86_64-unknown-linux-gnu`
+        target: String => vars::TARGET;
+        /// The triple of the host running the build script.
+        /// Differs from the target when cross-compiling
+        host: String => vars::HOST;
         /// Only set when building integration test or benchmark code.
         /// This is a path to a directory inside the target directory
         /// where integration tests or benchmarks are free to put any data needed by the tests/benches.
         /// Cargo initially creates this directory but doesn’t manage its content in any way,
         /// this is the responsibility of the test code
-        target_tmpdir: PathBuf => "CARGO_TARGET_TMPDIR";
+        target_tmpdir ? target_tmpdir_opt: PathBuf => vars::CARGO_TARGET_TMPDIR;
+        /// The profile the package is built with, see [`Profile`] for how custom profiles are reported
+        profile: Profile => vars::PROFILE;
+        /// The profile the package is built with, as reported by Cargo
+        profile_raw: String => vars::PROFILE;
+        /// The optimization level of the profile the package is built with
+        opt_level: OptLevel => vars::OPT_LEVEL;
+        /// The Rust compiler Cargo uses, which build scripts invoking the compiler should use too
+        rustc: PathBuf => vars::RUSTC;
+        /// The documentation generator Cargo uses
+        rustdoc: PathBuf => vars::RUSTDOC;
         /// This is a path that rustc is invoked from (nightly only)
-        rustc_current_dir: PathBuf => "CARGO_RUSTC_CURRENT_DIR";
+        rustc_current_dir ? rustc_current_dir_opt: PathBuf => vars::CARGO_RUSTC_CURRENT_DIR;
+    }
+}
+
+/// Finds the values of the compiler option `option` in `flags`, given either as a separate argument
+/// or joined to the option after `joined_prefix`, such as `-Copt-level=3` or `--cfg=foo`.
+fn option_values<'a>(
+    flags: &'a [String],
+    option: &'a str,
+    joined_prefix: &'a str,
+) -> impl Iterator<Item = &'a str> {
+    let mut flags = flags.iter();
+
+    std::iter::from_fn(move || loop {
+        let flag = flags.next()?;
+        if flag == option {
+            return flags.next().map(String::as_str);
+        }
+        if let Some(value) = flag.strip_prefix(joined_prefix) {
+            return Some(value);
+        }
+    })
+}
+
+/// Reads a path from the environment variable `var`, treating an empty one as unset.
+fn non_empty_path(var: &str) -> Option<PathBuf> {
+    Cargo::env_os_path(var).filter(|path| !path.as_os_str().is_empty())
+}
+
+/// Finds the profile directory among the ancestors of `out_dir`,
+/// checking that they follow the `<profile>/build/<package>-<hash>/out` layout.
+fn profile_dir(out_dir: &Path) -> Option<&Path> {
+    fn file_name(path: &Path) -> Option<&str> {
+        path.file_name()?.to_str()
+    }
+
+    let package_dir = out_dir
+        .parent()
+        .filter(|_| file_name(out_dir) == Some("out"))?;
+    let build_dir = package_dir
+        .parent()
+        .filter(|_| file_name(package_dir).is_some_and(|name| name.contains('-')))?;
+    build_dir
+        .parent()
+        .filter(|_| file_name(build_dir) == Some("build"))
+}
+
+/// Creates the error reported when `OUT_DIR` doesn't follow Cargo's usual layout.
+fn out_dir_layout_error(out_dir: &Path) -> EnvVarError {
+    EnvVarError {
+        name: vars::OUT_DIR.into(),
+        kind: EnvVarErrorKind::Invalid {
+            value: out_dir.display().to_string(),
+            reason: "it doesn't follow Cargo's `<profile>/build/<package>-<hash>/out` layout"
+                .to_owned(),
+        },
+    }
+}
+
+/// Reads the manifest of the package, returning its path and its `[package.metadata]` table.
+#[cfg(feature = "toml")]
+fn read_package_metadata() -> Result<(PathBuf, toml::Table), ManifestError> {
+    let manifest_path = match Cargo::manifest_path() {
+        Err(err) if err.is_not_present() => Cargo::manifest_dir()?.join("Cargo.toml"),
+        manifest_path => manifest_path?,
+    };
+    Cargo::rerun_if_changed(&manifest_path);
+
+    let contents = match std::fs::read_to_string(&manifest_path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            return Err(ManifestError::Io {
+                path: manifest_path,
+                err,
+            })
+        }
+    };
+    let mut manifest = match contents.parse::<toml::Table>() {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            let location = err
+                .span()
+                .map(|span| manifest::line_and_column(&contents, span.start));
+            return Err(ManifestError::Parse {
+                path: manifest_path,
+                line: location.map(|(line, _)| line),
+                column: location.map(|(_, column)| column),
+                message: err.message().to_owned(),
+            });
+        }
+    };
+
+    let metadata = match manifest.remove("package") {
+        Some(toml::Value::Table(mut package)) => package.remove("metadata"),
+        _ => None,
+    };
+    match metadata {
+        Some(toml::Value::Table(metadata)) => Ok((manifest_path, metadata)),
+        Some(_) => Err(ManifestError::NotATable {
+            path: manifest_path,
+            key: "package.metadata".to_owned(),
+        }),
+        None => Ok((manifest_path, toml::Table::new())),
+    }
+}
+
+/// Returns the error reported by [`Cargo::assert_msrv`] when the compiler of version `rustc` is older than `msrv`.
+fn msrv_error(package: &str, msrv: RustcVersion, rustc: Option<RustcVersion>) -> Option<String> {
+    let rustc = rustc.filter(|rustc| *rustc < msrv)?;
+
+    Some(format!(
+        "{package} requires Rust {msrv} or newer, but the compiler is Rust {rustc}; \
+         update it with `rustup update` or pin an older version of {package}"
+    ))
+}
+
+/// Parses a single component of the package version.
+fn parse_version_number(number: String) -> Result<u64, VersionError> {
+    number.parse().map_err(|_| VersionError::Invalid(number))
+}
+
+/// Finds the jobserver file descriptors in `MAKEFLAGS`, the last jobserver argument taking precedence as it does for `make`.
+fn parse_jobserver_fds(makeflags: &str) -> Option<(i32, i32)> {
+    let auth = makeflags.split_whitespace().rev().find_map(|arg| {
+        arg.strip_prefix("--jobserver-auth=")
+            .or_else(|| arg.strip_prefix("--jobserver-fds="))
+    })?;
+    let (read, write) = auth.split_once(',')?;
+
+    Some((read.parse().ok()?, write.parse().ok()?))
+}
+
+/// Splits `value` into words like a POSIX shell, without expansions: words are separated by whitespace,
+/// single quotes keep their contents as is, and a backslash escapes the next character,
+/// which within double quotes only applies to `"`, `\`, `$` and `` ` ``.
+/// Returns `None` if a quote isn't closed or the value ends with a backslash.
+fn split_shell_words(value: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '\\' | '$' | '`') => word.push(c),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).push(chars.next()?),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    Some(words)
+}
+
+/// Forgets the environment variables declared so far, for tests to observe the declarations they make.
+#[cfg(test)]
+pub(crate) fn forget_declared_envs() {
+    DECLARED_ENVS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_env::TestEnv, BuildEnv, BuildScript, EnvVarErrorKind, Instruction, InstructionKind,
+        TargetCfg,
+    };
+
+    #[test]
+    fn manifest_links_set_unset_and_empty() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_MANIFEST_LINKS, "z");
+        assert_eq!(Cargo::manifest_links(), Ok(Some("z".to_owned())));
+
+        env.set(vars::CARGO_MANIFEST_LINKS, "");
+        assert_eq!(Cargo::manifest_links(), Ok(None));
+
+        env.remove(vars::CARGO_MANIFEST_LINKS);
+        assert_eq!(Cargo::manifest_links(), Ok(None));
+    }
+
+    #[test]
+    fn metadata_doesnt_warn_on_its_own() {
+        let mut env = TestEnv::new();
+        env.remove(vars::CARGO_MANIFEST_LINKS);
+
+        let instructions = BuildScript::collect(|| Cargo::metadata("include", "/opt/z/include"));
+        assert!(instructions
+            .filter_kind(InstructionKind::Warning)
+            .is_empty());
+    }
+
+    #[test]
+    fn metadata_links_check() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_MANIFEST_LINKS, "z");
+        let instructions =
+            BuildScript::collect(|| assert_eq!(Cargo::check_metadata_links(), Ok(true)));
+        assert!(instructions.is_empty());
+
+        env.remove(vars::CARGO_MANIFEST_LINKS);
+        let instructions =
+            BuildScript::collect(|| assert_eq!(Cargo::check_metadata_links(), Ok(false)));
+        assert_eq!(instructions.filter_kind(InstructionKind::Warning).len(), 1);
+    }
+
+    #[test]
+    fn msrv_is_met_by_newer_or_equal_compilers() {
+        let msrv = RustcVersion::parse("1.70").unwrap();
+
+        assert_eq!(msrv_error("foo", msrv, RustcVersion::parse("1.70.0")), None);
+        assert_eq!(msrv_error("foo", msrv, RustcVersion::parse("1.70.3")), None);
+        assert_eq!(
+            msrv_error("foo", msrv, RustcVersion::parse("1.82.0-nightly")),
+            None
+        );
+        assert_eq!(msrv_error("foo", msrv, None), None);
+    }
+
+    #[test]
+    fn msrv_error_names_both_versions() {
+        let msrv = RustcVersion::parse("1.70.3").unwrap();
+        let message = msrv_error("foo", msrv, RustcVersion::parse("1.70")).unwrap();
+
+        assert!(message
+            .starts_with("foo requires Rust 1.70.3 or newer, but the compiler is Rust 1.70.0"));
+    }
+
+    #[test]
+    fn try_variants_reject_invalid_input_without_emitting() {
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(
+                Cargo::try_warning("a\nb"),
+                Err(InstructionError::LineBreak("a\nb".to_owned()))
+            );
+            assert_eq!(
+                Cargo::try_error("a\rb"),
+                Err(InstructionError::LineBreak("a\rb".to_owned()))
+            );
+            assert_eq!(Cargo::try_metadata("", "x"), Err(InstructionError::Empty));
+            assert_eq!(
+                Cargo::try_metadata("a=b", "x"),
+                Err(InstructionError::ContainsEquals("a=b".to_owned()))
+            );
+            assert_eq!(
+                Cargo::try_rerun_if_env_changed("A\nB"),
+                Err(InstructionError::LineBreak("A\nB".to_owned()))
+            );
+            assert_eq!(
+                Cargo::try_rerun_if_changed("a\nb"),
+                Err(InstructionError::LineBreak("a\nb".to_owned()))
+            );
+        });
+
+        assert!(instructions.is_empty());
+    }
+
+    #[test]
+    fn docs_rs() {
+        let mut env = TestEnv::new();
+        let mut docs_rs = (true, true);
+
+        env.remove(vars::DOCS_RS);
+        BuildScript::collect(|| docs_rs = (Cargo::is_docs_rs(), BuildEnv::is_docs_rs()));
+        assert_eq!(docs_rs, (false, false));
+
+        env.set(vars::DOCS_RS, "1");
+        BuildScript::collect(|| docs_rs = (Cargo::is_docs_rs(), BuildEnv::is_docs_rs()));
+        assert_eq!(docs_rs, (true, true));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn out_dir_canonical_resolves_symlinks() {
+        let mut env = TestEnv::new();
+        let dir = env.temp_dir("out-dir-canonical");
+        let real = dir.join("real");
+        std::fs::create_dir(&real).unwrap();
+        std::os::unix::fs::symlink(&real, dir.join("link")).unwrap();
+
+        env.set(
+            vars::OUT_DIR,
+            dir.join("link").join(".").join("..").join("link"),
+        );
+        assert_eq!(
+            Cargo::out_dir_canonical().unwrap(),
+            real.canonicalize().unwrap()
+        );
+
+        env.set(vars::OUT_DIR, dir.join("missing"));
+        assert_eq!(
+            Cargo::out_dir_canonical().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        env.remove(vars::OUT_DIR);
+        assert_eq!(
+            Cargo::out_dir_canonical().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn color_always_and_never() {
+        let mut env = TestEnv::new();
+
+        env.set(vars::CARGO_TERM_COLOR, "always");
+        assert!(Cargo::color_enabled());
+
+        env.set(vars::CARGO_TERM_COLOR, "never");
+        assert!(!Cargo::color_enabled());
+
+        // Anything else falls back to checking the terminal
+        env.set(vars::CARGO_TERM_COLOR, "auto");
+        assert_eq!(Cargo::color_enabled(), io::stderr().is_terminal());
+    }
+
+    #[test]
+    fn rerun_if_changed_build_rs() {
+        let instructions = BuildScript::collect(Cargo::rerun_if_changed_build_rs);
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rerun-if-changed=build.rs\n"
+        );
+    }
+
+    #[test]
+    fn metadata_value_from_the_manifest() {
+        let mut env = TestEnv::new();
+        let dir = env.temp_dir("metadata-value");
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"sample\"\n\n[package.metadata.mytool]\nmode = \"fast\"\n",
+        )
+        .unwrap();
+        env.set(vars::CARGO_MANIFEST_DIR, &dir);
+
+        let mut values = Vec::new();
+        let instructions = BuildScript::collect(|| {
+            values.push(Cargo::metadata_value("mytool.mode").unwrap());
+            values.push(Cargo::metadata_value("mytool.missing").unwrap());
+        });
+
+        assert_eq!(values, [Some("fast".to_owned()), None]);
+        assert_eq!(
+            *instructions.filter_kind(InstructionKind::RerunIfChanged)[0],
+            Instruction::RerunIfChanged(dir.join("Cargo.toml"))
+        );
+    }
+
+    #[test]
+    fn typed_pkg_version() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_PKG_VERSION, "1.2.3-rc.1")
+            .set(vars::CARGO_PKG_VERSION_MAJOR, "1")
+            .set(vars::CARGO_PKG_VERSION_MINOR, "2")
+            .set(vars::CARGO_PKG_VERSION_PATCH, "x")
+            .set(vars::CARGO_PKG_VERSION_PRE, "");
+
+        assert_eq!(
+            Cargo::pkg_version_parsed().map(|version| version.to_string()),
+            Ok("1.2.3-rc.1".to_owned())
+        );
+        assert_eq!(Cargo::pkg_version_major_parsed(), Ok(1));
+        assert_eq!(Cargo::pkg_version_minor_parsed(), Ok(2));
+        assert_eq!(
+            Cargo::pkg_version_patch_parsed(),
+            Err(VersionError::Invalid("x".to_owned()))
+        );
+        assert_eq!(Cargo::pkg_version_pre_parsed(), Ok(None));
+
+        env.set(vars::CARGO_PKG_VERSION, "1.2");
+        assert_eq!(
+            Cargo::pkg_version_parsed(),
+            Err(VersionError::Invalid("1.2".to_owned()))
+        );
+    }
+
+    #[cfg(feature = "semver")]
+    #[test]
+    fn pkg_version_matches_requirements() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_PKG_VERSION, "1.4.0-beta.2");
+
+        assert_eq!(
+            Cargo::pkg_version_semver().map(|version| version.to_string()),
+            Ok("1.4.0-beta.2".to_owned())
+        );
+        assert_eq!(Cargo::pkg_version_matches(">=1.4.0-beta.1, <2"), Ok(true));
+        assert_eq!(Cargo::pkg_version_matches(">=1.2"), Ok(false));
+        assert_eq!(
+            Cargo::pkg_version_matches("=>1"),
+            Err(VersionError::InvalidRequirement("=>1".to_owned()))
+        );
+    }
+
+    #[test]
+    fn jobserver_fds_from_makeflags() {
+        let cases = [
+            ("-j --jobserver-auth=3,4", Some((3, 4))),
+            (" -j --jobserver-fds=5,6 -j", Some((5, 6))),
+            ("--jobserver-fds=3,4 --jobserver-auth=7,8", Some((7, 8))),
+            ("-j --jobserver-auth=fifo:/tmp/GMfifo1234", None),
+            (
+                "--jobserver-auth=3,4 --jobserver-auth=fifo:/tmp/GMfifo1234",
+                None,
+            ),
+            ("--jobserver-auth=3", None),
+            ("--jobserver-auth=a,b", None),
+            ("-j4", None),
+            ("", None),
+        ];
+
+        for (makeflags, fds) in cases {
+            assert_eq!(parse_jobserver_fds(makeflags), fds, "{makeflags}");
+        }
+    }
+
+    #[test]
+    fn empty_package_fields_are_none() {
+        type Getter<T> = fn() -> Result<Option<T>, EnvVarError>;
+        let strings: [(Getter<String>, &str); 6] = [
+            (Cargo::pkg_authors_opt, vars::CARGO_PKG_AUTHORS),
+            (Cargo::pkg_description_opt, vars::CARGO_PKG_DESCRIPTION),
+            (Cargo::pkg_homepage_opt, vars::CARGO_PKG_HOMEPAGE),
+            (Cargo::pkg_repository_opt, vars::CARGO_PKG_REPOSITORY),
+            (Cargo::pkg_license_opt, vars::CARGO_PKG_LICENSE),
+            (Cargo::pkg_rust_version_opt, vars::CARGO_PKG_RUST_VERSION),
+        ];
+        let paths: [(Getter<PathBuf>, &str); 2] = [
+            (Cargo::pkg_license_file_opt, vars::CARGO_PKG_LICENSE_FILE),
+            (Cargo::pkg_readme_opt, vars::CARGO_PKG_README),
+        ];
+
+        let mut env = TestEnv::new();
+        for (getter, var) in strings {
+            env.set(var, "");
+            assert_eq!(getter(), Ok(None), "{var}");
+            env.set(var, "value");
+            assert_eq!(getter(), Ok(Some("value".to_owned())), "{var}");
+        }
+        for (getter, var) in paths {
+            env.set(var, "");
+            assert_eq!(getter(), Ok(None), "{var}");
+            env.set(var, "README.md");
+            assert_eq!(getter(), Ok(Some(PathBuf::from("README.md"))), "{var}");
+        }
+
+        // Required variables keep their empty value
+        env.set(vars::CARGO_PKG_NAME, "");
+        assert_eq!(Cargo::pkg_name(), Ok(String::new()));
+    }
+
+    #[test]
+    fn unset_contextual_variables_are_none() {
+        type Getter = fn() -> Result<Option<PathBuf>, EnvVarError>;
+        let paths: [(Getter, &str); 3] = [
+            (Cargo::out_dir_opt, vars::OUT_DIR),
+            (Cargo::target_tmpdir_opt, vars::CARGO_TARGET_TMPDIR),
+            (Cargo::rustc_current_dir_opt, vars::CARGO_RUSTC_CURRENT_DIR),
+        ];
+
+        let mut env = TestEnv::new();
+        for (getter, var) in paths {
+            env.remove(var);
+            assert_eq!(getter(), Ok(None), "{var}");
+            env.set(var, "/tmp/dir");
+            assert_eq!(getter(), Ok(Some(PathBuf::from("/tmp/dir"))), "{var}");
+        }
+
+        env.remove(vars::CARGO_BIN_NAME);
+        assert_eq!(Cargo::bin_name_opt(), Ok(None));
+        env.set(vars::CARGO_BIN_NAME, "cli");
+        assert_eq!(Cargo::bin_name_opt(), Ok(Some("cli".to_owned())));
+    }
+
+    #[test]
+    fn env_path_list_splits_with_the_platform_separator() {
+        let mut env = TestEnv::new();
+        let separator = if cfg!(windows) { ';' } else { ':' };
+
+        env.set(
+            "BUILD_INSTRUCTIONS_TEST_PATHS",
+            format!("/opt/a{separator}{separator}/opt/b"),
+        );
+        let mut paths = None;
+        let instructions = BuildScript::collect(|| {
+            paths = Some(Cargo::env_path_list("BUILD_INSTRUCTIONS_TEST_PATHS"));
+        });
+        assert_eq!(
+            paths.unwrap(),
+            Ok(vec![
+                PathBuf::from("/opt/a"),
+                PathBuf::new(),
+                PathBuf::from("/opt/b")
+            ])
+        );
+        assert_eq!(
+            instructions
+                .filter_kind(InstructionKind::RerunIfEnvChanged)
+                .len(),
+            1
+        );
+
+        env.set("BUILD_INSTRUCTIONS_TEST_PATHS", "");
+        assert_eq!(
+            Cargo::env_path_list("BUILD_INSTRUCTIONS_TEST_PATHS"),
+            Ok(Vec::new())
+        );
+
+        env.remove("BUILD_INSTRUCTIONS_TEST_PATHS");
+        assert!(Cargo::env_path_list("BUILD_INSTRUCTIONS_TEST_PATHS")
+            .unwrap_err()
+            .is_not_present());
+    }
+
+    #[test]
+    fn getter_errors_name_their_variable() {
+        type Getter = fn() -> Result<(), EnvVarError>;
+        let getters: [(Getter, &str); 29] = [
+            (|| Cargo::binary_path().map(drop), vars::CARGO),
+            (|| Cargo::manifest_dir().map(drop), vars::CARGO_MANIFEST_DIR),
+            (
+                || Cargo::manifest_path().map(drop),
+                vars::CARGO_MANIFEST_PATH,
+            ),
+            (|| Cargo::pkg_version().map(drop), vars::CARGO_PKG_VERSION),
+            (
+                || Cargo::pkg_version_major().map(drop),
+                vars::CARGO_PKG_VERSION_MAJOR,
+            ),
+            (
+                || Cargo::pkg_version_minor().map(drop),
+                vars::CARGO_PKG_VERSION_MINOR,
+            ),
+            (
+                || Cargo::pkg_version_patch().map(drop),
+                vars::CARGO_PKG_VERSION_PATCH,
+            ),
+            (
+                || Cargo::pkg_version_pre().map(drop),
+                vars::CARGO_PKG_VERSION_PRE,
+            ),
+            (|| Cargo::pkg_authors().map(drop), vars::CARGO_PKG_AUTHORS),
+            (|| Cargo::pkg_name().map(drop), vars::CARGO_PKG_NAME),
+            (
+                || Cargo::pkg_description().map(drop),
+                vars::CARGO_PKG_DESCRIPTION,
+            ),
+            (|| Cargo::pkg_homepage().map(drop), vars::CARGO_PKG_HOMEPAGE),
+            (
+                || Cargo::pkg_repository().map(drop),
+                vars::CARGO_PKG_REPOSITORY,
+            ),
+            (|| Cargo::pkg_license().map(drop), vars::CARGO_PKG_LICENSE),
+            (
+                || Cargo::pkg_license_file().map(drop),
+                vars::CARGO_PKG_LICENSE_FILE,
+            ),
+            (
+                || Cargo::pkg_rust_version().map(drop),
+                vars::CARGO_PKG_RUST_VERSION,
+            ),
+            (|| Cargo::pkg_readme().map(drop), vars::CARGO_PKG_README),
+            (|| Cargo::crate_name().map(drop), vars::CARGO_CRATE_NAME),
+            (|| Cargo::bin_name().map(drop), vars::CARGO_BIN_NAME),
+            (|| Cargo::out_dir().map(drop), vars::OUT_DIR),
+            (|| Cargo::target().map(drop), vars::TARGET),
+            (|| Cargo::host().map(drop), vars::HOST),
+            (
+                || Cargo::target_tmpdir().map(drop),
+                vars::CARGO_TARGET_TMPDIR,
+            ),
+            (|| Cargo::profile().map(drop), vars::PROFILE),
+            (|| Cargo::profile_raw().map(drop), vars::PROFILE),
+            (|| Cargo::opt_level().map(drop), vars::OPT_LEVEL),
+            (|| Cargo::rustc().map(drop), vars::RUSTC),
+            (|| Cargo::rustdoc().map(drop), vars::RUSTDOC),
+            (
+                || Cargo::rustc_current_dir().map(drop),
+                vars::CARGO_RUSTC_CURRENT_DIR,
+            ),
+        ];
+
+        let mut env = TestEnv::new();
+        for (getter, var) in getters {
+            env.remove(var);
+            let err = getter().unwrap_err();
+            assert!(err.is_not_present(), "{var}");
+            assert!(
+                err.to_string()
+                    .starts_with(&format!("environment variable {var} is not set")),
+                "{var}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_unicode_paths_round_trip() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let mut env = TestEnv::new();
+        let out_dir = OsStr::from_bytes(b"/tmp/out-\xff");
+        env.set(vars::OUT_DIR, out_dir).set(vars::TARGET, out_dir);
+
+        assert_eq!(Cargo::out_dir(), Ok(PathBuf::from(out_dir)));
+        assert_eq!(Cargo::out_dir_opt(), Ok(Some(PathBuf::from(out_dir))));
+        assert_eq!(Cargo::env_os(vars::OUT_DIR).as_deref(), Some(out_dir));
+        assert_eq!(
+            Cargo::env_os_path(vars::OUT_DIR),
+            Some(PathBuf::from(out_dir))
+        );
+
+        // String-valued getters can't represent the value
+        assert_eq!(
+            Cargo::target().unwrap_err().kind,
+            EnvVarErrorKind::NotUnicode(out_dir.to_owned())
+        );
+    }
+
+    #[test]
+    fn pkg_version_pre_parts() {
+        let mut env = TestEnv::new();
+
+        env.set(vars::CARGO_PKG_VERSION_PRE, "rc.1");
+        assert_eq!(
+            Cargo::pkg_version_pre_parts(),
+            Ok(vec!["rc".to_owned(), "1".to_owned()])
+        );
+        assert_eq!(Cargo::pkg_version_pre(), Ok("rc.1".to_owned()));
+
+        env.set(vars::CARGO_PKG_VERSION_PRE, "");
+        assert_eq!(Cargo::pkg_version_pre_parts(), Ok(Vec::new()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn metadata_json_round_trip() {
+        use std::collections::BTreeMap;
+
+        let value = BTreeMap::from([
+            (
+                "include".to_owned(),
+                vec!["/opt/z/include".to_owned(), "line\nbreak".to_owned()],
+            ),
+            ("libs".to_owned(), vec!["z".to_owned()]),
+        ]);
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Cargo::metadata_json("config", &value), Ok(()));
+            assert_eq!(
+                Cargo::metadata_json("a=b", &value),
+                Err(InstructionError::ContainsEquals("a=b".to_owned()))
+            );
+        });
+
+        let [Instruction::Metadata { key, value: json }] = &instructions[..] else {
+            panic!("expected a single metadata instruction, got {instructions:?}");
+        };
+        assert_eq!(key, "config");
+        assert!(!json.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<BTreeMap<String, Vec<String>>>(json).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn typed_vars() {
+        let mut env = TestEnv::new();
+        let var = "BUILD_INSTRUCTIONS_TEST_KNOB";
+
+        env.set(var, "42");
+        assert_eq!(Cargo::var::<u32>(var), Ok(42));
+        assert_eq!(Cargo::var_opt::<i64>(var), Ok(Some(42)));
+
+        env.set(var, "Yes");
+        assert_eq!(Cargo::var::<bool>(var), Ok(true));
+
+        env.set(var, "lots");
+        let err = Cargo::var::<u32>(var).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "environment variable BUILD_INSTRUCTIONS_TEST_KNOB has an invalid value \"lots\": \
+             invalid digit found in string"
+        );
+        assert_eq!(Cargo::var_opt::<u32>(var), Err(err));
+
+        env.remove(var);
+        assert!(Cargo::var::<u32>(var).unwrap_err().is_not_present());
+        assert_eq!(Cargo::var_opt::<u32>(var), Ok(None));
+    }
+
+    #[test]
+    fn env_os_reads_raw_values() {
+        let mut env = TestEnv::new();
+        let var = "BUILD_INSTRUCTIONS_TEST_RAW";
+
+        env.set(var, "/opt/sdk");
+        assert_eq!(Cargo::env_os(var), Some(OsString::from("/opt/sdk")));
+        assert_eq!(Cargo::env_os_path(var), Some(PathBuf::from("/opt/sdk")));
+
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+            let raw = OsStr::from_bytes(b"/opt/\xfe\xff");
+            env.set(var, raw);
+            assert_eq!(Cargo::env_os(var).as_deref(), Some(raw));
+            assert_eq!(Cargo::env_os_path(var), Some(PathBuf::from(raw)));
+        }
+
+        env.remove(var);
+        assert_eq!(Cargo::env_os(var), None);
+        assert_eq!(Cargo::env_os_path(var), None);
+    }
+
+    #[test]
+    fn typed_profile() {
+        let mut env = TestEnv::new();
+
+        for (raw, profile) in [
+            ("debug", Profile::Debug),
+            ("release", Profile::Release),
+            ("bench", Profile::Other("bench".to_owned())),
+        ] {
+            env.set(vars::PROFILE, raw);
+            assert_eq!(Cargo::profile(), Ok(profile));
+            assert_eq!(Cargo::profile_raw(), Ok(raw.to_owned()));
+        }
+    }
+
+    #[test]
+    fn env_changes_are_declared_once() {
+        let _env = TestEnv::new();
+        let var = "BUILD_INSTRUCTIONS_TEST_DECLARED";
+
+        let instructions = BuildScript::collect(|| {
+            Cargo::rerun_if_env_changed(var);
+            Cargo::rerun_if_env_changed(var);
+            let _ = Cargo::tracked_env(var);
+            Cargo::rerun_if_env_changed(vars::TARGET);
+        });
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rerun-if-env-changed=BUILD_INSTRUCTIONS_TEST_DECLARED\n\
+             cargo::rerun-if-env-changed=TARGET\n"
+        );
+
+        // The declarations are remembered for the rest of the process
+        assert!(BuildScript::collect(|| Cargo::rerun_if_env_changed(var)).is_empty());
+    }
+
+    #[test]
+    fn debug_info_levels() {
+        let mut env = TestEnv::new();
+        let enabled = [
+            "true",
+            "1",
+            "2",
+            "limited",
+            "full",
+            "line-tables-only",
+            "line-directives-only",
+        ];
+
+        for debug in enabled {
+            env.set(vars::DEBUG, debug);
+            assert_eq!(Cargo::debug_info(), Ok(true), "{debug}");
+        }
+        for debug in ["false", "0", "none"] {
+            env.set(vars::DEBUG, debug);
+            assert_eq!(Cargo::debug_info(), Ok(false), "{debug}");
+        }
+
+        env.set(vars::DEBUG, "3");
+        assert_eq!(
+            Cargo::debug_info().unwrap_err().kind,
+            EnvVarErrorKind::Invalid {
+                value: "3".to_owned(),
+                reason: "expected `true`, `false` or a debug info level".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn typed_opt_level() {
+        let mut env = TestEnv::new();
+
+        for (raw, opt_level) in [
+            ("0", OptLevel::O0),
+            ("1", OptLevel::O1),
+            ("2", OptLevel::O2),
+            ("3", OptLevel::O3),
+            ("s", OptLevel::Os),
+            ("z", OptLevel::Oz),
+            ("4", OptLevel::Other("4".to_owned())),
+        ] {
+            env.set(vars::OPT_LEVEL, raw);
+            assert_eq!(Cargo::opt_level(), Ok(opt_level), "{raw}");
+        }
+    }
+
+    #[test]
+    fn num_jobs_values() {
+        let mut env = TestEnv::new();
+        let available = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+        env.set(vars::NUM_JOBS, "8");
+        assert_eq!(Cargo::num_jobs(), Ok(8));
+        assert_eq!(Cargo::num_jobs_or_available(), 8);
+
+        env.set(vars::NUM_JOBS, "0");
+        assert_eq!(Cargo::num_jobs(), Ok(1));
+        assert_eq!(Cargo::num_jobs_or_available(), 1);
+
+        env.set(vars::NUM_JOBS, "many");
+        let err = Cargo::num_jobs().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("environment variable NUM_JOBS has an invalid value \"many\""));
+        assert_eq!(Cargo::num_jobs_or_available(), available);
+
+        env.remove(vars::NUM_JOBS);
+        assert!(Cargo::num_jobs().unwrap_err().is_not_present());
+        assert_eq!(Cargo::num_jobs_or_available(), available);
+    }
+
+    #[test]
+    fn keyed_warnings_are_printed_once() {
+        let instructions = BuildScript::collect(|| {
+            Cargo::warning_once("test-warning-once", "first");
+            Cargo::warning_once("test-warning-once", "second");
+            Cargo::warning_once("test-warning-once-other", "other");
+        });
+
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::warning=first\ncargo::warning=other\n"
+        );
+    }
+
+    #[test]
+    fn host_and_target() {
+        let mut env = TestEnv::new();
+        env.set(vars::HOST, "x86_64-unknown-linux-gnu")
+            .set(vars::TARGET, "aarch64-linux-android");
+
+        assert_eq!(Cargo::host(), Ok("x86_64-unknown-linux-gnu".to_owned()));
+        assert_eq!(Cargo::target(), Ok("aarch64-linux-android".to_owned()));
+        assert_eq!(
+            Cargo::target_or_host(),
+            Ok("aarch64-linux-android".to_owned())
+        );
+
+        env.remove(vars::TARGET);
+        assert!(Cargo::target().unwrap_err().is_not_present());
+        assert_eq!(
+            Cargo::target_or_host(),
+            Ok("x86_64-unknown-linux-gnu".to_owned())
+        );
+
+        env.remove(vars::HOST);
+        assert!(Cargo::target_or_host().unwrap_err().is_not_present());
+    }
+
+    #[test]
+    fn is_package() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_PKG_NAME, "my-crate");
+
+        assert_eq!(Cargo::is_package("my-crate"), Ok(true));
+        assert_eq!(Cargo::is_package("my_crate"), Ok(false));
+
+        env.remove(vars::CARGO_PKG_NAME);
+        assert!(Cargo::is_package("my-crate").unwrap_err().is_not_present());
+    }
+
+    #[test]
+    fn encoded_rustflags() {
+        let mut env = TestEnv::new();
+        env.set(
+            vars::CARGO_ENCODED_RUSTFLAGS,
+            "--cfg=a b\x1f\x1f-Ctarget-cpu=native",
+        );
+        assert_eq!(
+            Cargo::encoded_rustflags(),
+            Ok(vec![
+                "--cfg=a b".to_owned(),
+                "-Ctarget-cpu=native".to_owned()
+            ])
+        );
+
+        env.set(vars::CARGO_ENCODED_RUSTFLAGS, "");
+        assert_eq!(Cargo::encoded_rustflags(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn rustflags_fallback_and_helpers() {
+        let mut env = TestEnv::new();
+        env.remove(vars::CARGO_ENCODED_RUSTFLAGS).set(
+            vars::RUSTFLAGS,
+            " -C target-feature=+avx2  --cfg tokio_unstable",
+        );
+        assert_eq!(
+            Cargo::rustflags(),
+            Ok(vec![
+                "-C".to_owned(),
+                "target-feature=+avx2".to_owned(),
+                "--cfg".to_owned(),
+                "tokio_unstable".to_owned()
+            ])
+        );
+
+        env.set(
+            vars::CARGO_ENCODED_RUSTFLAGS,
+            "-Ctarget-feature=+sse4.1,-avx\x1f--cfg=feature=\"a b\"\x1f-C\x1ftarget-feature=+aes\x1f--cfg\x1fnightly",
+        );
+        let flags = Cargo::rustflags().unwrap();
+        assert_eq!(
+            Cargo::rustflags_target_features(&flags),
+            ["+sse4.1", "-avx", "+aes"]
+        );
+        assert_eq!(
+            Cargo::rustflags_cfgs(&flags),
+            ["feature=\"a b\"", "nightly"]
+        );
+    }
+
+    #[test]
+    fn makeflags_are_passed_to_child_commands() {
+        let mut env = TestEnv::new();
+        env.remove(vars::CARGO_MAKEFLAGS);
+        assert_eq!(Cargo::makeflags(), Ok(None));
+        let mut command = Command::new("make");
+        assert!(!Cargo::inherit_makeflags(&mut command));
+        assert_eq!(command.get_envs().count(), 0);
+
+        env.set(vars::CARGO_MAKEFLAGS, "-j --jobserver-auth=3,4");
+        assert_eq!(
+            Cargo::makeflags(),
+            Ok(Some("-j --jobserver-auth=3,4".to_owned()))
+        );
+        assert_eq!(Cargo::jobserver_fds(), Some((3, 4)));
+        let mut command = Command::new("make");
+        assert!(Cargo::inherit_makeflags(&mut command));
+        assert_eq!(
+            command.get_envs().collect::<Vec<_>>(),
+            [(
+                OsStr::new("MAKEFLAGS"),
+                Some(OsStr::new("-j --jobserver-auth=3,4"))
+            )]
+        );
+    }
+
+    #[test]
+    fn target_os_and_arch_shortcuts() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_CFG_TARGET_OS, "linux")
+            .set(vars::CARGO_CFG_TARGET_ARCH, "x86_64");
+
+        assert_eq!(Cargo::target_os(), Ok("linux".to_owned()));
+        assert_eq!(Cargo::target_arch(), Ok("x86_64".to_owned()));
+        assert_eq!(Cargo::target_os(), TargetCfg::target_os());
+        assert_eq!(Cargo::target_arch(), TargetCfg::target_arch());
+
+        env.remove(vars::CARGO_CFG_TARGET_OS)
+            .remove(vars::CARGO_CFG_TARGET_ARCH);
+        assert!(Cargo::target_os().unwrap_err().is_not_present());
+        assert!(Cargo::target_arch().unwrap_err().is_not_present());
+    }
+
+    #[test]
+    fn out_dir_hash_names() {
+        let mut env = TestEnv::new();
+        env.set(vars::OUT_DIR, "/target/out");
+
+        let path = Cargo::out_dir_hash(&[b"ab", b"c"]).unwrap();
+        assert_eq!(path.parent(), Some(Path::new("/target/out")));
+        assert_eq!(path.file_name().unwrap().len(), 16);
+        assert_eq!(Cargo::out_dir_hash(&[b"ab", b"c"]), Ok(path.clone()));
+        assert_ne!(Cargo::out_dir_hash(&[b"a", b"bc"]), Ok(path.clone()));
+        assert_ne!(Cargo::out_dir_hash(&[b"abc"]), Ok(path));
+
+        env.remove(vars::OUT_DIR);
+        assert!(Cargo::out_dir_hash(&[b"abc"]).unwrap_err().is_not_present());
+    }
+
+    #[test]
+    fn binary_executables_keep_their_names() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_BIN_EXE_PREFIX)
+            .set("CARGO_BIN_EXE_my-tool", "/target/debug/my-tool")
+            .set("CARGO_BIN_EXE_server", "/target/debug/server");
+
+        assert_eq!(
+            Cargo::binary_executables(),
+            BTreeMap::from([
+                ("my-tool".to_owned(), PathBuf::from("/target/debug/my-tool")),
+                ("server".to_owned(), PathBuf::from("/target/debug/server")),
+            ])
+        );
+        assert_eq!(
+            Cargo::binary_executable_path("my-tool"),
+            Ok(PathBuf::from("/target/debug/my-tool"))
+        );
+
+        env.remove_prefixed(vars::CARGO_BIN_EXE_PREFIX);
+        assert!(Cargo::binary_executables().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn manifest_dir_abs_resolves_symlinks() {
+        let mut env = TestEnv::new();
+        let dir = env.temp_dir("manifest-dir-abs");
+        let real = dir.join("package");
+        std::fs::create_dir(&real).unwrap();
+        std::os::unix::fs::symlink(&real, dir.join("link")).unwrap();
+
+        env.set(vars::CARGO_MANIFEST_DIR, dir.join("link"));
+        assert_eq!(
+            Cargo::manifest_dir_abs().unwrap(),
+            real.canonicalize().unwrap()
+        );
+
+        env.set(vars::CARGO_MANIFEST_DIR, dir.join("missing"));
+        assert_eq!(
+            Cargo::manifest_dir_abs().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        env.remove(vars::CARGO_MANIFEST_DIR);
+        assert_eq!(
+            Cargo::manifest_dir_abs().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn home_falls_back_to_user_home() {
+        let user_home = if cfg!(windows) {
+            vars::USERPROFILE
+        } else {
+            vars::HOME
+        };
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_HOME, "/opt/cargo")
+            .set(user_home, "/home/user");
+        assert_eq!(Cargo::home(), Ok(PathBuf::from("/opt/cargo")));
+        assert_eq!(Cargo::home_explicit(), Ok(Some("/opt/cargo".into())));
+
+        env.set(vars::CARGO_HOME, "");
+        assert_eq!(Cargo::home_explicit(), Ok(None));
+        assert_eq!(Cargo::home(), Ok(Path::new("/home/user").join(".cargo")));
+
+        env.remove(vars::CARGO_HOME);
+        assert_eq!(Cargo::home(), Ok(Path::new("/home/user").join(".cargo")));
+
+        env.remove(user_home);
+        assert!(Cargo::home().is_err_and(|err| err.is_not_present()));
+    }
+
+    #[test]
+    fn env_or_set_and_unset() {
+        const VAR: &str = "BUILD_INSTRUCTIONS_TEST_ENV_OR";
+        let mut env = TestEnv::new();
+
+        env.set(VAR, "custom");
+        assert_eq!(Cargo::env_or(VAR, "default"), "custom");
+
+        env.set(VAR, "");
+        assert_eq!(Cargo::env_or(VAR, "default"), "");
+
+        env.remove(VAR);
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Cargo::env_or(VAR, "default"), "default");
+        });
+        assert!(instructions.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn env_or_non_unicode_warns() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        const VAR: &str = "BUILD_INSTRUCTIONS_TEST_ENV_OR";
+        let mut env = TestEnv::new();
+        env.set(VAR, OsStr::from_bytes(b"\xff"));
+
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Cargo::env_or(VAR, "default"), "default");
+        });
+        assert_eq!(
+            *instructions,
+            [Instruction::Warning(format!(
+                "`{VAR}` isn't valid unicode, using the default \"default\""
+            ))]
+        );
+    }
+
+    #[test]
+    fn profile_dir_name_from_out_dir() {
+        let target_dir = Path::new("/work").join("target");
+        let out_dir = |profile_dir: &Path| {
+            profile_dir
+                .join("build")
+                .join("sample-0123456789abcdef")
+                .join("out")
+        };
+        let mut env = TestEnv::new();
+
+        for (profile_dir, name) in [
+            (target_dir.join("debug"), "debug"),
+            (target_dir.join("bench-lto"), "bench-lto"),
+            (
+                target_dir.join("x86_64-unknown-linux-gnu").join("release"),
+                "release",
+            ),
+        ] {
+            env.set(vars::OUT_DIR, out_dir(&profile_dir));
+            assert_eq!(Cargo::profile_dir_name().as_deref(), Ok(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn package_info_from_the_environment() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_PKG_NAME, "sample")
+            .set(vars::CARGO_PKG_VERSION, "0.4.1")
+            .set(vars::CARGO_PKG_AUTHORS, "Jane Doe <jane@example.com>:John")
+            .set(vars::CARGO_PKG_DESCRIPTION, "A sample package")
+            .set(vars::CARGO_PKG_HOMEPAGE, "")
+            .set(vars::CARGO_PKG_REPOSITORY, "https://example.com/sample")
+            .set(vars::CARGO_PKG_LICENSE, "MIT OR Apache-2.0")
+            .set(vars::CARGO_PKG_LICENSE_FILE, "")
+            .set(vars::CARGO_PKG_README, "README.md")
+            .remove(vars::CARGO_PKG_RUST_VERSION);
+
+        let info = Cargo::package_info().unwrap();
+        assert_eq!(info.name, "sample");
+        assert_eq!(info.version.to_string(), "0.4.1");
+        assert_eq!(
+            info.authors,
+            [
+                Author {
+                    name: "Jane Doe".to_owned(),
+                    email: Some("jane@example.com".to_owned()),
+                },
+                Author {
+                    name: "John".to_owned(),
+                    email: None,
+                },
+            ]
+        );
+        assert_eq!(info.description.as_deref(), Some("A sample package"));
+        assert_eq!(info.homepage, None);
+        assert_eq!(
+            info.repository.as_deref(),
+            Some("https://example.com/sample")
+        );
+        assert_eq!(info.license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(info.license_file, None);
+        assert_eq!(info.readme, Some(PathBuf::from("README.md")));
+        assert_eq!(info.rust_version, None);
+
+        env.set(vars::CARGO_PKG_VERSION, "0.4");
+        assert_eq!(
+            Cargo::package_info(),
+            Err(VersionError::Invalid("0.4".to_owned()))
+        );
+
+        env.remove(vars::CARGO_PKG_NAME);
+        assert!(matches!(
+            Cargo::package_info(),
+            Err(VersionError::Var(err)) if err.is_not_present()
+        ));
+    }
+
+    #[test]
+    fn target_profile_dir_from_out_dir() {
+        let target_dir = Path::new("/work").join("target");
+        let out_dir = |profile_dir: &Path| {
+            profile_dir
+                .join("build")
+                .join("sample-0123456789abcdef")
+                .join("out")
+        };
+        let mut env = TestEnv::new();
+
+        for profile_dir in [
+            target_dir.join("bench-lto"),
+            target_dir.join("x86_64-unknown-linux-gnu").join("debug"),
+        ] {
+            env.set(vars::OUT_DIR, out_dir(&profile_dir));
+            assert_eq!(Cargo::target_profile_dir(), Ok(profile_dir));
+        }
+
+        env.remove(vars::OUT_DIR);
+        assert!(Cargo::target_profile_dir().unwrap_err().is_not_present());
+    }
+
+    #[test]
+    fn profile_dir_rejects_other_layouts() {
+        let mut env = TestEnv::new();
+
+        for out_dir in [
+            "/tmp/out",
+            "/target/debug/build/sample/out",
+            "/target/debug/x/sample-0/out",
+        ] {
+            env.set(vars::OUT_DIR, out_dir);
+            assert_eq!(
+                Cargo::profile_dir_name(),
+                Err(out_dir_layout_error(Path::new(out_dir))),
+                "{out_dir}"
+            );
+            assert_eq!(
+                Cargo::target_profile_dir(),
+                Err(out_dir_layout_error(Path::new(out_dir))),
+                "{out_dir}"
+            );
+        }
+    }
+
+    #[test]
+    fn workspace_root_of_members_and_standalone_packages() {
+        let mut env = TestEnv::new();
+        let dir = env.temp_dir("workspace-root");
+        let workspace = dir.join("workspace");
+        let member = workspace.join("crates").join("member");
+        let standalone = dir.join("standalone");
+        std::fs::create_dir_all(&member).unwrap();
+        std::fs::create_dir_all(&standalone).unwrap();
+        std::fs::write(
+            workspace.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"member\"\n\n[package.metadata.workspace]\nkey = 1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            standalone.join("Cargo.toml"),
+            "[package]\nname = \"standalone\"\n",
+        )
+        .unwrap();
+        env.remove(vars::CARGO_RUSTC_CURRENT_DIR)
+            .set(vars::CARGO_HOME, dir.join("cargo-home"));
+
+        env.set(vars::CARGO_MANIFEST_DIR, &member);
+        assert_eq!(Cargo::workspace_root(), Ok(workspace.clone()));
+
+        env.set(vars::CARGO_MANIFEST_DIR, &workspace);
+        assert_eq!(Cargo::workspace_root(), Ok(workspace.clone()));
+
+        env.set(vars::CARGO_MANIFEST_DIR, &standalone);
+        assert_eq!(Cargo::workspace_root(), Ok(standalone));
+
+        env.set(vars::CARGO_HOME, &workspace)
+            .set(vars::CARGO_MANIFEST_DIR, &member);
+        assert_eq!(Cargo::workspace_root(), Ok(member));
+
+        env.set(vars::CARGO_RUSTC_CURRENT_DIR, "/reported/root");
+        assert_eq!(Cargo::workspace_root(), Ok(PathBuf::from("/reported/root")));
+    }
+
+    #[test]
+    fn command_runs_from_the_package_directory() {
+        const MAKEFLAGS: &str = "-j --jobserver-auth=fifo:/nonexistent/fifo";
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_MANIFEST_DIR, "/work/sample")
+            .set(vars::CARGO_MAKEFLAGS, MAKEFLAGS);
+
+        let command = Cargo::command("make");
+        assert_eq!(command.get_program(), "make");
+        assert_eq!(command.get_current_dir(), Some(Path::new("/work/sample")));
+        let makeflags = command
+            .get_envs()
+            .find_map(|(var, value)| (var == "MAKEFLAGS").then_some(value));
+        // With the `jobserver` feature, the flags come from the client when it connects
+        assert!(makeflags.is_some_and(|value| value.is_some()));
+        #[cfg(not(feature = "jobserver"))]
+        assert_eq!(makeflags, Some(Some(OsStr::new(MAKEFLAGS))));
+
+        env.remove(vars::CARGO_MANIFEST_DIR)
+            .remove(vars::CARGO_MAKEFLAGS);
+        let command = Cargo::command("make");
+        assert_eq!(command.get_current_dir(), None);
+        #[cfg(not(feature = "jobserver"))]
+        assert_eq!(command.get_envs().count(), 0);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn package_metadata_from_the_manifest() {
+        let mut env = TestEnv::new();
+        let dir = env.temp_dir("package-metadata");
+        let manifest_path = dir.join("Cargo.toml");
+        std::fs::write(
+            &manifest_path,
+            "[package]\nname = \"sample\"\n\n\
+             [package.metadata]\nplain = 1\n\n\
+             [package.metadata.mytool]\nmode = \"fast\"\n",
+        )
+        .unwrap();
+        env.remove(vars::CARGO_MANIFEST_PATH)
+            .set(vars::CARGO_MANIFEST_DIR, &dir);
+
+        let instructions = BuildScript::collect(|| {
+            let metadata = Cargo::package_metadata().unwrap();
+            assert_eq!(metadata["plain"].as_integer(), Some(1));
+
+            let mytool = Cargo::package_metadata_for("mytool").unwrap();
+            assert_eq!(mytool["mode"].as_str(), Some("fast"));
+            assert!(Cargo::package_metadata_for("missing").unwrap().is_empty());
+            assert!(matches!(
+                Cargo::package_metadata_for("plain"),
+                Err(ManifestError::NotATable { key, .. }) if key == "package.metadata.plain"
+            ));
+        });
+        assert!(instructions.contains(&Instruction::RerunIfChanged(manifest_path.clone())));
+
+        std::fs::write(&manifest_path, "[package]\nname = \"sample\"\n").unwrap();
+        assert!(Cargo::package_metadata().unwrap().is_empty());
+
+        std::fs::write(&manifest_path, "[package]\nmetadata = 1\n").unwrap();
+        assert!(matches!(
+            Cargo::package_metadata(),
+            Err(ManifestError::NotATable { key, .. }) if key == "package.metadata"
+        ));
+    }
+
+    #[test]
+    fn shell_words() {
+        let cases = [
+            (
+                "qemu-aarch64 -L /usr",
+                Some(&["qemu-aarch64", "-L", "/usr"][..]),
+            ),
+            (
+                "  wine\t\"C:/Program Files/app.exe\" ",
+                Some(&["wine", "C:/Program Files/app.exe"]),
+            ),
+            (
+                "run 'a \\b' \"c\\\"d\\e\"",
+                Some(&["run", "a \\b", "c\"d\\e"]),
+            ),
+            ("a\\ b ''  x\"y\"z", Some(&["a b", "", "xyz"])),
+            ("", Some(&[])),
+            ("run 'open", None),
+            ("run \"open", None),
+            ("run \\", None),
+        ];
+
+        for (value, words) in cases {
+            assert_eq!(
+                split_shell_words(value),
+                words.map(|words| words.iter().map(|&word| word.to_owned()).collect()),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn target_runner_from_the_mangled_triple() {
+        const VAR: &str = "CARGO_TARGET_THUMBV7EM_NONE_EABIHF_V1_2_RUNNER";
+        let mut env = TestEnv::new();
+        env.set(vars::TARGET, "thumbv7em-none-eabihf.v1.2")
+            .set(VAR, "probe-rs run --chip 'STM32F411 RE'");
+
+        let mut runner = None;
+        let instructions = BuildScript::collect(|| runner = Some(Cargo::target_runner()));
+        assert_eq!(
+            runner.unwrap(),
+            Ok(Some(vec![
+                "probe-rs".to_owned(),
+                "run".to_owned(),
+                "--chip".to_owned(),
+                "STM32F411 RE".to_owned(),
+            ]))
+        );
+        assert_eq!(
+            *instructions,
+            [Instruction::RerunIfEnvChanged(VAR.to_owned())]
+        );
+
+        let command = Cargo::target_runner_command("app").unwrap();
+        assert_eq!(command.get_program(), "probe-rs");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["run", "--chip", "STM32F411 RE", "app"]
+        );
+
+        env.set(VAR, "qemu-arm 'unterminated");
+        assert_eq!(
+            Cargo::target_runner().unwrap_err().kind,
+            EnvVarErrorKind::Invalid {
+                value: "qemu-arm 'unterminated".to_owned(),
+                reason: "it has an unterminated quote or escape".to_owned(),
+            }
+        );
+
+        env.remove(VAR);
+        assert_eq!(Cargo::target_runner(), Ok(None));
+        assert_eq!(
+            Cargo::target_runner_command("app").unwrap().get_program(),
+            "app"
+        );
+    }
+
+    #[test]
+    fn inputs_changed_tracks_fingerprints() {
+        let mut env = TestEnv::new();
+        let out_dir = env.temp_dir("inputs-changed");
+        env.set(vars::OUT_DIR, &out_dir);
+        let input = out_dir.join("input.txt");
+        let missing = out_dir.join("missing.txt");
+        std::fs::write(&input, "a").unwrap();
+        let inputs = [input.as_path(), missing.as_path()];
+
+        assert_eq!(Cargo::inputs_changed(&inputs).ok(), Some(true));
+        assert_eq!(Cargo::inputs_changed(&inputs).ok(), Some(false));
+        assert_eq!(Cargo::inputs_changed(&inputs[..1]).ok(), Some(true));
+
+        // A different size, as modification times may be too coarse to tell the writes apart
+        std::fs::write(&input, "abc").unwrap();
+        assert_eq!(Cargo::inputs_changed(&inputs).ok(), Some(true));
+        assert_eq!(Cargo::inputs_changed(&inputs).ok(), Some(false));
+
+        std::fs::write(&missing, "").unwrap();
+        assert_eq!(Cargo::inputs_changed(&inputs).ok(), Some(true));
+
+        env.remove(vars::OUT_DIR);
+        assert_eq!(
+            Cargo::inputs_changed(&inputs).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn target_dir_env_precedence() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_TARGET_DIR, "/a")
+            .set(vars::CARGO_BUILD_TARGET_DIR, "/b");
+
+        let mut target_dir = None;
+        let instructions = BuildScript::collect(|| target_dir = Cargo::target_dir_env());
+        assert_eq!(target_dir, Some("/a".into()));
+        assert_eq!(
+            *instructions,
+            [Instruction::RerunIfEnvChanged(
+                vars::CARGO_TARGET_DIR.to_owned()
+            )]
+        );
+
+        env.set(vars::CARGO_TARGET_DIR, "");
+        let instructions = BuildScript::collect(|| target_dir = Cargo::target_dir_env());
+        assert_eq!(target_dir, Some("/b".into()));
+        // Variables are only declared once per process
+        assert_eq!(
+            *instructions,
+            [Instruction::RerunIfEnvChanged(
+                vars::CARGO_BUILD_TARGET_DIR.to_owned()
+            )]
+        );
+
+        env.remove(vars::CARGO_BUILD_TARGET_DIR);
+        BuildScript::collect(|| target_dir = Cargo::target_dir_env());
+        assert_eq!(target_dir, None);
+    }
+
+    #[test]
+    fn target_dir_guess_from_out_dir() {
+        let target_dir = Path::new("/work").join("target");
+        let out_dir = |profile_dir: &Path| {
+            profile_dir
+                .join("build")
+                .join("sample-0123456789abcdef")
+                .join("out")
+        };
+        let mut env = TestEnv::new();
+        env.set(vars::TARGET, "x86_64-unknown-linux-gnu");
+
+        for profile_dir in [
+            target_dir.join("bench-lto"),
+            target_dir.join("x86_64-unknown-linux-gnu").join("debug"),
+        ] {
+            env.set(vars::OUT_DIR, out_dir(&profile_dir));
+            assert_eq!(Cargo::target_dir_guess(), Ok(target_dir.clone()));
+        }
     }
 }
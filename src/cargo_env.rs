@@ -0,0 +1,191 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use crate::{features, vars, Cargo, EnvVarError};
+
+/// Represents a snapshot of the environment variables Cargo sets for build scripts, read at once by [`CargoEnv::capture`].
+///
+/// The [`Debug`] output includes every field, so it can be passed to [`Cargo::warning`] to diagnose a build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoEnv {
+    /// The directory containing the manifest of the package, from `CARGO_MANIFEST_DIR`
+    pub manifest_dir: PathBuf,
+    /// The directory for the build script's output, from `OUT_DIR`
+    pub out_dir: PathBuf,
+    /// The target triple being compiled for, from `TARGET`
+    pub target: String,
+    /// The target triple of the host running the build, from `HOST`
+    pub host: String,
+    /// The profile being built, `debug` or `release`, from `PROFILE`
+    pub profile: String,
+    /// The optimization level, from `OPT_LEVEL`
+    pub opt_level: String,
+    /// Whether debug info is enabled, from `DEBUG`
+    pub debug: String,
+    /// The name of the package, from `CARGO_PKG_NAME`
+    pub pkg_name: String,
+    /// The version of the package, from `CARGO_PKG_VERSION`
+    pub pkg_version: String,
+    /// The authors of the package, from `CARGO_PKG_AUTHORS`
+    pub pkg_authors: Option<String>,
+    /// The description of the package, from `CARGO_PKG_DESCRIPTION`
+    pub pkg_description: Option<String>,
+    /// The home page of the package, from `CARGO_PKG_HOMEPAGE`
+    pub pkg_homepage: Option<String>,
+    /// The repository of the package, from `CARGO_PKG_REPOSITORY`
+    pub pkg_repository: Option<String>,
+    /// The license of the package, from `CARGO_PKG_LICENSE`
+    pub pkg_license: Option<String>,
+    /// The minimum supported Rust version of the package, from `CARGO_PKG_RUST_VERSION`
+    pub pkg_rust_version: Option<String>,
+    /// The enabled features, as returned by [`Features::enabled`](crate::Features::enabled)
+    pub features: BTreeSet<String>,
+    /// The cfgs of the target by lowercase name, from the `CARGO_CFG_*` variables
+    pub cfgs: BTreeMap<String, String>,
+}
+
+impl CargoEnv {
+    /// Reads every variable of the snapshot, failing with the first required variable that isn't set.
+    /// Package fields omitted from the manifest, which Cargo sets to an empty string, are `None`.
+    ///
+    /// The variables aren't registered with `rerun-if-env-changed`, as Cargo reruns the build script
+    /// whenever any of them changes on its own.
+    pub fn capture() -> Result<Self, EnvVarError> {
+        Ok(Self {
+            manifest_dir: Cargo::manifest_dir()?,
+            out_dir: Cargo::out_dir()?,
+            target: Cargo::var(vars::TARGET)?,
+            host: Cargo::var(vars::HOST)?,
+            profile: Cargo::var(vars::PROFILE)?,
+            opt_level: Cargo::var(vars::OPT_LEVEL)?,
+            debug: Cargo::var(vars::DEBUG)?,
+            pkg_name: Cargo::pkg_name()?,
+            pkg_version: Cargo::pkg_version()?,
+            pkg_authors: optional(Cargo::pkg_authors_opt())?,
+            pkg_description: optional(Cargo::pkg_description_opt())?,
+            pkg_homepage: optional(Cargo::pkg_homepage_opt())?,
+            pkg_repository: optional(Cargo::pkg_repository_opt())?,
+            pkg_license: optional(Cargo::pkg_license_opt())?,
+            pkg_rust_version: optional(Cargo::pkg_rust_version_opt())?,
+            features: features::enabled(),
+            cfgs: cfgs(),
+        })
+    }
+}
+
+/// Treats a missing variable like an empty one, as older Cargo versions don't set every package variable.
+pub(crate) fn optional<T>(value: Result<Option<T>, EnvVarError>) -> Result<Option<T>, EnvVarError> {
+    match value {
+        Err(err) if err.is_not_present() => Ok(None),
+        value => value,
+    }
+}
+
+/// Reads the `CARGO_CFG_*` variables, keyed by the lowercase cfg name.
+fn cfgs() -> BTreeMap<String, String> {
+    std::env::vars_os()
+        .filter_map(|(var, value)| {
+            let name = var.to_str()?.strip_prefix(vars::CARGO_CFG_PREFIX)?;
+            Some((name.to_lowercase(), value.into_string().ok()?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::TestEnv;
+
+    const REQUIRED: &[(&str, &str)] = &[
+        (vars::CARGO_MANIFEST_DIR, "/src/foo"),
+        (vars::OUT_DIR, "/src/foo/target/debug/build/foo-1234/out"),
+        (vars::TARGET, "x86_64-unknown-linux-gnu"),
+        (vars::HOST, "x86_64-unknown-linux-gnu"),
+        (vars::PROFILE, "debug"),
+        (vars::OPT_LEVEL, "0"),
+        (vars::DEBUG, "true"),
+        (vars::CARGO_PKG_NAME, "foo"),
+        (vars::CARGO_PKG_VERSION, "0.1.0"),
+    ];
+
+    const OPTIONAL: &[&str] = &[
+        vars::CARGO_PKG_AUTHORS,
+        vars::CARGO_PKG_DESCRIPTION,
+        vars::CARGO_PKG_HOMEPAGE,
+        vars::CARGO_PKG_REPOSITORY,
+        vars::CARGO_PKG_LICENSE,
+        vars::CARGO_PKG_RUST_VERSION,
+        vars::CARGO_CFG_FEATURE,
+    ];
+
+    /// Sets up an environment with only the required variables.
+    fn minimal_env() -> TestEnv {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_CFG_PREFIX)
+            .remove_prefixed(vars::CARGO_FEATURE_PREFIX);
+        for var in OPTIONAL {
+            env.remove(var);
+        }
+        for (var, value) in REQUIRED {
+            env.set(var, value);
+        }
+        env
+    }
+
+    #[test]
+    fn capture_minimal_env() {
+        let _env = minimal_env();
+        let cargo_env = CargoEnv::capture().unwrap();
+
+        assert_eq!(
+            cargo_env.out_dir,
+            PathBuf::from("/src/foo/target/debug/build/foo-1234/out")
+        );
+        assert_eq!(cargo_env.profile, "debug");
+        assert_eq!(cargo_env.opt_level, "0");
+        assert_eq!(cargo_env.debug, "true");
+        assert_eq!(cargo_env.pkg_authors, None);
+        assert_eq!(cargo_env.pkg_rust_version, None);
+        assert!(cargo_env.features.is_empty());
+        assert!(cargo_env.cfgs.is_empty());
+    }
+
+    #[test]
+    fn capture_full_env() {
+        let mut env = minimal_env();
+        env.set(vars::CARGO_PKG_AUTHORS, "Jane Doe <jane@example.com>")
+            .set(vars::CARGO_PKG_DESCRIPTION, "")
+            .set(vars::CARGO_PKG_LICENSE, "MIT OR Apache-2.0")
+            .set(vars::CARGO_PKG_RUST_VERSION, "1.70")
+            .set(vars::CARGO_CFG_FEATURE, "default,std")
+            .set(vars::CARGO_CFG_TARGET_OS, "linux")
+            .set("CARGO_CFG_UNIX", "");
+        let cargo_env = CargoEnv::capture().unwrap();
+
+        assert_eq!(
+            cargo_env.pkg_authors.as_deref(),
+            Some("Jane Doe <jane@example.com>")
+        );
+        assert_eq!(cargo_env.pkg_description, None);
+        assert_eq!(cargo_env.pkg_license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(cargo_env.pkg_rust_version.as_deref(), Some("1.70"));
+        assert_eq!(
+            cargo_env.features,
+            BTreeSet::from(["default".to_owned(), "std".to_owned()])
+        );
+        assert_eq!(cargo_env.cfgs["target_os"], "linux");
+        assert_eq!(cargo_env.cfgs["unix"], "");
+    }
+
+    #[test]
+    fn capture_names_missing_variable() {
+        let mut env = minimal_env();
+        env.remove(vars::OPT_LEVEL);
+
+        let err = CargoEnv::capture().unwrap_err();
+        assert!(err.is_not_present());
+        assert_eq!(err.name, vars::OPT_LEVEL);
+    }
+}
@@ -0,0 +1,202 @@
+use std::{collections::BTreeMap, convert::Infallible, path::PathBuf};
+
+use crate::{instruction::fail, vars, Cargo, EnvVarError, FromEnvVar};
+
+/// Provides access to the metadata dependencies with a `links` key pass to the build script
+/// through the `DEP_<links>_<key>` environment variables.
+///
+/// A dependency emitting `cargo::metadata=include=/path` from its build script, with `links = "z"` in its manifest,
+/// is read with `Dep::get("z", "include")`. Only direct dependencies pass metadata this way.
+/// Empty values are treated as absent, as a dependency can only clear a key by emitting it empty.
+pub struct Dep(Infallible);
+
+impl Dep {
+    /// Returns the value of the metadata `key` of the dependency linking `links_name`,
+    /// or `None` if the variable isn't set or is empty.
+    pub fn get(links_name: &str, key: &str) -> Result<Option<String>, EnvVarError> {
+        match String::read_env_var_opt(env_var_name(links_name, key)) {
+            Err(err) if err.is_not_present() => Ok(None),
+            value => value,
+        }
+    }
+
+    /// Returns the value of the metadata `key` of the dependency linking `links_name` as a path,
+    /// or `None` if the variable isn't set or is empty. Paths that aren't valid unicode are returned as is.
+    pub fn get_path(links_name: &str, key: &str) -> Result<Option<PathBuf>, EnvVarError> {
+        match PathBuf::read_env_var_opt(env_var_name(links_name, key)) {
+            Err(err) if err.is_not_present() => Ok(None),
+            value => value,
+        }
+    }
+
+    /// Returns every metadata key of the dependency linking `links_name` with its value, from the variables
+    /// prefixed by `DEP_<links>_`. Variables that aren't valid unicode are skipped.
+    ///
+    /// The keys are lowercased, as the variable names don't preserve their spelling, and keep `_` in place of `-`,
+    /// so a key emitted as `include-dir` is reported as `include_dir`. The prefix of a `links` name also
+    /// prefixes the variables of longer names starting with it, so the keys containing `_` are kept apart
+    /// in [`DepMetadata::ambiguous`], as they may belong to another dependency.
+    pub fn all(links_name: &str) -> DepMetadata {
+        let prefix = env_var_name(links_name, "");
+        let mut metadata = DepMetadata::default();
+
+        for (var, value) in std::env::vars_os() {
+            let Some(key) = var.to_str().and_then(|var| var.strip_prefix(&prefix)) else {
+                continue;
+            };
+            let Ok(value) = value.into_string() else {
+                continue;
+            };
+
+            let key = key.to_lowercase();
+            if key.contains('_') {
+                metadata.ambiguous.insert(key, value);
+            } else {
+                metadata.keys.insert(key, value);
+            }
+        }
+
+        metadata
+    }
+
+    /// Prints every metadata key of the dependency linking `links_name` with its value as a warning,
+    /// to find out what a dependency exports while debugging a build script.
+    pub fn dump_warning(links_name: &str) {
+        let metadata = Self::all(links_name);
+        if metadata.is_empty() {
+            Cargo::warning(format!("no metadata is exported by `{links_name}`"));
+        }
+
+        for (key, value) in metadata.keys {
+            Cargo::warning(format!("{links_name}: {key} = {value:?}"));
+        }
+        for (key, value) in metadata.ambiguous {
+            Cargo::warning(format!(
+                "{links_name}: {key} = {value:?} (or exported by a dependency with a longer `links` name)"
+            ));
+        }
+    }
+
+    /// Returns the value of the metadata `key` of the dependency linking `links_name`,
+    /// failing the build with an error explaining how the metadata is passed if it isn't set.
+    pub fn require(links_name: &str, key: &str) -> String {
+        match Self::get(links_name, key) {
+            Ok(Some(value)) => value,
+            Ok(None) => fail(format!(
+                "`{}` is not set: a direct dependency with `links = \"{links_name}\"` in its manifest \
+                 must emit `cargo::metadata={key}=<value>` from its build script",
+                env_var_name(links_name, key),
+            )),
+            Err(err) => fail(err.to_string()),
+        }
+    }
+}
+
+/// Represents the metadata of a dependency, as returned by [`Dep::all`].
+///
+/// The variables Cargo sets don't tell where the `links` name ends and the key starts, so `DEP_FOO_INCLUDE_DIR`
+/// is the key `include_dir` of `foo` as much as the key `dir` of `foo-include`. Keys without `_` can only belong
+/// to the dependency, while the others are kept apart to let the build script decide.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepMetadata {
+    /// The keys without `_`, which belong to the dependency, with their values
+    pub keys: BTreeMap<String, String>,
+    /// The keys containing `_`, which belong either to the dependency or to one whose `links` name extends its own,
+    /// with their values
+    pub ambiguous: BTreeMap<String, String>,
+}
+
+impl DepMetadata {
+    /// Returns the value of `key`, looking at the ambiguous keys too.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.keys
+            .get(key)
+            .or_else(|| self.ambiguous.get(key))
+            .map(String::as_str)
+    }
+
+    /// Checks whether no key is set.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.ambiguous.is_empty()
+    }
+}
+
+/// Maps a `links` name and a metadata key to the `DEP_*` environment variable Cargo sets for them,
+/// both uppercased with `-` replaced by `_`.
+pub(crate) fn env_var_name(links_name: &str, key: &str) -> String {
+    let envify = |value: &str| value.to_uppercase().replace('-', "_");
+    format!("{}{}_{}", vars::DEP_PREFIX, envify(links_name), envify(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::TestEnv;
+
+    #[test]
+    fn names_are_mangled() {
+        assert_eq!(env_var_name("z", "include"), "DEP_Z_INCLUDE");
+        assert_eq!(
+            env_var_name("foo-sys", "include-dir"),
+            "DEP_FOO_SYS_INCLUDE_DIR"
+        );
+        assert_eq!(env_var_name("Foo", "Root"), "DEP_FOO_ROOT");
+    }
+
+    #[test]
+    fn get_reads_injected_variables() {
+        let mut env = TestEnv::new();
+        env.set("DEP_FOO_SYS_INCLUDE", "/opt/foo/include")
+            .set("DEP_FOO_SYS_ROOT", "")
+            .remove("DEP_FOO_SYS_LIB");
+
+        assert_eq!(
+            Dep::get("foo-sys", "include").unwrap().as_deref(),
+            Some("/opt/foo/include")
+        );
+        assert_eq!(Dep::get("foo-sys", "root").unwrap(), None);
+        assert_eq!(Dep::get("foo-sys", "lib").unwrap(), None);
+    }
+
+    #[test]
+    fn all_separates_ambiguous_keys() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed("DEP_FOO_")
+            .set("DEP_FOO_INCLUDE", "/opt/foo/include")
+            .set("DEP_FOO_ROOT", "/opt/foo")
+            .set("DEP_FOO_INCLUDE_DIR", "/opt/foo/include")
+            .set("DEP_FOO_BAR_VERSION", "2.1")
+            .set("DEP_FOOBAR_ROOT", "/opt/foobar");
+
+        let metadata = Dep::all("foo");
+        assert_eq!(
+            metadata.keys,
+            BTreeMap::from([
+                ("include".to_owned(), "/opt/foo/include".to_owned()),
+                ("root".to_owned(), "/opt/foo".to_owned()),
+            ])
+        );
+        assert_eq!(
+            metadata.ambiguous,
+            BTreeMap::from([
+                ("bar_version".to_owned(), "2.1".to_owned()),
+                ("include_dir".to_owned(), "/opt/foo/include".to_owned()),
+            ])
+        );
+        assert_eq!(metadata.get("include_dir"), Some("/opt/foo/include"));
+        assert!(Dep::all("missing").is_empty());
+    }
+
+    #[test]
+    fn get_path_reads_injected_variables() {
+        let mut env = TestEnv::new();
+        env.set("DEP_FOO_SYS_INCLUDE", "/opt/foo/include")
+            .remove("DEP_FOO_SYS_LIB");
+
+        assert_eq!(
+            Dep::get_path("foo-sys", "include").unwrap(),
+            Some(PathBuf::from("/opt/foo/include"))
+        );
+        assert_eq!(Dep::get_path("foo-sys", "lib").unwrap(), None);
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize},
+    path::PathBuf,
+};
+#[cfg(not(feature = "no-cache"))]
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{EnvVarError, EnvVarErrorKind};
+
+/// Represents a type the value of an environment variable can be converted to,
+/// as used by the getters generated with [`env_getters!`](crate::env_getters).
+///
+/// Types implementing [`FromStr`](std::str::FromStr) can implement it with [`parse_env_value`].
+pub trait FromEnvVar: Sized {
+    /// Converts the raw value of an environment variable, returning the reason on failure.
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind>;
+
+    /// Reads the environment variable `name` and converts its value.
+    ///
+    /// Every variable is read once per process and cached by name unless the `no-cache` feature is enabled.
+    /// See [`Cargo::invalidate_cache`](crate::Cargo::invalidate_cache).
+    fn read_env_var(name: impl Into<Cow<'static, str>>) -> Result<Self, EnvVarError> {
+        let name = name.into();
+        let value = var_os(&name);
+
+        match value {
+            Some(value) => Self::from_env_var(value).map_err(|kind| EnvVarError { name, kind }),
+            None => Err(EnvVarError {
+                name,
+                kind: EnvVarErrorKind::NotPresent,
+            }),
+        }
+    }
+
+    /// Like [`FromEnvVar::read_env_var`], but returns `None` when the variable is empty,
+    /// as Cargo sets variables of fields omitted from the manifest to an empty string.
+    fn read_env_var_opt(name: impl Into<Cow<'static, str>>) -> Result<Option<Self>, EnvVarError> {
+        let name = name.into();
+
+        match var_os(&name) {
+            Some(value) if value.is_empty() => Ok(None),
+            _ => Self::read_env_var(name).map(Some),
+        }
+    }
+}
+
+#[cfg(not(feature = "no-cache"))]
+static CACHE: Mutex<BTreeMap<String, Option<OsString>>> = Mutex::new(BTreeMap::new());
+
+/// Reads the environment variable `name`, through the cache unless the `no-cache` feature is enabled.
+fn var_os(name: &str) -> Option<OsString> {
+    #[cfg(not(feature = "no-cache"))]
+    {
+        let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(value) = cache.get(name) {
+            return value.clone();
+        }
+
+        let value = std::env::var_os(name);
+        cache.insert(name.to_owned(), value.clone());
+        value
+    }
+    #[cfg(feature = "no-cache")]
+    std::env::var_os(name)
+}
+
+/// Clears the cached values of environment variables, so that they're read again on next access.
+pub(crate) fn invalidate_cache() {
+    #[cfg(not(feature = "no-cache"))]
+    CACHE.lock().unwrap_or_else(PoisonError::into_inner).clear();
+}
+
+/// Parses the raw value of an environment variable with [`FromStr`](std::str::FromStr).
+pub fn parse_env_value<T>(value: OsString) -> Result<T, EnvVarErrorKind>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = value.into_string().map_err(EnvVarErrorKind::NotUnicode)?;
+
+    value
+        .parse()
+        .map_err(|err: T::Err| EnvVarErrorKind::Invalid {
+            reason: err.to_string(),
+            value,
+        })
+}
+
+impl FromEnvVar for String {
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+        value.into_string().map_err(EnvVarErrorKind::NotUnicode)
+    }
+}
+
+impl FromEnvVar for OsString {
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+        Ok(value)
+    }
+}
+
+/// Paths don't need to be valid unicode, so any value is read losslessly.
+impl FromEnvVar for PathBuf {
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+        Ok(value.into())
+    }
+}
+
+macro_rules! impl_from_env_var_with_parse {
+    ($($ty: ty),*) => {
+        $(
+            impl FromEnvVar for $ty {
+                fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+                    parse_env_value(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_env_var_with_parse!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    char,
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
+    NonZeroUsize,
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr
+);
+
+/// Accepts `1`, `true`, `yes` and `on` as `true` and `0`, `false`, `no` and `off` as `false`, ignoring case,
+/// as environment variables are commonly set in any of these forms.
+impl FromEnvVar for bool {
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+        let value = String::from_env_var(value)?;
+
+        match value.to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "0" | "false" | "no" | "off" => Ok(false),
+            _ => Err(EnvVarErrorKind::Invalid {
+                value,
+                reason: "expected one of `1`, `true`, `yes`, `on`, `0`, `false`, `no` or `off`"
+                    .to_owned(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::TestEnv;
+
+    const VAR: &str = "BUILD_INSTRUCTIONS_TEST_CACHE";
+
+    #[test]
+    #[cfg(not(feature = "no-cache"))]
+    fn reads_are_cached_by_name() {
+        let mut env = TestEnv::new();
+        env.set(VAR, "1");
+        assert_eq!(u8::read_env_var(VAR.to_owned()), Ok(1));
+
+        std::env::set_var(VAR, "2");
+        assert_eq!(u8::read_env_var(VAR), Ok(1));
+        assert_eq!(u8::read_env_var(VAR.to_owned()), Ok(1));
+
+        crate::Cargo::invalidate_cache();
+        assert_eq!(u8::read_env_var(VAR.to_owned()), Ok(2));
+    }
+
+    #[test]
+    fn empty_values_are_none() {
+        let mut env = TestEnv::new();
+        env.set(VAR, "");
+        assert_eq!(String::read_env_var_opt(VAR), Ok(None));
+
+        env.set(VAR, "value");
+        assert_eq!(String::read_env_var_opt(VAR), Ok(Some("value".to_owned())));
+    }
+
+    #[test]
+    fn bool_spellings() {
+        for value in ["1", "true", "YES", "On"] {
+            assert_eq!(bool::from_env_var(value.into()), Ok(true));
+        }
+        for value in ["0", "false", "No", "OFF"] {
+            assert_eq!(bool::from_env_var(value.into()), Ok(false));
+        }
+        assert!(bool::from_env_var("maybe".into()).is_err());
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    borrow::Cow,
+    env::VarError,
+    error::Error,
+    ffi::OsString,
+    fmt::{Display, Formatter},
+    io,
+};
+
+use crate::vars;
+
+/// Represents the failure of reading an environment variable, carrying the name of the variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVarError {
+    /// The name of the variable
+    pub name: Cow<'static, str>,
+    /// The reason reading the variable failed
+    pub kind: EnvVarErrorKind,
+}
+
+/// Represents the reasons reading an environment variable can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvVarErrorKind {
+    /// The variable isn't set
+    NotPresent,
+    /// The variable is set, but its value isn't valid unicode
+    NotUnicode(OsString),
+    /// The variable is set, but its value couldn't be parsed
+    Invalid {
+        /// The value of the variable
+        value: String,
+        /// The reason the value couldn't be parsed
+        reason: String,
+    },
+}
+
+impl EnvVarError {
+    /// Creates an error for the variable `name` from the error returned by [`std::env::var`].
+    pub fn new(name: impl Into<Cow<'static, str>>, err: VarError) -> Self {
+        let kind = match err {
+            VarError::NotPresent => EnvVarErrorKind::NotPresent,
+            VarError::NotUnicode(value) => EnvVarErrorKind::NotUnicode(value),
+        };
+
+        Self {
+            name: name.into(),
+            kind,
+        }
+    }
+
+    /// Checks if the error is caused by the variable not being set.
+    pub fn is_not_present(&self) -> bool {
+        self.kind == EnvVarErrorKind::NotPresent
+    }
+}
+
+impl Display for EnvVarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = &self.name;
+
+        match &self.kind {
+            EnvVarErrorKind::NotPresent if is_set_by_cargo(name) => write!(
+                f,
+                "environment variable {name} is not set; is this code running inside a cargo build script?"
+            ),
+            EnvVarErrorKind::NotPresent => write!(f, "environment variable {name} is not set"),
+            EnvVarErrorKind::NotUnicode(value) => {
+                write!(f, "environment variable {name} is not valid unicode: {value:?}")
+            }
+            EnvVarErrorKind::Invalid { value, reason } => {
+                write!(f, "environment variable {name} has an invalid value {value:?}: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for EnvVarError {}
+
+/// A value that couldn't be parsed is converted to [`VarError::NotUnicode`], the only other reason [`VarError`] has.
+impl From<EnvVarError> for VarError {
+    fn from(err: EnvVarError) -> Self {
+        match err.kind {
+            EnvVarErrorKind::NotPresent => VarError::NotPresent,
+            EnvVarErrorKind::NotUnicode(value) => VarError::NotUnicode(value),
+            EnvVarErrorKind::Invalid { value, .. } => VarError::NotUnicode(value.into()),
+        }
+    }
+}
+
+/// Checks whether a variable is one Cargo sets for build scripts, for which absence hints at running outside of Cargo
+/// and which `rustc-env` shouldn't override.
+pub(crate) fn is_set_by_cargo(name: &str) -> bool {
+    name.starts_with("CARGO")
+        || matches!(
+            name,
+            vars::OUT_DIR
+                | vars::TARGET
+                | vars::HOST
+                | vars::NUM_JOBS
+                | vars::OPT_LEVEL
+                | vars::DEBUG
+                | vars::PROFILE
+                | vars::RUSTC
+                | vars::RUSTDOC
+        )
+}
+
+/// Represents the reasons a build instruction can be rejected before it's emitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstructionError {
+    /// The value contains a line break, which would terminate the instruction early
+    LineBreak(String),
+    /// The value is empty
+    Empty,
+    /// The value contains `=`, which Cargo uses to separate a key from its value
+    ContainsEquals(String),
+    /// The value isn't a valid Rust identifier
+    InvalidIdentifier(String),
+    /// The value isn't a feature name Cargo accepts, such as one with non-ASCII characters
+    InvalidFeatureName(String),
+    /// The value isn't a valid `major.minor[.patch]` version
+    InvalidVersion(String),
+    /// The path doesn't name a library file with a recognized extension
+    UnknownLibraryFile(String),
+    /// The value couldn't be serialized, with the reason given by the serializer
+    Unserializable(String),
+    /// The codegen option can't be passed to the compiler from a build script
+    UnsupportedCodegenOption(String),
+    /// The environment variable is one Cargo sets itself, which the value would silently replace
+    ReservedEnv(String),
+    /// The path isn't valid unicode, which Cargo can't read from build script output.
+    /// Carries the path with invalid sequences replaced
+    NotUnicode(String),
+}
+
+impl Display for InstructionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstructionError::LineBreak(value) => write!(f, "{value:?} contains a line break"),
+            InstructionError::Empty => write!(f, "value is empty"),
+            InstructionError::ContainsEquals(value) => write!(f, "{value:?} contains `=`"),
+            InstructionError::InvalidIdentifier(value) => {
+                write!(f, "{value:?} is not a valid identifier")
+            }
+            InstructionError::InvalidFeatureName(value) => {
+                write!(f, "{value:?} is not a valid feature name")
+            }
+            InstructionError::InvalidVersion(value) => {
+                write!(f, "{value:?} is not a valid version")
+            }
+            InstructionError::UnknownLibraryFile(value) => {
+                write!(
+                    f,
+                    "{value:?} is not a library file with a recognized extension"
+                )
+            }
+            InstructionError::Unserializable(reason) => {
+                write!(f, "value couldn't be serialized: {reason}")
+            }
+            InstructionError::UnsupportedCodegenOption(key) => write!(
+                f,
+                "`-C {key}` can't be passed from a build script, as Cargo only accepts `-l` and `-L` in `rustc-flags`; \
+                 set it in `RUSTFLAGS`, the `build.rustflags` config or a `[profile]` of the manifest instead"
+            ),
+            InstructionError::ReservedEnv(var) => write!(
+                f,
+                "environment variable {var} is set by Cargo, and overriding it breaks code relying on Cargo's value"
+            ),
+            InstructionError::NotUnicode(path) => write!(
+                f,
+                "{path:?} is not valid unicode, and Cargo ignores build script output that isn't"
+            ),
+        }
+    }
+}
+
+impl Error for InstructionError {}
+
+/// Represents the failures of a compile probe itself, as opposed to the probed code not compiling.
+#[derive(Debug)]
+pub enum ProbeError {
+    /// The `OUT_DIR` environment variable, where probes are written, couldn't be read
+    OutDir(EnvVarError),
+    /// Writing the probe or invoking the Rust compiler failed
+    Io(io::Error),
+}
+
+impl Display for ProbeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeError::OutDir(err) => write!(f, "couldn't find where to write the probe: {err}"),
+            ProbeError::Io(err) => write!(f, "couldn't run the Rust compiler: {err}"),
+        }
+    }
+}
+
+impl Error for ProbeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProbeError::OutDir(err) => Some(err),
+            ProbeError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<EnvVarError> for ProbeError {
+    fn from(err: EnvVarError) -> Self {
+        ProbeError::OutDir(err)
+    }
+}
+
+impl From<io::Error> for ProbeError {
+    fn from(err: io::Error) -> Self {
+        ProbeError::Io(err)
+    }
+}
+
+/// Represents the failures of reading a version from the environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionError {
+    /// The environment variable holding the version couldn't be read
+    Var(EnvVarError),
+    /// The version isn't a valid semantic version
+    Invalid(String),
+    /// The version requirement isn't valid
+    InvalidRequirement(String),
+}
+
+impl Display for VersionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionError::Var(err) => write!(f, "couldn't read the version: {err}"),
+            VersionError::Invalid(version) => write!(f, "{version:?} is not a valid version"),
+            VersionError::InvalidRequirement(req) => {
+                write!(f, "{req:?} is not a valid version requirement")
+            }
+        }
+    }
+}
+
+impl Error for VersionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VersionError::Var(err) => Some(err),
+            VersionError::Invalid(_) | VersionError::InvalidRequirement(_) => None,
+        }
+    }
+}
+
+impl From<EnvVarError> for VersionError {
+    fn from(err: EnvVarError) -> Self {
+        VersionError::Var(err)
+    }
+}
+
+/// Represents the failures of reading the path to a file of an artifact dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArtifactError {
+    /// The variable holding the path isn't set, as the dependency isn't declared as an artifact dependency
+    NotDeclared {
+        /// The name of the variable
+        var: String,
+        /// The declaration of the dependency that sets the variable
+        declaration: String,
+    },
+    /// The variable holding the path couldn't be read
+    Var(EnvVarError),
+}
+
+impl Display for ArtifactError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArtifactError::NotDeclared { var, declaration } => write!(
+                f,
+                "environment variable {var} is not set; declare the dependency as `{declaration}` \
+                 in `[build-dependencies]` and build with `-Z bindeps` on a nightly compiler"
+            ),
+            ArtifactError::Var(err) => write!(f, "couldn't read the artifact path: {err}"),
+        }
+    }
+}
+
+impl Error for ArtifactError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ArtifactError::NotDeclared { .. } => None,
+            ArtifactError::Var(err) => Some(err),
+        }
+    }
+}
+
+impl From<EnvVarError> for ArtifactError {
+    fn from(err: EnvVarError) -> Self {
+        ArtifactError::Var(err)
+    }
+}
+
+/// Represents the failures of reading the license expression of the package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseError {
+    /// The environment variable holding the expression couldn't be read
+    Var(EnvVarError),
+    /// The expression isn't a valid SPDX license expression
+    Invalid {
+        /// The expression
+        expression: String,
+        /// The byte position in the expression where parsing failed
+        position: usize,
+        /// What was expected at that position
+        reason: String,
+    },
+}
+
+impl Display for LicenseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LicenseError::Var(err) => write!(f, "couldn't read the license: {err}"),
+            LicenseError::Invalid {
+                expression,
+                position,
+                reason,
+            } => write!(
+                f,
+                "{expression:?} is not a valid license expression: {reason} at position {position}"
+            ),
+        }
+    }
+}
+
+impl Error for LicenseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LicenseError::Var(err) => Some(err),
+            LicenseError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl From<EnvVarError> for LicenseError {
+    fn from(err: EnvVarError) -> Self {
+        LicenseError::Var(err)
+    }
+}
+
+/// Represents the failures of checking which of a set of features are enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeatureError {
+    /// None of the features is enabled
+    NoneEnabled(Vec<String>),
+    /// More than one of the features is enabled
+    Conflicting(Vec<String>),
+}
+
+impl Display for FeatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeatureError::NoneEnabled(features) => {
+                write!(f, "none of the features {} is enabled", features.join(", "))
+            }
+            FeatureError::Conflicting(features) => {
+                write!(f, "features {} are all enabled", features.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for FeatureError {}
+
+/// Represents the failures of reading the manifest of the package.
+#[cfg(feature = "toml")]
+#[derive(Debug)]
+pub enum ManifestError {
+    /// The environment variable locating the manifest couldn't be read
+    Var(EnvVarError),
+    /// The manifest couldn't be read
+    Io {
+        /// The path to the manifest
+        path: std::path::PathBuf,
+        /// The underlying error
+        err: io::Error,
+    },
+    /// The manifest isn't valid TOML
+    Parse {
+        /// The path to the manifest
+        path: std::path::PathBuf,
+        /// The line of the error, starting from 1, if the parser reported a location
+        line: Option<usize>,
+        /// The column of the error, starting from 1, if the parser reported a location
+        column: Option<usize>,
+        /// The reason the manifest couldn't be parsed
+        message: String,
+    },
+    /// The value at the key isn't a table
+    NotATable {
+        /// The path to the manifest
+        path: std::path::PathBuf,
+        /// The dotted key of the value, such as `package.metadata.mytool`
+        key: String,
+    },
+}
+
+#[cfg(feature = "toml")]
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Var(err) => write!(f, "couldn't find the manifest: {err}"),
+            ManifestError::Io { path, err } => {
+                write!(f, "couldn't read {}: {err}", path.display())
+            }
+            ManifestError::Parse {
+                path,
+                line: Some(line),
+                column: Some(column),
+                message,
+            } => write!(f, "{}:{line}:{column}: {message}", path.display()),
+            ManifestError::Parse { path, message, .. } => {
+                write!(f, "{}: {message}", path.display())
+            }
+            ManifestError::NotATable { path, key } => {
+                write!(f, "`{key}` in {} is not a table", path.display())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Error for ManifestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ManifestError::Var(err) => Some(err),
+            ManifestError::Io { err, .. } => Some(err),
+            ManifestError::Parse { .. } | ManifestError::NotATable { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<EnvVarError> for ManifestError {
+    fn from(err: EnvVarError) -> Self {
+        ManifestError::Var(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_var_error_messages() {
+        assert_eq!(
+            EnvVarError::new(vars::OUT_DIR, VarError::NotPresent).to_string(),
+            "environment variable OUT_DIR is not set; is this code running inside a cargo build script?"
+        );
+        assert_eq!(
+            EnvVarError::new("MY_VAR", VarError::NotPresent).to_string(),
+            "environment variable MY_VAR is not set"
+        );
+
+        let invalid = EnvVarError {
+            name: "MY_VAR".into(),
+            kind: EnvVarErrorKind::Invalid {
+                value: "x".to_owned(),
+                reason: "expected a number".to_owned(),
+            },
+        };
+        assert_eq!(
+            invalid.to_string(),
+            "environment variable MY_VAR has an invalid value \"x\": expected a number"
+        );
+        assert!(!invalid.is_not_present());
+        assert_eq!(VarError::from(invalid), VarError::NotUnicode("x".into()));
+    }
+
+    #[test]
+    fn variables_set_by_cargo() {
+        for var in ["CARGO_PKG_NAME", "OUT_DIR", "TARGET", "RUSTDOC"] {
+            assert!(is_set_by_cargo(var), "{var}");
+        }
+        for var in ["HOME", "RUSTC_WRAPPER", "MY_VAR"] {
+            assert!(!is_set_by_cargo(var), "{var}");
+        }
+    }
+}
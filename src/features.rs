@@ -0,0 +1,341 @@
+#[cfg(not(feature = "no-cache"))]
+use std::sync::{Mutex, PoisonError};
+use std::{collections::BTreeSet, convert::Infallible};
+
+use crate::{instruction::fail, validation, vars, Cargo, FeatureError};
+
+/// Provides utilities for inspecting the Cargo features the package is built with.
+pub struct Features(Infallible);
+
+impl Features {
+    /// Returns the names of the features the package is built with.
+    ///
+    /// The names are read from `CARGO_CFG_FEATURE`, which spells them as in the manifest.
+    /// Cargo versions older than 1.80 don't set it, in which case the names are recovered
+    /// from the `CARGO_FEATURE_*` variables, lowercased with `-` in place of `_`.
+    /// That mapping is lossy, so a feature named `foo_bar` is then reported as `foo-bar`,
+    /// and uppercase letters in feature names are lost.
+    ///
+    /// The set is computed once and cached unless the `no-cache` feature is enabled.
+    /// See [`Cargo::invalidate_cache`].
+    pub fn enabled() -> BTreeSet<String> {
+        enabled()
+    }
+
+    /// Checks whether the feature `name` is enabled, by reading the `CARGO_FEATURE_*` variable Cargo derives from it:
+    /// the name uppercased with `-` replaced by `_`, so `foo-bar` and `foo_bar` are checked through `CARGO_FEATURE_FOO_BAR`.
+    ///
+    /// The variable is declared with `rerun-if-env-changed`. Cargo already re-runs the build script when the enabled
+    /// features change, so this only matters for tools running build scripts with hand-set `CARGO_FEATURE_*` variables.
+    /// Like any `rerun-if` instruction, it stops Cargo from re-running the script on every change to the package.
+    ///
+    /// Names Cargo doesn't accept for features, such as ones with non-ASCII characters, panic in debug builds
+    /// and are reported as disabled otherwise, as their uppercased form could match the variable of another feature.
+    pub fn is_enabled(name: &str) -> bool {
+        if let Err(err) = validation::feature_name(name) {
+            validation::debug_check(|| Err(err));
+            return false;
+        }
+
+        let var = env_var_name(name);
+        Cargo::rerun_if_env_changed(&var);
+        std::env::var_os(var).is_some()
+    }
+
+    /// Fails the build with an error explaining how to enable the feature `name` if it isn't enabled.
+    pub fn require(name: &str) {
+        if !Self::is_enabled(name) {
+            fail(format!(
+                "the feature `{name}` must be enabled, {}",
+                example(&[name]),
+            ));
+        }
+    }
+
+    /// Returns the enabled features among `features` if more than one of them is enabled, and an empty list otherwise.
+    pub fn check_mutually_exclusive<'a>(features: &[&'a str]) -> Vec<&'a str> {
+        let enabled = features
+            .iter()
+            .copied()
+            .filter(|feature| is_enabled(feature))
+            .collect::<Vec<_>>();
+
+        if enabled.len() > 1 {
+            enabled
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Fails the build with an error naming the conflicting features if more than one of `features` is enabled.
+    pub fn assert_mutually_exclusive(features: &[&str]) {
+        let conflicting = Self::check_mutually_exclusive(features);
+
+        if !conflicting.is_empty() {
+            fail(format!(
+                "features {} are mutually exclusive, but all of them are enabled. \
+                 If you only enabled one of them, another crate in the dependency graph likely enabled the other, \
+                 as Cargo unifies the features of a package across the whole build",
+                list(&conflicting),
+            ));
+        }
+    }
+
+    /// Returns an error if none of `features` is enabled.
+    pub fn check_any(features: &[&str]) -> Result<(), FeatureError> {
+        if features.iter().any(|feature| is_enabled(feature)) {
+            Ok(())
+        } else {
+            Err(FeatureError::NoneEnabled(
+                features.iter().map(|&feature| feature.to_owned()).collect(),
+            ))
+        }
+    }
+
+    /// Returns the enabled feature among `features`, or an error if none or more than one of them is enabled.
+    pub fn check_exactly_one<'a>(features: &[&'a str]) -> Result<&'a str, FeatureError> {
+        let enabled = features
+            .iter()
+            .copied()
+            .filter(|feature| is_enabled(feature))
+            .collect::<Vec<_>>();
+
+        match enabled[..] {
+            [] => Err(FeatureError::NoneEnabled(
+                features.iter().map(|&feature| feature.to_owned()).collect(),
+            )),
+            [feature] => Ok(feature),
+            _ => Err(FeatureError::Conflicting(
+                enabled.into_iter().map(str::to_owned).collect(),
+            )),
+        }
+    }
+
+    /// Fails the build with an error listing the valid choices if none of `features` is enabled.
+    pub fn assert_any(features: &[&str]) {
+        if Self::check_any(features).is_err() {
+            fail(format!(
+                "at least one of the features {} must be enabled, {}",
+                list(features),
+                example(features),
+            ));
+        }
+    }
+
+    /// Fails the build with an error if not exactly one of `features` is enabled,
+    /// listing the valid choices when none is and the conflicting ones when several are.
+    pub fn assert_exactly_one(features: &[&str]) {
+        match Self::check_exactly_one(features) {
+            Ok(_) => {}
+            Err(FeatureError::NoneEnabled(_)) => fail(format!(
+                "exactly one of the features {} must be enabled, {}",
+                list(features),
+                example(features),
+            )),
+            Err(FeatureError::Conflicting(conflicting)) => fail(format!(
+                "exactly one of the features {} must be enabled, but {} are enabled. \
+                 If you only enabled one of them, another crate in the dependency graph likely enabled the others, \
+                 as Cargo unifies the features of a package across the whole build",
+                list(features),
+                list(&conflicting),
+            )),
+        }
+    }
+}
+
+/// Maps a feature name to the `CARGO_FEATURE_*` environment variable Cargo sets for it.
+pub(crate) fn env_var_name(feature: &str) -> String {
+    let feature = feature.to_uppercase().replace('-', "_");
+    format!("{}{feature}", vars::CARGO_FEATURE_PREFIX)
+}
+
+/// Checks whether a feature is enabled.
+pub(crate) fn is_enabled(feature: &str) -> bool {
+    std::env::var_os(env_var_name(feature)).is_some()
+}
+
+#[cfg(not(feature = "no-cache"))]
+static ENABLED: Mutex<Option<BTreeSet<String>>> = Mutex::new(None);
+
+/// Returns the names of the enabled features, through the cache unless the `no-cache` feature is enabled.
+pub(crate) fn enabled() -> BTreeSet<String> {
+    #[cfg(not(feature = "no-cache"))]
+    {
+        let mut cache = ENABLED.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.get_or_insert_with(read_enabled).clone()
+    }
+
+    #[cfg(feature = "no-cache")]
+    read_enabled()
+}
+
+/// Clears the cached names of the enabled features.
+pub(crate) fn invalidate_cache() {
+    #[cfg(not(feature = "no-cache"))]
+    ENABLED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+}
+
+/// Reads the names of the enabled features from `CARGO_CFG_FEATURE`,
+/// falling back to the `CARGO_FEATURE_*` variables for older Cargo versions.
+fn read_enabled() -> BTreeSet<String> {
+    if let Some(features) = std::env::var_os(vars::CARGO_CFG_FEATURE) {
+        if let Some(features) = features.to_str() {
+            return features
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_owned)
+                .collect();
+        }
+    }
+
+    std::env::vars_os()
+        .filter_map(|(var, _)| {
+            let feature = var.to_str()?.strip_prefix(vars::CARGO_FEATURE_PREFIX)?;
+            Some(feature.to_lowercase().replace('_', "-"))
+        })
+        .collect()
+}
+
+/// Formats feature names as a comma separated list of code spans.
+fn list(features: &[impl AsRef<str>]) -> String {
+    features
+        .iter()
+        .map(|feature| format!("`{}`", feature.as_ref()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats an example of enabling the first of `features` on the package being built.
+fn example(features: &[&str]) -> String {
+    let package = Cargo::pkg_name().unwrap_or_else(|_| "<package>".to_owned());
+    let feature = features.first().copied().unwrap_or("<feature>");
+
+    format!(
+        "for example with `cargo add {package} --features {feature}` \
+         or `features = [\"{feature}\"]` in the dependency declaration"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_env::TestEnv, BuildScript, Instruction};
+
+    #[test]
+    fn mutually_exclusive_features() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_FEATURE_PREFIX)
+            .set("CARGO_FEATURE_TOKIO", "1");
+
+        assert!(Features::check_mutually_exclusive(&["tokio", "async-std", "smol"]).is_empty());
+
+        env.set("CARGO_FEATURE_SMOL", "1");
+        assert_eq!(
+            Features::check_mutually_exclusive(&["tokio", "async-std", "smol"]),
+            ["tokio", "smol"]
+        );
+    }
+
+    #[test]
+    fn error_message_parts() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_PKG_NAME, "my-crate");
+
+        assert_eq!(list(&["tokio", "smol"]), "`tokio`, `smol`");
+        assert_eq!(
+            example(&["tokio", "smol"]),
+            "for example with `cargo add my-crate --features tokio` \
+             or `features = [\"tokio\"]` in the dependency declaration"
+        );
+    }
+
+    #[test]
+    fn any_and_exactly_one_feature() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_FEATURE_PREFIX);
+        let backends = ["backend-openssl", "backend-rustls"];
+        let none = FeatureError::NoneEnabled(backends.map(str::to_owned).to_vec());
+
+        assert_eq!(Features::check_any(&backends), Err(none.clone()));
+        assert_eq!(Features::check_exactly_one(&backends), Err(none));
+
+        env.set("CARGO_FEATURE_BACKEND_RUSTLS", "1");
+        assert_eq!(Features::check_any(&backends), Ok(()));
+        assert_eq!(Features::check_exactly_one(&backends), Ok("backend-rustls"));
+
+        env.set("CARGO_FEATURE_BACKEND_OPENSSL", "1");
+        assert_eq!(Features::check_any(&backends), Ok(()));
+        assert_eq!(
+            Features::check_exactly_one(&backends),
+            Err(FeatureError::Conflicting(
+                backends.map(str::to_owned).to_vec()
+            ))
+        );
+    }
+
+    #[test]
+    fn enabled_from_cfg_feature() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_FEATURE_PREFIX)
+            .set(vars::CARGO_CFG_FEATURE, "Foo_bar,std");
+
+        assert_eq!(
+            Features::enabled(),
+            BTreeSet::from(["Foo_bar".to_owned(), "std".to_owned()])
+        );
+
+        env.set(vars::CARGO_CFG_FEATURE, "");
+        assert!(Features::enabled().is_empty());
+    }
+
+    #[test]
+    fn enabled_falls_back_to_feature_vars() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_FEATURE_PREFIX)
+            .remove(vars::CARGO_CFG_FEATURE)
+            .set("CARGO_FEATURE_FOO_BAR", "1")
+            .set("CARGO_FEATURE_HTTP2", "1")
+            .set("CARGO_FEATURE_STD", "1");
+
+        assert_eq!(
+            Features::enabled(),
+            BTreeSet::from(["foo-bar".to_owned(), "http2".to_owned(), "std".to_owned()])
+        );
+    }
+
+    #[test]
+    fn env_var_names() {
+        assert_eq!(env_var_name("std"), "CARGO_FEATURE_STD");
+        assert_eq!(env_var_name("foo-bar"), "CARGO_FEATURE_FOO_BAR");
+        assert_eq!(env_var_name("foo_bar"), "CARGO_FEATURE_FOO_BAR");
+    }
+
+    #[test]
+    fn is_enabled_declares_its_variable() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_FEATURE_PREFIX)
+            .set("CARGO_FEATURE_FOO_BAR", "1");
+
+        let mut enabled = false;
+        let instructions = BuildScript::collect(|| enabled = Features::is_enabled("foo-bar"));
+        assert!(enabled);
+        assert_eq!(
+            *instructions,
+            [Instruction::RerunIfEnvChanged(
+                "CARGO_FEATURE_FOO_BAR".to_owned()
+            )]
+        );
+
+        BuildScript::collect(|| enabled = Features::is_enabled("std"));
+        assert!(!enabled);
+    }
+
+    #[test]
+    #[should_panic = "is not a valid feature name"]
+    fn is_enabled_rejects_non_ascii_names() {
+        Features::is_enabled("stra\u{df}e");
+    }
+}
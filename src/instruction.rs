@@ -0,0 +1,550 @@
+use std::{
+    cell::RefCell,
+    fmt::{Display, Formatter},
+    ops::Deref,
+    path::PathBuf,
+};
+
+use crate::LinkSearchKind;
+
+/// Represents a single build instruction, printed to Cargo as a `cargo::` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    /// `rerun-if-changed`
+    RerunIfChanged(PathBuf),
+    /// `rerun-if-env-changed`
+    RerunIfEnvChanged(String),
+    /// `warning`
+    Warning(String),
+    /// `error`
+    Error(String),
+    /// `metadata`
+    Metadata {
+        /// The metadata key
+        key: String,
+        /// The metadata value
+        value: String,
+    },
+    /// `rustc-link-arg`
+    LinkArg(String),
+    /// `rustc-link-arg-bin`
+    LinkArgBin {
+        /// The binary target the argument is passed for
+        bin: String,
+        /// The linker argument
+        flag: String,
+    },
+    /// `rustc-link-arg-bins`
+    LinkArgBins(String),
+    /// `rustc-link-arg-tests`
+    LinkArgTests(String),
+    /// `rustc-link-arg-examples`
+    LinkArgExamples(String),
+    /// `rustc-cdylib-link-arg`
+    CdylibLinkArg(String),
+    /// `rustc-link-lib`, in the `[KIND[:MODIFIERS]=]NAME[:RENAME]` form
+    LinkLib(String),
+    /// `rustc-link-search`
+    LinkSearch {
+        /// The kind of libraries searched for in the directory
+        kind: Option<LinkSearchKind>,
+        /// The directory to search in
+        path: PathBuf,
+    },
+    /// `rustc-flags`
+    Flags(String),
+    /// `rustc-cfg`
+    Cfg {
+        /// The cfg name
+        key: String,
+        /// The cfg value, if any
+        value: Option<String>,
+    },
+    /// `rustc-check-cfg`
+    CheckCfg(String),
+    /// `rustc-env`
+    Env {
+        /// The environment variable name
+        var: String,
+        /// The environment variable value
+        value: String,
+    },
+}
+
+impl Instruction {
+    /// Returns the kind of the instruction.
+    pub fn kind(&self) -> InstructionKind {
+        match self {
+            Instruction::RerunIfChanged(_) => InstructionKind::RerunIfChanged,
+            Instruction::RerunIfEnvChanged(_) => InstructionKind::RerunIfEnvChanged,
+            Instruction::Warning(_) => InstructionKind::Warning,
+            Instruction::Error(_) => InstructionKind::Error,
+            Instruction::Metadata { .. } => InstructionKind::Metadata,
+            Instruction::LinkArg(_) => InstructionKind::LinkArg,
+            Instruction::LinkArgBin { .. } => InstructionKind::LinkArgBin,
+            Instruction::LinkArgBins(_) => InstructionKind::LinkArgBins,
+            Instruction::LinkArgTests(_) => InstructionKind::LinkArgTests,
+            Instruction::LinkArgExamples(_) => InstructionKind::LinkArgExamples,
+            Instruction::CdylibLinkArg(_) => InstructionKind::CdylibLinkArg,
+            Instruction::LinkLib(_) => InstructionKind::LinkLib,
+            Instruction::LinkSearch { .. } => InstructionKind::LinkSearch,
+            Instruction::Flags(_) => InstructionKind::Flags,
+            Instruction::Cfg { .. } => InstructionKind::Cfg,
+            Instruction::CheckCfg(_) => InstructionKind::CheckCfg,
+            Instruction::Env { .. } => InstructionKind::Env,
+        }
+    }
+
+    /// Returns a human-readable explanation of the instruction, such as "re-run the build script if `build.rs` changes",
+    /// to log what a build script does. Use the [`Display`] output for the line Cargo reads.
+    pub fn describe(&self) -> String {
+        match self {
+            Instruction::RerunIfChanged(path) => {
+                format!("re-run the build script if `{}` changes", path.display())
+            }
+            Instruction::RerunIfEnvChanged(env) => {
+                format!("re-run the build script if the environment variable `{env}` changes")
+            }
+            Instruction::Warning(message) => format!("print the warning {message:?}"),
+            Instruction::Error(message) => format!("fail the build with the error {message:?}"),
+            Instruction::Metadata { key, value } => {
+                format!("pass the metadata `{key}` = {value:?} to dependent build scripts")
+            }
+            Instruction::LinkArg(flag) => format!("pass `{flag}` to the linker"),
+            Instruction::LinkArgBin { bin, flag } => {
+                format!("pass `{flag}` to the linker for the binary `{bin}`")
+            }
+            Instruction::LinkArgBins(flag) => format!("pass `{flag}` to the linker for binaries"),
+            Instruction::LinkArgTests(flag) => format!("pass `{flag}` to the linker for tests"),
+            Instruction::LinkArgExamples(flag) => {
+                format!("pass `{flag}` to the linker for examples")
+            }
+            Instruction::CdylibLinkArg(flag) => {
+                format!("pass `{flag}` to the linker for dynamic libraries")
+            }
+            Instruction::LinkLib(lib) => format!("link the native library `{lib}`"),
+            Instruction::LinkSearch {
+                kind: Some(kind),
+                path,
+            } => format!(
+                "search `{}` for libraries of the kind `{kind}`",
+                path.display()
+            ),
+            Instruction::LinkSearch { kind: None, path } => {
+                format!("search `{}` for libraries", path.display())
+            }
+            Instruction::Flags(flags) => format!("pass `{flags}` to the compiler"),
+            Instruction::Cfg {
+                key,
+                value: Some(value),
+            } => format!("set the cfg `{key} = {}`", quote(value)),
+            Instruction::Cfg { key, value: None } => format!("set the cfg `{key}`"),
+            Instruction::CheckCfg(cfg) => format!("declare `{cfg}` as an expected cfg"),
+            Instruction::Env { var, value } => {
+                format!("set the environment variable `{var}` to {value:?} when compiling")
+            }
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::RerunIfChanged(path) => {
+                write!(f, "cargo::rerun-if-changed={}", path.display())
+            }
+            Instruction::RerunIfEnvChanged(env) => write!(f, "cargo::rerun-if-env-changed={env}"),
+            Instruction::Warning(message) => write!(f, "cargo::warning={message}"),
+            Instruction::Error(message) => write!(f, "cargo::error={message}"),
+            Instruction::Metadata { key, value } => write!(f, "cargo::metadata={key}={value}"),
+            Instruction::LinkArg(flag) => write!(f, "cargo::rustc-link-arg={flag}"),
+            Instruction::LinkArgBin { bin, flag } => {
+                write!(f, "cargo::rustc-link-arg-bin={bin}={flag}")
+            }
+            Instruction::LinkArgBins(flag) => write!(f, "cargo::rustc-link-arg-bins={flag}"),
+            Instruction::LinkArgTests(flag) => write!(f, "cargo::rustc-link-arg-tests={flag}"),
+            Instruction::LinkArgExamples(flag) => {
+                write!(f, "cargo::rustc-link-arg-examples={flag}")
+            }
+            Instruction::CdylibLinkArg(flag) => write!(f, "cargo::rustc-cdylib-link-arg={flag}"),
+            Instruction::LinkLib(lib) => write!(f, "cargo::rustc-link-lib={lib}"),
+            Instruction::LinkSearch {
+                kind: Some(kind),
+                path,
+            } => write!(f, "cargo::rustc-link-search={kind}={}", path.display()),
+            Instruction::LinkSearch { kind: None, path } => {
+                write!(f, "cargo::rustc-link-search={}", path.display())
+            }
+            Instruction::Flags(flags) => write!(f, "cargo::rustc-flags={flags}"),
+            Instruction::Cfg {
+                key,
+                value: Some(value),
+            } => write!(f, "cargo::rustc-cfg={key}={}", quote(value)),
+            Instruction::Cfg { key, value: None } => write!(f, "cargo::rustc-cfg={key}"),
+            Instruction::CheckCfg(cfg) => write!(f, "cargo::rustc-check-cfg={cfg}"),
+            Instruction::Env { var, value } => write!(f, "cargo::rustc-env={var}={value}"),
+        }
+    }
+}
+
+/// Represents the kind of an [`Instruction`], without its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InstructionKind {
+    /// `rerun-if-changed`
+    RerunIfChanged,
+    /// `rerun-if-env-changed`
+    RerunIfEnvChanged,
+    /// `warning`
+    Warning,
+    /// `error`
+    Error,
+    /// `metadata`
+    Metadata,
+    /// `rustc-link-arg`
+    LinkArg,
+    /// `rustc-link-arg-bin`
+    LinkArgBin,
+    /// `rustc-link-arg-bins`
+    LinkArgBins,
+    /// `rustc-link-arg-tests`
+    LinkArgTests,
+    /// `rustc-link-arg-examples`
+    LinkArgExamples,
+    /// `rustc-cdylib-link-arg`
+    CdylibLinkArg,
+    /// `rustc-link-lib`
+    LinkLib,
+    /// `rustc-link-search`
+    LinkSearch,
+    /// `rustc-flags`
+    Flags,
+    /// `rustc-cfg`
+    Cfg,
+    /// `rustc-check-cfg`
+    CheckCfg,
+    /// `rustc-env`
+    Env,
+}
+
+/// A recorded sequence of instructions, in the order they were emitted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Instructions(Vec<Instruction>);
+
+impl Instructions {
+    /// Returns the instructions of the given kind.
+    pub fn filter_kind(&self, kind: InstructionKind) -> Vec<&Instruction> {
+        self.0
+            .iter()
+            .filter(|instruction| instruction.kind() == kind)
+            .collect()
+    }
+
+    /// Returns the names of the linked libraries, without their kind and modifiers.
+    pub fn link_libs(&self) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::LinkLib(lib) => {
+                    Some(lib.split_once('=').map_or(lib.as_str(), |(_, name)| name))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the configured conditional compilation flags with their values.
+    pub fn cfgs(&self) -> Vec<(&str, Option<&str>)> {
+        self.0
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Cfg { key, value } => Some((key.as_str(), value.as_deref())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the recorded instructions.
+    pub fn into_vec(self) -> Vec<Instruction> {
+        self.0
+    }
+}
+
+impl Deref for Instructions {
+    type Target = [Instruction];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<Instruction>> for Instructions {
+    fn from(instructions: Vec<Instruction>) -> Self {
+        Self(instructions)
+    }
+}
+
+impl IntoIterator for Instructions {
+    type Item = Instruction;
+    type IntoIter = std::vec::IntoIter<Instruction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Instructions {
+    type Item = &'a Instruction;
+    type IntoIter = std::slice::Iter<'a, Instruction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Display for Instructions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for instruction in &self.0 {
+            writeln!(f, "{instruction}")?;
+        }
+
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// Stack of the collectors active on the current thread, innermost last.
+    static COLLECTORS: RefCell<Vec<Vec<Instruction>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Emits an instruction, recording it in the innermost active collector or printing it to Cargo otherwise.
+pub(crate) fn emit(instruction: Instruction) {
+    let depth = COLLECTORS.with_borrow(Vec::len);
+    emit_into(depth, instruction);
+}
+
+/// Emits an instruction into the collector at `depth`, or prints it to Cargo if `depth` is zero.
+pub(crate) fn emit_into(depth: usize, instruction: Instruction) {
+    let instruction = COLLECTORS.with_borrow_mut(|collectors| {
+        match depth.checked_sub(1).and_then(|i| collectors.get_mut(i)) {
+            Some(collector) => {
+                collector.push(instruction);
+                None
+            }
+            None => Some(instruction),
+        }
+    });
+
+    if let Some(instruction) = instruction {
+        println!("{instruction}");
+    }
+}
+
+/// Starts a new innermost collector on the current thread, returning its depth.
+pub(crate) fn push_collector() -> usize {
+    COLLECTORS.with_borrow_mut(|collectors| {
+        collectors.push(Vec::new());
+        collectors.len()
+    })
+}
+
+/// Takes the instructions recorded so far by the collector at `depth`.
+pub(crate) fn take_collected(depth: usize) -> Vec<Instruction> {
+    COLLECTORS.with_borrow_mut(|collectors| {
+        collectors
+            .get_mut(depth - 1)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    })
+}
+
+/// Returns a copy of the instructions recorded so far by the collector at `depth`, leaving them in place.
+pub(crate) fn peek_collected(depth: usize) -> Vec<Instruction> {
+    COLLECTORS.with_borrow(|collectors| collectors.get(depth - 1).cloned().unwrap_or_default())
+}
+
+/// Stops the innermost collector on the current thread.
+pub(crate) fn pop_collector() {
+    COLLECTORS.with_borrow_mut(Vec::pop);
+}
+
+/// Runs `f`, recording the instructions it emits on the current thread instead of printing them.
+pub(crate) fn collect(f: impl FnOnce()) -> Instructions {
+    struct Guard;
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            pop_collector();
+        }
+    }
+
+    let depth = push_collector();
+    let guard = Guard;
+    f();
+    let instructions = take_collected(depth);
+    drop(guard);
+
+    Instructions(instructions)
+}
+
+/// Reports `message` as a build error and exits the build script.
+///
+/// The error is printed to Cargo directly, bypassing the active collectors,
+/// as exiting skips the destructors that would otherwise flush them.
+pub(crate) fn fail(message: String) -> ! {
+    println!("{}", Instruction::Error(message));
+    std::process::exit(1);
+}
+
+/// Quotes a cfg value as a Rust string literal, escaping backslashes and double quotes.
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_cfg_values() {
+        assert_eq!(quote("plain"), r#""plain""#);
+        assert_eq!(quote(r#"C:\"x""#), r#""C:\\\"x\"""#);
+    }
+
+    #[test]
+    fn display_as_cargo_lines() {
+        let cases = [
+            (
+                Instruction::RerunIfChanged("build.rs".into()),
+                "cargo::rerun-if-changed=build.rs",
+            ),
+            (
+                Instruction::Metadata {
+                    key: "include".to_owned(),
+                    value: "/usr/include".to_owned(),
+                },
+                "cargo::metadata=include=/usr/include",
+            ),
+            (
+                Instruction::LinkArgBin {
+                    bin: "cli".to_owned(),
+                    flag: "-Wl,--as-needed".to_owned(),
+                },
+                "cargo::rustc-link-arg-bin=cli=-Wl,--as-needed",
+            ),
+            (
+                Instruction::LinkSearch {
+                    kind: Some(LinkSearchKind::Native),
+                    path: "/opt/lib".into(),
+                },
+                "cargo::rustc-link-search=native=/opt/lib",
+            ),
+            (
+                Instruction::LinkSearch {
+                    kind: None,
+                    path: "/opt/lib".into(),
+                },
+                "cargo::rustc-link-search=/opt/lib",
+            ),
+            (
+                Instruction::Cfg {
+                    key: "backend".to_owned(),
+                    value: Some(r#"a"b\c"#.to_owned()),
+                },
+                r#"cargo::rustc-cfg=backend="a\"b\\c""#,
+            ),
+            (
+                Instruction::Cfg {
+                    key: "nightly".to_owned(),
+                    value: None,
+                },
+                "cargo::rustc-cfg=nightly",
+            ),
+        ];
+
+        for (instruction, line) in cases {
+            assert_eq!(instruction.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn nested_collectors() {
+        let mut inner = Instructions::default();
+        let outer = collect(|| {
+            emit(Instruction::Warning("outer".to_owned()));
+            inner = collect(|| emit(Instruction::Warning("inner".to_owned())));
+            emit(Instruction::LinkLib("static=z".to_owned()));
+        });
+
+        assert_eq!(*inner, [Instruction::Warning("inner".to_owned())]);
+        assert_eq!(outer.len(), 2);
+        assert_eq!(outer.link_libs(), ["z"]);
+        assert_eq!(outer.filter_kind(InstructionKind::Warning).len(), 1);
+        assert_eq!(
+            outer.to_string(),
+            "cargo::warning=outer\ncargo::rustc-link-lib=static=z\n"
+        );
+    }
+
+    #[test]
+    fn queries_a_mixed_recording() {
+        let instructions = collect(|| {
+            emit(Instruction::LinkLib("static:+whole-archive=z".to_owned()));
+            emit(Instruction::Cfg {
+                key: "nightly".to_owned(),
+                value: None,
+            });
+            emit(Instruction::Warning("w".to_owned()));
+            emit(Instruction::LinkLib("ssl".to_owned()));
+            emit(Instruction::Cfg {
+                key: "backend".to_owned(),
+                value: Some("rustls".to_owned()),
+            });
+        });
+
+        assert_eq!(instructions.link_libs(), ["z", "ssl"]);
+        assert_eq!(
+            instructions.cfgs(),
+            [("nightly", None), ("backend", Some("rustls"))]
+        );
+        assert_eq!(
+            instructions.filter_kind(InstructionKind::Warning),
+            [&Instruction::Warning("w".to_owned())]
+        );
+        assert!(instructions
+            .filter_kind(InstructionKind::LinkSearch)
+            .is_empty());
+    }
+
+    #[test]
+    fn descriptions() {
+        assert_eq!(
+            Instruction::RerunIfChanged("build.rs".into()).describe(),
+            "re-run the build script if `build.rs` changes"
+        );
+        assert_eq!(
+            Instruction::Cfg {
+                key: "backend".to_owned(),
+                value: Some("gl".to_owned()),
+            }
+            .describe(),
+            r#"set the cfg `backend = "gl"`"#
+        );
+        assert_eq!(
+            Instruction::LinkSearch {
+                kind: Some(LinkSearchKind::Native),
+                path: "/opt/lib".into(),
+            }
+            .describe(),
+            "search `/opt/lib` for libraries of the kind `native`"
+        );
+    }
+}
@@ -1,7 +1,107 @@
 #![doc = include_str!("../README.md")]
 
+/// Defines functions returning the values of environment variables, with the same shape as the getters of [`Cargo`].
+///
+/// Each entry generates a `pub fn` returning `Result<T, EnvVarError>`, where `T` implements [`FromEnvVar`],
+/// which includes [`String`], [`PathBuf`](std::path::PathBuf), [`OsString`](std::ffi::OsString), [`bool`] and the numeric types.
+/// An entry can also name a second function, after a `|`, returning `Result<Option<T>, EnvVarError>` that is `None` for an empty variable,
+/// or after a `?`, returning `Result<Option<T>, EnvVarError>` that is `None` for a variable that isn't set,
+/// for variables only set in some contexts.
+/// Ending an entry with `, rerun` makes the functions register the variable with [`Cargo::rerun_if_env_changed`] when called.
+///
+/// ```
+/// mod sdk {
+///     build_instructions::env_getters! {
+///         /// Returns the directory of the SDK to link against.
+///         sdk_dir | sdk_dir_opt: std::path::PathBuf => "MYCRATE_SDK_DIR", rerun;
+///         /// Returns the number of worker threads of the code generator.
+///         codegen_threads ? codegen_threads_opt: u32 => "MYCRATE_CODEGEN_THREADS";
+///     }
+/// }
+///
+/// if let Ok(Some(sdk_dir)) = sdk::sdk_dir_opt() {
+///     build_instructions::Rustc::link_search(sdk_dir.join("lib"), None);
+/// }
+/// ```
+#[macro_export]
+macro_rules! env_getters {
+    () => {};
+    ($(#[$meta: meta])* $name: ident $(| $opt_name: ident)? $(? $unset_name: ident)?: $result: ty => $env: expr $(, $rerun: ident)?; $($rest: tt)*) => {
+        $(#[$meta])*
+        pub fn $name() -> Result<$result, $crate::EnvVarError> {
+            $($crate::env_getters!(@$rerun $env);)?
+            <$result as $crate::FromEnvVar>::read_env_var($env)
+        }
+
+        $crate::env_getters!(@opt $name [$($opt_name)?] $result => $env, [$($rerun)?]);
+        $crate::env_getters!(@unset $name [$($unset_name)?] $result => $env, [$($rerun)?]);
+        $crate::env_getters!($($rest)*);
+    };
+    (@opt $name: ident [] $result: ty => $env: expr, [$($rerun: ident)?]) => {};
+    (@opt $name: ident [$opt_name: ident] $result: ty => $env: expr, [$($rerun: ident)?]) => {
+        #[doc = concat!("Like `", stringify!($name), "`, but returns `None` when the variable is empty,")]
+        #[doc = "as Cargo sets it to an empty string when the manifest omits the field"]
+        pub fn $opt_name() -> Result<Option<$result>, $crate::EnvVarError> {
+            $($crate::env_getters!(@$rerun $env);)?
+            <$result as $crate::FromEnvVar>::read_env_var_opt($env)
+        }
+    };
+    (@unset $name: ident [] $result: ty => $env: expr, [$($rerun: ident)?]) => {};
+    (@unset $name: ident [$unset_name: ident] $result: ty => $env: expr, [$($rerun: ident)?]) => {
+        #[doc = concat!("Like `", stringify!($name), "`, but returns `None` when the variable isn't set,")]
+        #[doc = "as Cargo only sets it in some contexts"]
+        pub fn $unset_name() -> Result<Option<$result>, $crate::EnvVarError> {
+            $($crate::env_getters!(@$rerun $env);)?
+            match <$result as $crate::FromEnvVar>::read_env_var($env) {
+                Err(err) if err.is_not_present() => Ok(None),
+                value => value.map(Some),
+            }
+        }
+    };
+    (@rerun $env: expr) => {
+        $crate::Cargo::rerun_if_env_changed($env)
+    };
+}
+
+mod artifact;
+mod build_env;
+mod build_script;
 mod cargo;
+mod cargo_env;
+mod dep;
+mod env_var;
+mod error;
+mod features;
+mod instruction;
+mod manifest;
+mod metadata;
+mod package;
+mod probe;
+mod profile;
 mod rustc;
+mod rustc_info;
+mod target;
+#[cfg(test)]
+mod test_env;
+mod validation;
+pub mod vars;
+mod version;
 
+pub use artifact::Artifact;
+pub use build_env::BuildEnv;
+pub use build_script::{BuildScript, CfgConflict, InstructionConflict, LinkGroup, LinkOrder};
 pub use cargo::Cargo;
+pub use cargo_env::CargoEnv;
+pub use dep::{Dep, DepMetadata};
+pub use env_var::{parse_env_value, FromEnvVar};
+pub use error::*;
+pub use features::Features;
+pub use instruction::{Instruction, InstructionKind, Instructions};
+pub use metadata::MetadataStore;
+pub use package::{Author, LicenseExpr, PackageInfo};
+pub use probe::*;
+pub use profile::{OptLevel, Profile};
 pub use rustc::*;
+pub use rustc_info::*;
+pub use target::*;
+pub use version::Version;
@@ -0,0 +1,171 @@
+/// Finds the scalar value at `path`, a dotted key relative to `[package.metadata]`, in the contents of a `Cargo.toml`.
+///
+/// This is a line-based scan supporting a small subset of TOML: table headers, `key = value` pairs with bare,
+/// quoted or dotted keys, and single-line basic strings, literal strings, numbers and booleans as values.
+/// Arrays, inline tables, multi-line strings and arrays of tables aren't supported.
+pub(crate) fn find_metadata_value(contents: &str, path: &str) -> Option<String> {
+    let wanted = format!("package.metadata.{path}");
+    let mut table = Some(String::new());
+
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with("[[") {
+            table = None;
+        } else if let Some(header) = line.strip_prefix('[') {
+            let header = header.split(']').next().unwrap_or_default();
+            table = Some(normalize_key(header));
+        } else if let (Some(table), Some((key, value))) = (&table, line.split_once('=')) {
+            let key = normalize_key(key);
+            let full_key = if table.is_empty() {
+                key
+            } else {
+                format!("{table}.{key}")
+            };
+
+            if full_key == wanted {
+                return parse_scalar(value);
+            }
+        }
+    }
+
+    None
+}
+
+/// Checks whether the contents of a `Cargo.toml` declare a `[workspace]` table, or one of its subtables.
+pub(crate) fn has_workspace_table(contents: &str) -> bool {
+    contents.lines().map(str::trim).any(|line| {
+        line.strip_prefix('[')
+            .filter(|header| !header.starts_with('['))
+            .and_then(|header| header.split(']').next())
+            .is_some_and(|header| {
+                let header = normalize_key(header);
+                header == "workspace" || header.starts_with("workspace.")
+            })
+    })
+}
+
+/// Converts a byte offset in `contents` into a line and a column, both starting from 1.
+#[cfg(feature = "toml")]
+pub(crate) fn line_and_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Normalizes a possibly dotted key, trimming whitespace around its parts and removing quotes.
+fn normalize_key(key: &str) -> String {
+    key.split('.')
+        .map(|part| part.trim().trim_matches(['"', '\'']))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Parses a single-line scalar value, dropping a trailing comment.
+fn parse_scalar(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Some(parsed),
+                '\\' => match chars.next()? {
+                    'n' => parsed.push('\n'),
+                    't' => parsed.push('\t'),
+                    'r' => parsed.push('\r'),
+                    c => parsed.push(c),
+                },
+                c => parsed.push(c),
+            }
+        }
+        None
+    } else if let Some(rest) = value.strip_prefix('\'') {
+        rest.split_once('\'').map(|(literal, _)| literal.to_owned())
+    } else {
+        let value = value.split('#').next().unwrap_or_default().trim();
+        let is_scalar = !value.is_empty() && !value.starts_with(['[', '{']);
+        is_scalar.then(|| value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[package]
+name = "sample"
+
+[package.metadata]
+plain = "value" # comment
+"quoted.key" = 'literal \n'
+
+[package.metadata.docs]
+escaped = "a\"b\tc"
+count = 3 # comment
+list = ["a"]
+
+[package.metadata.system-deps.zlib]
+version = "1.2"
+
+[[package.metadata.entries]]
+ignored = "yes"
+
+[workspace.dependencies]
+"#;
+
+    #[test]
+    fn metadata_values() {
+        let cases = [
+            ("plain", Some("value")),
+            ("quoted.key", Some(r"literal \n")),
+            ("docs.escaped", Some("a\"b\tc")),
+            ("docs.count", Some("3")),
+            ("docs.list", None),
+            ("system-deps.zlib.version", Some("1.2")),
+            ("entries.ignored", None),
+            ("missing", None),
+        ];
+
+        for (path, value) in cases {
+            assert_eq!(
+                find_metadata_value(MANIFEST, path).as_deref(),
+                value,
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn dotted_keys_under_package() {
+        let manifest = "[package]\nmetadata.docs . \"rs\" = true\n";
+        assert_eq!(
+            find_metadata_value(manifest, "docs.rs").as_deref(),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn workspace_tables() {
+        assert!(has_workspace_table(MANIFEST));
+        assert!(has_workspace_table("[ workspace ]\n"));
+        assert!(!has_workspace_table(
+            "[package]\nworkspace = \"..\"\n[[workspace.x]]\n"
+        ));
+        assert!(!has_workspace_table("[workspaces]\n"));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn line_and_column_of_offsets() {
+        let contents = "a = 1\nbé = 2\n";
+        assert_eq!(line_and_column(contents, 0), (1, 1));
+        assert_eq!(line_and_column(contents, 6), (2, 1));
+        assert_eq!(line_and_column(contents, 9), (2, 3));
+        assert_eq!(line_and_column(contents, 100), (3, 1));
+    }
+}
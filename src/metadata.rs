@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use crate::{dep, validation, Cargo, Instruction, InstructionError, Instructions};
+
+/// Collects the metadata a package with a `links` key passes to its dependents,
+/// so that the whole set of keys can be emitted at once and checked as one contract.
+///
+/// Setting a key again replaces its value, as Cargo passes the last value emitted for a key.
+/// The store serializes to a manifest listing every key with its value, one `key=value` per line in key order,
+/// which makes the metadata surface of a producer easy to compare against an expected one.
+///
+/// ```
+/// use build_instructions::{BuildScript, MetadataStore};
+///
+/// fn producer() {
+///     MetadataStore::new()
+///         .set("include", "/opt/z/include")
+///         .set("version", "1.3")
+///         .emit();
+/// }
+///
+/// let emitted = MetadataStore::from_instructions(&BuildScript::collect(producer));
+/// assert_eq!(emitted.manifest(), "include=/opt/z/include\nversion=1.3\n");
+/// assert_eq!(emitted.env_vars("z")["DEP_Z_VERSION"], "1.3");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataStore {
+    entries: BTreeMap<String, String>,
+}
+
+impl MetadataStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the metadata set by recorded instructions, such as those returned by [`BuildScript::collect`](crate::BuildScript::collect).
+    pub fn from_instructions(instructions: &Instructions) -> Self {
+        let entries = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Metadata { key, value } => Some((key.clone(), value.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Sets the metadata `key` to `value`, replacing any previous value.
+    pub fn set(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+        let key = key.as_ref();
+        let value = value.as_ref();
+        validation::debug_check(|| validation::key(key).and_then(|_| validation::line(value)));
+
+        self.entries.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Fallible version of [`MetadataStore::set`] that rejects invalid keys and values containing line breaks.
+    pub fn try_set(
+        &mut self,
+        key: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<&mut Self, InstructionError> {
+        let key = key.as_ref();
+        let value = value.as_ref();
+        validation::key(key)?;
+        validation::line(value)?;
+        Ok(self.set(key, value))
+    }
+
+    /// Returns the value of the metadata `key`, if it's set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// Returns the set keys with their values, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Returns whether no key is set.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Emits every set key with [`Cargo::metadata`], in key order.
+    pub fn emit(&self) {
+        for (key, value) in self.iter() {
+            Cargo::metadata(key, value);
+        }
+    }
+
+    /// Returns the manifest of the set keys, one `key=value` per line in key order.
+    pub fn manifest(&self) -> String {
+        self.iter()
+            .map(|(key, value)| format!("{key}={value}\n"))
+            .collect()
+    }
+
+    /// Returns the `DEP_*` environment variables the build scripts of dependents see for the set keys
+    /// when the package has `links = "<links_name>"`, with their values.
+    pub fn env_vars(&self, links_name: &str) -> BTreeMap<String, String> {
+        self.iter()
+            .map(|(key, value)| (dep::env_var_name(links_name, key), value.to_owned()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildScript;
+
+    /// A producer exporting its metadata both through a store and directly.
+    fn producer() {
+        let mut store = MetadataStore::new();
+        store
+            .set("root", "/opt/z")
+            .set("include", "/opt/z/include")
+            .set("version", "1.2");
+        store.set("version", "1.3");
+        store.emit();
+
+        Cargo::metadata("static", "1");
+        Cargo::warning("not metadata");
+    }
+
+    #[test]
+    fn captures_metadata_of_producer() {
+        let emitted = MetadataStore::from_instructions(&BuildScript::collect(producer));
+
+        assert_eq!(
+            emitted.manifest(),
+            "include=/opt/z/include\nroot=/opt/z\nstatic=1\nversion=1.3\n"
+        );
+        assert_eq!(
+            emitted.env_vars("z-sys"),
+            BTreeMap::from([
+                ("DEP_Z_SYS_INCLUDE".to_owned(), "/opt/z/include".to_owned()),
+                ("DEP_Z_SYS_ROOT".to_owned(), "/opt/z".to_owned()),
+                ("DEP_Z_SYS_STATIC".to_owned(), "1".to_owned()),
+                ("DEP_Z_SYS_VERSION".to_owned(), "1.3".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn emits_in_key_order() {
+        let instructions = BuildScript::collect(|| {
+            MetadataStore::new().set("b", "2").set("a", "1").emit();
+        });
+
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::metadata=a=1\ncargo::metadata=b=2\n"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let mut store = MetadataStore::new();
+
+        assert!(store.try_set("include", "/opt/z\n/include").is_err());
+        assert!(store.try_set("", "value").is_err());
+        assert!(store.is_empty());
+    }
+}
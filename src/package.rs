@@ -0,0 +1,401 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::{LicenseError, Version};
+
+/// Represents the metadata of the package being built from the `CARGO_PKG_*` variables,
+/// read at once by [`Cargo::package_info`](crate::Cargo::package_info).
+///
+/// The name and the version are always set by Cargo. The other fields are `None`,
+/// or empty for the authors, when the manifest omits them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageInfo {
+    /// The name of the package, from `CARGO_PKG_NAME`
+    pub name: String,
+    /// The version of the package, from `CARGO_PKG_VERSION`
+    pub version: Version,
+    /// The authors of the package, from `CARGO_PKG_AUTHORS`
+    pub authors: Vec<Author>,
+    /// The description of the package, from `CARGO_PKG_DESCRIPTION`
+    pub description: Option<String>,
+    /// The home page of the package, from `CARGO_PKG_HOMEPAGE`
+    pub homepage: Option<String>,
+    /// The repository of the package, from `CARGO_PKG_REPOSITORY`
+    pub repository: Option<String>,
+    /// The license expression of the package, from `CARGO_PKG_LICENSE`
+    pub license: Option<String>,
+    /// The path to the license file of the package, from `CARGO_PKG_LICENSE_FILE`
+    pub license_file: Option<PathBuf>,
+    /// The path to the README file of the package, from `CARGO_PKG_README`
+    pub readme: Option<PathBuf>,
+    /// The minimum supported Rust version of the package, from `CARGO_PKG_RUST_VERSION`
+    pub rust_version: Option<String>,
+}
+
+/// Represents an author of the package, as listed in the `authors` field of the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Author {
+    /// The name of the author
+    pub name: String,
+    /// The email address of the author, if the entry has one in angle brackets
+    pub email: Option<String>,
+}
+
+impl Author {
+    /// Parses an entry shaped like `Jane Doe <jane@example.com>`.
+    /// Entries without a well-formed angle-bracketed email are taken as a name only.
+    pub fn parse(entry: &str) -> Self {
+        let entry = entry.trim();
+
+        let email = entry
+            .strip_suffix('>')
+            .and_then(|rest| rest.rsplit_once('<'))
+            .filter(|(_, email)| !email.is_empty() && !email.contains(['<', '>']));
+
+        match email {
+            Some((name, email)) => Self {
+                name: name.trim().to_owned(),
+                email: Some(email.trim().to_owned()),
+            },
+            None => Self {
+                name: entry.to_owned(),
+                email: None,
+            },
+        }
+    }
+}
+
+impl Display for Author {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.email {
+            Some(email) if self.name.is_empty() => write!(f, "<{email}>"),
+            Some(email) => write!(f, "{} <{email}>", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Parses the colon separated list of authors from `CARGO_PKG_AUTHORS`, skipping empty entries.
+pub(crate) fn parse_authors(authors: &str) -> Vec<Author> {
+    authors
+        .split(':')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(Author::parse)
+        .collect()
+}
+
+/// Represents an SPDX license expression, such as `MIT OR Apache-2.0`, as found in the `license` field of the manifest.
+///
+/// `WITH` binds tighter than `AND`, which binds tighter than `OR`. Operators may be uppercase or lowercase,
+/// and the deprecated `/` separator, as in `MIT/Apache-2.0`, is read as `OR`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LicenseExpr {
+    /// A license identifier, such as `MIT`, `GPL-2.0+` or `LicenseRef-Proprietary`
+    Id(String),
+    /// A license identifier with an exception, such as `Apache-2.0 WITH LLVM-exception`
+    WithException {
+        /// The license identifier
+        license: String,
+        /// The exception identifier
+        exception: String,
+    },
+    /// Both expressions apply
+    And(Box<LicenseExpr>, Box<LicenseExpr>),
+    /// Either expression applies, at the choice of the user
+    Or(Box<LicenseExpr>, Box<LicenseExpr>),
+}
+
+impl LicenseExpr {
+    /// Returns the license identifiers in the expression, without the exceptions.
+    pub fn identifiers(&self) -> BTreeSet<&str> {
+        let mut identifiers = BTreeSet::new();
+        self.collect_identifiers(&mut identifiers);
+        identifiers
+    }
+
+    /// Adds the license identifiers in the expression to `identifiers`.
+    fn collect_identifiers<'a>(&'a self, identifiers: &mut BTreeSet<&'a str>) {
+        match self {
+            LicenseExpr::Id(license) | LicenseExpr::WithException { license, .. } => {
+                identifiers.insert(license);
+            }
+            LicenseExpr::And(left, right) | LicenseExpr::Or(left, right) => {
+                left.collect_identifiers(identifiers);
+                right.collect_identifiers(identifiers);
+            }
+        }
+    }
+}
+
+impl FromStr for LicenseExpr {
+    type Err = LicenseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = LicenseParser {
+            expression: s,
+            tokens: tokenize(s),
+            next: 0,
+        };
+
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            Some((position, _)) => Err(parser.error(*position, "expected an operator")),
+            None => Ok(expr),
+        }
+    }
+}
+
+impl Display for LicenseExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LicenseExpr::Id(license) => write!(f, "{license}"),
+            LicenseExpr::WithException { license, exception } => {
+                write!(f, "{license} WITH {exception}")
+            }
+            LicenseExpr::And(left, right) => {
+                for (i, operand) in [left, right].into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " AND ")?;
+                    }
+                    match **operand {
+                        LicenseExpr::Or(..) => write!(f, "({operand})")?,
+                        _ => write!(f, "{operand}")?,
+                    }
+                }
+
+                Ok(())
+            }
+            LicenseExpr::Or(left, right) => write!(f, "{left} OR {right}"),
+        }
+    }
+}
+
+/// Splits a license expression into parentheses, `/` separators and words, each with its byte position.
+fn tokenize(expression: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut word_start = None;
+
+    for (position, c) in expression.char_indices() {
+        let is_separator = c.is_whitespace() || matches!(c, '(' | ')' | '/');
+        if !is_separator {
+            word_start.get_or_insert(position);
+            continue;
+        }
+
+        if let Some(start) = word_start.take() {
+            tokens.push((start, &expression[start..position]));
+        }
+        if !c.is_whitespace() {
+            tokens.push((position, &expression[position..position + 1]));
+        }
+    }
+    if let Some(start) = word_start {
+        tokens.push((start, &expression[start..]));
+    }
+
+    tokens
+}
+
+/// Recursive descent parser of license expressions, with one method per precedence level.
+struct LicenseParser<'a> {
+    expression: &'a str,
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+}
+
+impl LicenseParser<'_> {
+    fn or(&mut self) -> Result<LicenseExpr, LicenseError> {
+        let mut expr = self.and()?;
+        while self.eat(&["OR", "or", "/"]) {
+            expr = LicenseExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<LicenseExpr, LicenseError> {
+        let mut expr = self.primary()?;
+        while self.eat(&["AND", "and"]) {
+            expr = LicenseExpr::And(Box::new(expr), Box::new(self.primary()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<LicenseExpr, LicenseError> {
+        if self.eat(&["("]) {
+            let expr = self.or()?;
+            if !self.eat(&[")"]) {
+                return Err(self.error(self.position(), "expected `)`"));
+            }
+
+            return Ok(expr);
+        }
+
+        let license = self.identifier()?;
+        if self.eat(&["WITH", "with"]) {
+            return Ok(LicenseExpr::WithException {
+                license,
+                exception: self.identifier()?,
+            });
+        }
+
+        Ok(LicenseExpr::Id(license))
+    }
+
+    fn identifier(&mut self) -> Result<String, LicenseError> {
+        let position = self.position();
+        match self.tokens.get(self.next) {
+            Some((_, token)) if is_identifier(token) => {
+                self.next += 1;
+                Ok((*token).to_owned())
+            }
+            _ => Err(self.error(position, "expected a license identifier")),
+        }
+    }
+
+    /// Consumes the next token if it's one of `expected`.
+    fn eat(&mut self, expected: &[&str]) -> bool {
+        let matches = self
+            .tokens
+            .get(self.next)
+            .is_some_and(|(_, token)| expected.contains(token));
+        if matches {
+            self.next += 1;
+        }
+
+        matches
+    }
+
+    /// Returns the byte position of the next token, or the end of the expression.
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.expression.len(), |(position, _)| *position)
+    }
+
+    fn error(&self, position: usize, reason: &str) -> LicenseError {
+        LicenseError::Invalid {
+            expression: self.expression.to_owned(),
+            position,
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+/// Checks whether `token` can be a license or exception identifier rather than an operator or a parenthesis.
+fn is_identifier(token: &str) -> bool {
+    !matches!(token, "AND" | "and" | "OR" | "or" | "WITH" | "with")
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '+' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license(s: &str) -> LicenseExpr {
+        s.parse().unwrap()
+    }
+
+    fn id(license: &str) -> Box<LicenseExpr> {
+        Box::new(LicenseExpr::Id(license.to_owned()))
+    }
+
+    #[test]
+    fn authors() {
+        assert_eq!(
+            parse_authors("Jane Doe <jane@example.com>: :John"),
+            [
+                Author {
+                    name: "Jane Doe".to_owned(),
+                    email: Some("jane@example.com".to_owned()),
+                },
+                Author {
+                    name: "John".to_owned(),
+                    email: None,
+                },
+            ]
+        );
+        assert!(parse_authors("").is_empty());
+        assert_eq!(
+            Author::parse("Jane <jane+ci@example.com>").email.as_deref(),
+            Some("jane+ci@example.com")
+        );
+        assert_eq!(
+            Author::parse(" Jane Doe  <jane@example.com> ").to_string(),
+            "Jane Doe <jane@example.com>"
+        );
+        assert_eq!(Author::parse("<a@b.c>").to_string(), "<a@b.c>");
+        assert_eq!(Author::parse("Jane <>").email, None);
+        assert_eq!(Author::parse("Jane <a>b>").email, None);
+    }
+
+    #[test]
+    fn license_precedence() {
+        assert_eq!(
+            license("MIT OR Apache-2.0 AND BSD-3-Clause"),
+            LicenseExpr::Or(
+                id("MIT"),
+                Box::new(LicenseExpr::And(id("Apache-2.0"), id("BSD-3-Clause")))
+            )
+        );
+        assert_eq!(
+            license("(MIT or Apache-2.0) and Zlib"),
+            LicenseExpr::And(
+                Box::new(LicenseExpr::Or(id("MIT"), id("Apache-2.0"))),
+                id("Zlib")
+            )
+        );
+        assert_eq!(
+            license("Apache-2.0 WITH LLVM-exception"),
+            LicenseExpr::WithException {
+                license: "Apache-2.0".to_owned(),
+                exception: "LLVM-exception".to_owned(),
+            }
+        );
+        assert_eq!(license("MIT/Apache-2.0"), license("MIT OR Apache-2.0"));
+    }
+
+    #[test]
+    fn license_display_and_identifiers() {
+        let expr = license("(MIT OR Apache-2.0) AND GPL-2.0+ WITH Classpath-exception-2.0");
+
+        assert_eq!(
+            expr.to_string(),
+            "(MIT OR Apache-2.0) AND GPL-2.0+ WITH Classpath-exception-2.0"
+        );
+        assert_eq!(
+            expr.identifiers(),
+            BTreeSet::from(["Apache-2.0", "GPL-2.0+", "MIT"])
+        );
+    }
+
+    #[test]
+    fn license_errors() {
+        let cases = [
+            ("", 0, "expected a license identifier"),
+            ("MIT OR", 6, "expected a license identifier"),
+            ("(MIT", 4, "expected `)`"),
+            ("MIT Apache-2.0", 4, "expected an operator"),
+            ("MIT AND AND", 8, "expected a license identifier"),
+        ];
+
+        for (expression, position, reason) in cases {
+            assert_eq!(
+                expression.parse::<LicenseExpr>(),
+                Err(LicenseError::Invalid {
+                    expression: expression.to_owned(),
+                    position,
+                    reason: reason.to_owned(),
+                }),
+                "{expression}"
+            );
+        }
+    }
+}
@@ -0,0 +1,542 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    ffi::OsString,
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+    io,
+    path::Path,
+    process::Stdio,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{rustc_info::rustc_path, vars, Cargo, ProbeError, RustcInfo, RustcInvocation};
+
+/// Represents a Rust edition to compile probes with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Edition {
+    /// Rust 2015
+    E2015,
+    /// Rust 2018
+    E2018,
+    /// Rust 2021
+    E2021,
+    /// Rust 2024
+    E2024,
+}
+
+impl Display for Edition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Edition::E2015 => "2015",
+            Edition::E2018 => "2018",
+            Edition::E2021 => "2021",
+            Edition::E2024 => "2024",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+/// A compile probe that checks whether a snippet of code compiles with the Rust compiler building the crate.
+///
+/// The snippet is written to `OUT_DIR` and compiled as a library with `--emit=metadata`
+/// for the target from the `TARGET` environment variable. Lints are capped to `allow`,
+/// so warnings in the snippet never make it fail. The compiler is invoked through `RUSTC_WRAPPER` when it's set.
+#[derive(Debug, Clone, Default)]
+pub struct Probe {
+    code: String,
+    edition: Option<Edition>,
+    extra_flags: Vec<String>,
+    inherit_rustflags: bool,
+    cache: bool,
+}
+
+impl Probe {
+    /// Creates a probe with empty code, compiled with the compiler's default edition.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the code to compile.
+    pub fn code(mut self, code: impl Into<String>) -> Self {
+        self.code = code.into();
+        self
+    }
+
+    /// Sets the edition to compile the code with.
+    pub fn edition(mut self, edition: Edition) -> Self {
+        self.edition = Some(edition);
+        self
+    }
+
+    /// Adds a flag passed to the compiler, such as `-Ctarget-feature=+avx2`.
+    pub fn extra_flag(mut self, flag: impl Into<String>) -> Self {
+        self.extra_flags.push(flag.into());
+        self
+    }
+
+    /// Sets whether the flags the crate is compiled with, decoded from `CARGO_ENCODED_RUSTFLAGS`,
+    /// are passed to the compiler, so that the probe is compiled under the same conditions as the crate.
+    pub fn inherit_rustflags(mut self, inherit: bool) -> Self {
+        self.inherit_rustflags = inherit;
+        self
+    }
+
+    /// Sets whether the outcome of the probe is cached under `OUT_DIR/.build_instructions/probe-cache`,
+    /// so that re-runs of the build script don't invoke the compiler again.
+    /// Entries are keyed by the code, the compiler and its version, the target and the flags,
+    /// so changing any of them invalidates the cache automatically. Unreadable entries are ignored.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Checks whether the type `ty`, such as `std::sync::LazyLock<u8>`, exists and is well-formed.
+    pub fn type_exists(ty: &str) -> bool {
+        Self::new().code(type_exists_code(ty)).run()
+    }
+
+    /// Checks whether the expression `expr`, such as `1u32.isqrt()`, compiles.
+    pub fn expression_compiles(expr: &str) -> bool {
+        Self::new().code(expression_compiles_code(expr)).run()
+    }
+
+    /// Checks whether the type `ty` implements the trait `trait_path`.
+    pub fn trait_impl(trait_path: &str, ty: &str) -> bool {
+        Self::new().code(trait_impl_code(trait_path, ty)).run()
+    }
+
+    /// Compiles the code, returning whether it compiled successfully.
+    /// Failures of the probe itself, such as a missing compiler or an unwritable `OUT_DIR`, are reported as `false`.
+    pub fn run(&self) -> bool {
+        self.try_run().unwrap_or(false)
+    }
+
+    /// Compiles the code, returning whether it compiled successfully, or an error if the probe itself failed.
+    pub fn try_run(&self) -> Result<bool, ProbeError> {
+        static PROBE_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let out_dir = Cargo::out_dir()?;
+        let flags = self.compiler_flags();
+
+        let cache_path = self.cache.then(|| {
+            out_dir
+                .join(".build_instructions")
+                .join("probe-cache")
+                .join(format!("{:016x}", self.cache_key(&flags)))
+        });
+        if let Some(compiled) = cache_path.as_deref().and_then(read_cached) {
+            return Ok(compiled);
+        }
+
+        let crate_name = format!(
+            "build_instructions_probe_{}",
+            PROBE_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let source_path = out_dir.join(format!("{crate_name}.rs"));
+        std::fs::write(&source_path, &self.code)?;
+
+        let status = RustcInvocation::from_env()
+            .command()
+            .arg("--crate-name")
+            .arg(&crate_name)
+            .args(["--crate-type=lib", "--emit=metadata", "--cap-lints=allow"])
+            .arg("--out-dir")
+            .arg(&out_dir)
+            .args(&flags)
+            .arg(&source_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        let compiled = status.success();
+
+        if let Some(cache_path) = cache_path {
+            // A failure to cache only costs a recompilation next time
+            let _ = write_cached(&cache_path, compiled);
+        }
+
+        Ok(compiled)
+    }
+
+    /// Returns the flags affecting the outcome of the probe.
+    fn compiler_flags(&self) -> Vec<OsString> {
+        let mut flags = Vec::new();
+
+        if let Some(edition) = self.edition {
+            flags.push(format!("--edition={edition}").into());
+        }
+        if let Some(target) = std::env::var_os(vars::TARGET) {
+            flags.push("--target".into());
+            flags.push(target);
+        }
+        if self.inherit_rustflags {
+            flags.extend(
+                Cargo::encoded_rustflags()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(OsString::from),
+            );
+        }
+        flags.extend(self.extra_flags.iter().map(OsString::from));
+
+        flags
+    }
+
+    /// Computes the key of the probe's cache entry from everything that affects its outcome.
+    fn cache_key(&self, flags: &[OsString]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.code.hash(&mut hasher);
+        rustc_path().hash(&mut hasher);
+        RustcInfo::version_verbose().hash(&mut hasher);
+        flags.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Reads a cached probe outcome, returning `None` if it's missing or corrupted.
+fn read_cached(path: &Path) -> Option<bool> {
+    match std::fs::read(path).ok()?.as_slice() {
+        b"1" => Some(true),
+        b"0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Writes a probe outcome to the cache.
+fn write_cached(path: &Path, compiled: bool) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, if compiled { "1" } else { "0" })
+}
+
+/// Lints are allowed in generated probes, so that warnings-as-errors setups don't affect the outcome.
+const PROBE_PRELUDE: &str = "#![allow(warnings)]\n";
+
+/// Generates a probe that names the type `ty` behind a reference, so unsized types work too.
+fn type_exists_code(ty: &str) -> String {
+    format!("{PROBE_PRELUDE}pub fn probe() {{\n    let _: ::std::option::Option<&{ty}> = ::std::option::Option::None;\n}}\n")
+}
+
+/// Generates a probe that evaluates the expression `expr` inside a function body.
+fn expression_compiles_code(expr: &str) -> String {
+    format!("{PROBE_PRELUDE}pub fn probe() {{\n    let _ = {expr};\n}}\n")
+}
+
+/// Generates a probe that asserts the type `ty` implements the trait `trait_path` through a generic bound.
+fn trait_impl_code(trait_path: &str, ty: &str) -> String {
+    format!(
+        "{PROBE_PRELUDE}pub fn probe() {{\n    fn assert_impl<T: ?Sized + {trait_path}>() {{}}\n    assert_impl::<{ty}>();\n}}\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::TestEnv;
+
+    /// Clears the variables Cargo sets for build scripts that would change how probes are compiled.
+    fn probe_env() -> TestEnv {
+        let mut env = TestEnv::new();
+        env.remove(vars::TARGET)
+            .remove(vars::RUSTC_WRAPPER)
+            .remove(vars::RUSTC_WORKSPACE_WRAPPER)
+            .remove(vars::CARGO_ENCODED_RUSTFLAGS);
+        env
+    }
+
+    #[test]
+    fn runs_the_compiler() {
+        let mut env = probe_env();
+        let out_dir = env.temp_dir("probe-run");
+        env.set(vars::OUT_DIR, &out_dir);
+
+        assert_eq!(
+            Probe::new().code("pub fn f() -> u8 { 1 }").try_run().ok(),
+            Some(true)
+        );
+        assert_eq!(
+            Probe::new()
+                .code("pub fn f() -> u8 { \"1\" }")
+                .try_run()
+                .ok(),
+            Some(false)
+        );
+        assert!(Probe::new()
+            .code("#![deny(warnings)] pub fn f() { let unused = 1; }")
+            .run());
+    }
+
+    #[test]
+    fn missing_out_dir() {
+        let mut env = probe_env();
+        env.remove(vars::OUT_DIR);
+
+        assert!(matches!(
+            Probe::new().try_run(),
+            Err(ProbeError::OutDir(err)) if err.is_not_present()
+        ));
+    }
+
+    #[test]
+    fn generated_probes_embed_their_input() {
+        let ty = type_exists_code("std::sync::LazyLock<u8>");
+        assert!(ty.starts_with(PROBE_PRELUDE));
+        assert!(ty.contains("::std::option::Option<&std::sync::LazyLock<u8>>"));
+
+        let expr = expression_compiles_code("1u32.isqrt()");
+        assert!(expr.starts_with(PROBE_PRELUDE));
+        assert!(expr.contains("let _ = 1u32.isqrt();"));
+
+        let trait_impl = trait_impl_code("Send", "str");
+        assert!(trait_impl.starts_with(PROBE_PRELUDE));
+        assert!(trait_impl.contains("fn assert_impl<T: ?Sized + Send>()"));
+        assert!(trait_impl.contains("assert_impl::<str>();"));
+    }
+
+    #[test]
+    fn helpers_probe_the_compiler() {
+        let mut env = probe_env();
+        let out_dir = env.temp_dir("probe-helpers");
+        env.set(vars::OUT_DIR, &out_dir);
+
+        assert!(Probe::type_exists("std::sync::Mutex<u8>"));
+        assert!(Probe::type_exists("str"));
+        assert!(!Probe::type_exists("std::sync::NoSuchType"));
+        assert!(Probe::expression_compiles("1u32.pow(2)"));
+        assert!(!Probe::expression_compiles("1u32.no_such_method()"));
+        assert!(Probe::trait_impl("Send", "str"));
+        assert!(!Probe::trait_impl("Send", "std::rc::Rc<u8>"));
+    }
+
+    #[test]
+    fn compiler_flags_in_order() {
+        let mut env = probe_env();
+        env.set(vars::TARGET, "x86_64-unknown-linux-gnu")
+            .set(vars::CARGO_ENCODED_RUSTFLAGS, "-Copt-level=1\x1f--cfg=foo");
+
+        let probe = Probe::new()
+            .edition(Edition::E2021)
+            .extra_flag("-Ctarget-feature=+avx2");
+        assert_eq!(
+            probe.compiler_flags(),
+            [
+                "--edition=2021",
+                "--target",
+                "x86_64-unknown-linux-gnu",
+                "-Ctarget-feature=+avx2"
+            ]
+        );
+
+        assert_eq!(
+            probe.inherit_rustflags(true).compiler_flags(),
+            [
+                "--edition=2021",
+                "--target",
+                "x86_64-unknown-linux-gnu",
+                "-Copt-level=1",
+                "--cfg=foo",
+                "-Ctarget-feature=+avx2"
+            ]
+        );
+
+        env.remove(vars::TARGET);
+        assert!(Probe::new().compiler_flags().is_empty());
+    }
+
+    /// Writes a shell script named `name` to `dir` that records its arguments in `<name>.log`,
+    /// one per line and followed by `---`, and then runs `then`.
+    #[cfg(unix)]
+    fn recording_script(dir: &Path, name: &str, then: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = dir.join(name);
+        let log = dir.join(format!("{name}.log"));
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$@\" --- >> '{}'\n{then}\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        script
+    }
+
+    /// Returns the arguments of every invocation recorded by a [`recording_script`].
+    #[cfg(unix)]
+    fn recorded_invocations(script: &Path) -> Vec<Vec<String>> {
+        let log = std::fs::read_to_string(script.with_extension("log")).unwrap_or_default();
+        let mut invocations = log
+            .split("---\n")
+            .map(|args| args.lines().map(str::to_owned).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        invocations.pop();
+
+        invocations
+    }
+
+    /// Returns the flags of a recorded probe invocation, between `--out-dir <dir>` and the source file.
+    #[cfg(unix)]
+    fn probe_flags(args: &[String]) -> &[String] {
+        let start = args.iter().position(|arg| arg == "--out-dir").unwrap() + 2;
+        &args[start..args.len() - 1]
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn options_reach_the_compiler() {
+        let mut env = probe_env();
+        let dir = env.temp_dir("probe-options");
+        let rustc = recording_script(&dir, "rustc", "exit 0");
+        env.set(vars::OUT_DIR, &dir)
+            .set(vars::RUSTC, &rustc)
+            .set(vars::TARGET, "thumbv7em-none-eabi")
+            .set(vars::CARGO_ENCODED_RUSTFLAGS, "--cfg\x1ffoo");
+
+        assert!(Probe::new().code("pub fn f() {}").run());
+        let probe = Probe::new()
+            .code("pub fn f() {}")
+            .edition(Edition::E2021)
+            .extra_flag("-Ctarget-feature=+avx2");
+        assert!(probe.clone().run());
+        assert!(probe.inherit_rustflags(true).run());
+
+        let invocations = recorded_invocations(&rustc);
+        assert_eq!(invocations.len(), 3);
+        assert_eq!(invocations[0][0], "--crate-name");
+        for arg in ["--crate-type=lib", "--emit=metadata", "--cap-lints=allow"] {
+            assert!(
+                invocations[0].iter().any(|recorded| recorded == arg),
+                "{arg}"
+            );
+        }
+        assert_eq!(
+            probe_flags(&invocations[0]),
+            ["--target", "thumbv7em-none-eabi"]
+        );
+        assert_eq!(
+            probe_flags(&invocations[1]),
+            [
+                "--edition=2021",
+                "--target",
+                "thumbv7em-none-eabi",
+                "-Ctarget-feature=+avx2"
+            ]
+        );
+        assert_eq!(
+            probe_flags(&invocations[2]),
+            [
+                "--edition=2021",
+                "--target",
+                "thumbv7em-none-eabi",
+                "--cfg",
+                "foo",
+                "-Ctarget-feature=+avx2"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_the_compiler_through_the_wrapper() {
+        let mut env = probe_env();
+        let dir = env.temp_dir("probe-wrapper");
+        let rustc = recording_script(&dir, "rustc", "exit 0");
+        let wrapper = recording_script(&dir, "wrapper", "exec \"$@\"");
+        env.set(vars::OUT_DIR, &dir)
+            .set(vars::RUSTC, &rustc)
+            .set(vars::RUSTC_WRAPPER, &wrapper);
+
+        assert!(Probe::new()
+            .code("pub fn f() {}")
+            .edition(Edition::E2018)
+            .run());
+
+        let wrapped = recorded_invocations(&wrapper);
+        let compiled = recorded_invocations(&rustc);
+        assert_eq!(wrapped.len(), 1);
+        assert_eq!(wrapped[0][0], rustc.to_str().unwrap());
+        assert_eq!(wrapped[0][1..], compiled[0]);
+        assert_eq!(probe_flags(&compiled[0]), ["--edition=2018"]);
+    }
+
+    #[test]
+    fn cache_entries() {
+        let mut env = TestEnv::new();
+        let path = env
+            .temp_dir("probe-cache-entries")
+            .join("nested")
+            .join("entry");
+
+        assert_eq!(read_cached(&path), None);
+        write_cached(&path, true).unwrap();
+        assert_eq!(read_cached(&path), Some(true));
+        write_cached(&path, false).unwrap();
+        assert_eq!(read_cached(&path), Some(false));
+
+        std::fs::write(&path, "yes").unwrap();
+        assert_eq!(read_cached(&path), None);
+    }
+
+    #[test]
+    fn cache_key_depends_on_code_and_flags() {
+        let _env = probe_env();
+        let probe = Probe::new().code("pub fn a() {}");
+
+        assert_eq!(probe.cache_key(&[]), probe.cache_key(&[]));
+        assert_ne!(
+            probe.cache_key(&[]),
+            probe.cache_key(&["--edition=2021".into()])
+        );
+        assert_ne!(
+            probe.cache_key(&[]),
+            Probe::new().code("pub fn b() {}").cache_key(&[])
+        );
+    }
+
+    #[test]
+    fn caches_outcomes() {
+        let mut env = probe_env();
+        let out_dir = env.temp_dir("probe-cache");
+        env.set(vars::OUT_DIR, &out_dir);
+
+        let probe = Probe::new().code("pub fn f() {}").cache(true);
+        let entry = out_dir
+            .join(".build_instructions")
+            .join("probe-cache")
+            .join(format!("{:016x}", probe.cache_key(&probe.compiler_flags())));
+
+        assert!(probe.try_run().unwrap());
+        assert_eq!(read_cached(&entry), Some(true));
+
+        // A cached outcome is trusted over the compiler, and a corrupted one is recomputed
+        std::fs::write(&entry, "0").unwrap();
+        assert!(!probe.try_run().unwrap());
+        std::fs::write(&entry, "corrupted").unwrap();
+        assert!(probe.try_run().unwrap());
+        assert_eq!(read_cached(&entry), Some(true));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cached_probes_skip_the_compiler() {
+        let mut env = probe_env();
+        // The version is part of the cache key and read once per process, so it must come from the real compiler
+        RustcInfo::version_verbose();
+        let dir = env.temp_dir("probe-cache-hits");
+        let rustc = recording_script(&dir, "rustc", "exit 0");
+        env.set(vars::OUT_DIR, &dir).set(vars::RUSTC, &rustc);
+
+        let probe = Probe::new().code("pub fn f() {}").cache(true);
+        assert!(probe.run());
+        assert!(probe.run());
+        assert_eq!(recorded_invocations(&rustc).len(), 1);
+
+        assert!(Probe::new().code("pub fn g() {}").cache(true).run());
+        assert_eq!(recorded_invocations(&rustc).len(), 2);
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    convert::Infallible,
+    ffi::OsString,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{EnvVarErrorKind, FromEnvVar};
+
+/// Represents the profile a package is built with, as reported by the `PROFILE` environment variable.
+///
+/// Cargo only reports `debug` or `release`: custom profiles are reported as whichever of `dev` or `release`
+/// they inherit from, so a `[profile.bench]` build is reported as [`Profile::Release`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// The `dev` profile, or a profile inheriting from it
+    Debug,
+    /// The `release` profile, or a profile inheriting from it
+    Release,
+    /// A value not known to this crate, kept for compatibility with future versions of Cargo
+    Other(String),
+}
+
+impl Profile {
+    /// Checks if the profile is [`Profile::Release`].
+    pub fn is_release(&self) -> bool {
+        *self == Profile::Release
+    }
+}
+
+impl FromStr for Profile {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "debug" => Profile::Debug,
+            "release" => Profile::Release,
+            other => Profile::Other(other.to_owned()),
+        })
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Profile::Debug => "debug",
+            Profile::Release => "release",
+            Profile::Other(other) => other,
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+impl FromEnvVar for Profile {
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+        let Ok(profile) = String::from_env_var(value)?.parse();
+        Ok(profile)
+    }
+}
+
+/// Represents the optimization level of the profile a package is built with, as reported by the `OPT_LEVEL` environment variable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OptLevel {
+    /// No optimizations
+    O0,
+    /// Basic optimizations
+    O1,
+    /// Some optimizations
+    O2,
+    /// All optimizations
+    O3,
+    /// Optimize for binary size
+    Os,
+    /// Optimize for binary size, also turning off loop vectorization
+    Oz,
+    /// A value not known to this crate, kept for compatibility with future versions of Cargo
+    Other(String),
+}
+
+impl OptLevel {
+    /// Returns the matching optimization flag for GCC and Clang, such as `-O2`, or `None` for [`OptLevel::Other`].
+    /// Note that `-Oz` is only supported by Clang and GCC 12 or later.
+    pub fn gnu_flag(&self) -> Option<&'static str> {
+        let flag = match self {
+            OptLevel::O0 => "-O0",
+            OptLevel::O1 => "-O1",
+            OptLevel::O2 => "-O2",
+            OptLevel::O3 => "-O3",
+            OptLevel::Os => "-Os",
+            OptLevel::Oz => "-Oz",
+            OptLevel::Other(_) => return None,
+        };
+
+        Some(flag)
+    }
+
+    /// Returns the closest optimization flag for MSVC, or `None` for [`OptLevel::Other`].
+    /// MSVC has no levels beyond optimizing for size (`/O1`) or speed (`/O2`), so the levels are mapped to these.
+    pub fn msvc_flag(&self) -> Option<&'static str> {
+        let flag = match self {
+            OptLevel::O0 => "/Od",
+            OptLevel::O1 | OptLevel::Os | OptLevel::Oz => "/O1",
+            OptLevel::O2 | OptLevel::O3 => "/O2",
+            OptLevel::Other(_) => return None,
+        };
+
+        Some(flag)
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "0" => OptLevel::O0,
+            "1" => OptLevel::O1,
+            "2" => OptLevel::O2,
+            "3" => OptLevel::O3,
+            "s" => OptLevel::Os,
+            "z" => OptLevel::Oz,
+            other => OptLevel::Other(other.to_owned()),
+        })
+    }
+}
+
+impl Display for OptLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+            OptLevel::O3 => "3",
+            OptLevel::Os => "s",
+            OptLevel::Oz => "z",
+            OptLevel::Other(other) => other,
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+impl FromEnvVar for OptLevel {
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+        let Ok(opt_level) = String::from_env_var(value)?.parse();
+        Ok(opt_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_round_trip() {
+        for (s, profile) in [
+            ("debug", Profile::Debug),
+            ("release", Profile::Release),
+            ("bench", Profile::Other("bench".to_owned())),
+        ] {
+            let Ok(parsed) = s.parse::<Profile>();
+            assert_eq!(parsed, profile);
+            assert_eq!(profile.to_string(), s);
+        }
+
+        assert!(Profile::Release.is_release());
+        assert!(!Profile::Debug.is_release());
+    }
+
+    #[test]
+    fn from_env_var() {
+        assert_eq!(
+            Profile::from_env_var("release".into()),
+            Ok(Profile::Release)
+        );
+        assert_eq!(OptLevel::from_env_var("z".into()), Ok(OptLevel::Oz));
+    }
+
+    #[test]
+    fn opt_level_flags() {
+        let cases = [
+            ("0", Some("-O0"), Some("/Od")),
+            ("1", Some("-O1"), Some("/O1")),
+            ("2", Some("-O2"), Some("/O2")),
+            ("3", Some("-O3"), Some("/O2")),
+            ("s", Some("-Os"), Some("/O1")),
+            ("z", Some("-Oz"), Some("/O1")),
+            ("4", None, None),
+        ];
+
+        for (s, gnu, msvc) in cases {
+            let Ok(opt_level) = s.parse::<OptLevel>();
+            assert_eq!(opt_level.gnu_flag(), gnu, "{s}");
+            assert_eq!(opt_level.msvc_flag(), msvc, "{s}");
+            assert_eq!(opt_level.to_string(), s);
+        }
+    }
+}
@@ -1,10 +1,24 @@
 use std::{
+    collections::BTreeSet,
     convert::Infallible,
+    env::VarError,
     fmt::{Display, Formatter},
     path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use crate::{
+    build_script, features,
+    instruction::{emit, quote, Instruction},
+    validation, vars, BuildEnv, Cargo, EnvVarError, InstructionError, PanicStrategy, Probe,
+    RustcChannel, RustcInfo, RustcVersion, TargetCfg, TargetOs,
 };
 
 /// Represents the different kinds of link search paths used by the Rust compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LinkSearchKind {
     /// Only search for transitive dependencies in this directory
     Dependency,
@@ -33,6 +47,84 @@ impl Display for LinkSearchKind {
     }
 }
 
+/// Represents whether a native library is linked statically or dynamically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkPreference {
+    /// Link the library statically
+    Static,
+    /// Link the library dynamically
+    Dynamic,
+}
+
+impl LinkPreference {
+    /// Picks [`LinkPreference::Static`] when the `static` Cargo feature is enabled,
+    /// or when the environment variable `var` is set to anything other than an empty string or `0`,
+    /// and [`LinkPreference::Dynamic`] otherwise. The build script is re-run when `var` changes.
+    pub fn detect(var: &str) -> Self {
+        Cargo::rerun_if_env_changed(var);
+
+        let env_static =
+            std::env::var_os(var).is_some_and(|value| !value.is_empty() && value != "0");
+        if features::is_enabled("static") || env_static {
+            LinkPreference::Static
+        } else {
+            LinkPreference::Dynamic
+        }
+    }
+}
+
+impl Display for LinkPreference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            LinkPreference::Static => "static",
+            LinkPreference::Dynamic => "dylib",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+/// Determines how an environment variable set to the empty string is treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmptyEnv {
+    /// The variable is treated as if it wasn't set
+    Unset,
+    /// The variable is treated as set to the empty string
+    Set,
+}
+
+/// Whether [`Rustc::cfg`] automatically declares the flags it configures.
+static AUTO_CHECK_CFG: AtomicBool = AtomicBool::new(false);
+
+/// Every cfg declared with [`Rustc::check_cfg`] so far.
+static DECLARED_CFGS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Reports which gates of [`Rustc::version_gates`] passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionGateReport<'a> {
+    /// The detected version of the Rust compiler, or `None` if it couldn't be determined
+    pub rustc_version: Option<RustcVersion>,
+    /// Every gate's cfg name and minimum version, along with whether its cfg was set
+    pub gates: Vec<(&'a str, RustcVersion, bool)>,
+}
+
+impl Display for VersionGateReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.rustc_version {
+            Some(version) => write!(f, "rustc {version}:")?,
+            None => write!(f, "unknown rustc version:")?,
+        }
+
+        for (i, (cfg_name, required, passed)) in self.gates.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            let state = if *passed { "set" } else { "unset" };
+            write!(f, "{separator}{cfg_name} (>= {required}) {state}")?;
+        }
+
+        Ok(())
+    }
+}
+
 /// Provides utilities for interacting with the Rust compiler through Cargo build instructions.
 pub struct Rustc(Infallible);
 
@@ -40,84 +132,1326 @@ impl Rustc {
     /// Passes a single linker argument to the Rust compiler.
     pub fn link_arg(flag: impl AsRef<str>) {
         let flag = flag.as_ref();
-        println!("cargo::rustc-link-arg={flag}");
+        validation::debug_check(|| validation::line(flag));
+        emit(Instruction::LinkArg(flag.to_owned()));
+    }
+
+    /// Fallible version of [`Rustc::link_arg`] that rejects arguments containing line breaks.
+    pub fn try_link_arg(flag: impl AsRef<str>) -> Result<(), InstructionError> {
+        let flag = flag.as_ref();
+        validation::line(flag)?;
+        Self::link_arg(flag);
+        Ok(())
     }
 
     /// Passes a linker argument for a specific binary target.
     pub fn link_arg_bin(bin: impl AsRef<str>, flag: impl AsRef<str>) {
         let bin = bin.as_ref();
         let flag = flag.as_ref();
-        println!("cargo::rustc-link-arg-bin={bin}={flag}");
+        validation::debug_check(|| validation::key(bin).and_then(|_| validation::line(flag)));
+        emit(Instruction::LinkArgBin {
+            bin: bin.to_owned(),
+            flag: flag.to_owned(),
+        });
+    }
+
+    /// Fallible version of [`Rustc::link_arg_bin`] that rejects invalid binary names and line breaks.
+    pub fn try_link_arg_bin(
+        bin: impl AsRef<str>,
+        flag: impl AsRef<str>,
+    ) -> Result<(), InstructionError> {
+        let bin = bin.as_ref();
+        let flag = flag.as_ref();
+        validation::key(bin)?;
+        validation::line(flag)?;
+        Self::link_arg_bin(bin, flag);
+        Ok(())
     }
 
     /// Passes a linker argument for all binary targets.
     pub fn link_arg_bins(flag: impl AsRef<str>) {
         let flag = flag.as_ref();
-        println!("cargo::rustc-link-arg-bins={flag}");
+        validation::debug_check(|| validation::line(flag));
+        emit(Instruction::LinkArgBins(flag.to_owned()));
+    }
+
+    /// Fallible version of [`Rustc::link_arg_bins`] that rejects arguments containing line breaks.
+    pub fn try_link_arg_bins(flag: impl AsRef<str>) -> Result<(), InstructionError> {
+        let flag = flag.as_ref();
+        validation::line(flag)?;
+        Self::link_arg_bins(flag);
+        Ok(())
+    }
+
+    /// Passes the linker arguments whose operating system matches the target being compiled for.
+    /// Every matching entry is emitted, in iteration order.
+    pub fn link_arg_for_target_os(
+        entries: impl IntoIterator<Item = (TargetOs, impl AsRef<str>)>,
+    ) -> Result<(), EnvVarError> {
+        let target_os = TargetCfg::target_os()?;
+
+        for (os, flag) in entries {
+            if os.to_string() == target_os {
+                Self::link_arg(flag);
+            }
+        }
+
+        Ok(())
     }
 
     /// Links a library with the specified name.
     pub fn link_lib(lib: impl AsRef<str>) {
         let lib = lib.as_ref();
-        println!("cargo::rustc-link-lib={lib}");
+        validation::debug_check(|| validation::non_empty_line(lib));
+        emit(Instruction::LinkLib(lib.to_owned()));
+    }
+
+    /// Fallible version of [`Rustc::link_lib`] that rejects empty library names and line breaks.
+    pub fn try_link_lib(lib: impl AsRef<str>) -> Result<(), InstructionError> {
+        let lib = lib.as_ref();
+        validation::non_empty_line(lib)?;
+        Self::link_lib(lib);
+        Ok(())
+    }
+
+    /// Links a library with the specified name, statically or dynamically according to `pref`.
+    pub fn link_lib_pref(name: impl AsRef<str>, pref: LinkPreference) {
+        let name = name.as_ref();
+        Self::link_lib(format!("{pref}={name}"));
+    }
+
+    /// Links the system library `name`, such as `m` or `dl`, only when the target's operating system is one of `platforms`,
+    /// avoiding linker errors on platforms that don't provide it.
+    pub fn link_system_lib(
+        name: impl AsRef<str>,
+        platforms: &[TargetOs],
+    ) -> Result<(), EnvVarError> {
+        let target_os = TargetCfg::target_os()?;
+
+        if platforms.iter().any(|os| os.to_string() == target_os) {
+            Self::link_lib(name);
+        }
+
+        Ok(())
+    }
+
+    /// Links the static library `name` with the `+whole-archive` modifier, keeping every object of the archive
+    /// instead of only the ones referenced by the crate. Needed when objects register themselves through
+    /// static constructors or linker sections, such as plugin registries, which nothing references directly.
+    ///
+    /// Static libraries are bundled into the `rlib` of a library crate by default (`+bundle`),
+    /// where whole-archive only applies to the final link if the crate is linked directly.
+    /// Add `-bundle`, as in `static:+whole-archive,-bundle=name`, through [`Rustc::link_lib`] to defer the library
+    /// to the final link instead, which then needs the library's search path too.
+    pub fn link_whole_archive(name: impl AsRef<str>) {
+        let name = name.as_ref();
+        Self::link_lib(format!("static:+whole-archive={name}"));
+    }
+
+    /// Links the library file at `path`, deriving the name of the library from the file name and the kind from its extension:
+    /// `.a` and `.lib` are linked statically, while `.so`, `.dylib`, `.dll` and `.dll.a` import libraries are linked dynamically.
+    /// The `lib` prefix is stripped from the name unless the target uses the MSVC toolchain, whose libraries don't have it.
+    /// The parent directory of the file is added as a native search path.
+    pub fn link_lib_path(path: impl AsRef<Path>) -> Result<(), InstructionError> {
+        let path = path.as_ref();
+        let is_msvc = TargetCfg::target_env().is_ok_and(|env| env == "msvc");
+        let (name, pref) = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(|file_name| library_name_and_preference(file_name, is_msvc))
+            .ok_or_else(|| InstructionError::UnknownLibraryFile(path.display().to_string()))?;
+        validation::line(name)?;
+
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        if let Some(dir) = dir {
+            Self::try_link_search(dir, LinkSearchKind::Native)?;
+        }
+        Self::link_lib_pref(name, pref);
+
+        Ok(())
     }
 
     /// Passes a linker argument specifically for test builds.
     pub fn link_arg_tests(flag: impl AsRef<str>) {
         let flag = flag.as_ref();
-        println!("cargo::rustc-link-arg-tests={flag}");
+        validation::debug_check(|| validation::line(flag));
+        emit(Instruction::LinkArgTests(flag.to_owned()));
+    }
+
+    /// Fallible version of [`Rustc::link_arg_tests`] that rejects arguments containing line breaks.
+    pub fn try_link_arg_tests(flag: impl AsRef<str>) -> Result<(), InstructionError> {
+        let flag = flag.as_ref();
+        validation::line(flag)?;
+        Self::link_arg_tests(flag);
+        Ok(())
     }
 
     /// Passes a linker argument specifically for example builds.
     pub fn link_arg_examples(flag: impl AsRef<str>) {
         let flag = flag.as_ref();
-        println!("cargo::rustc-link-arg-examples={flag}");
+        validation::debug_check(|| validation::line(flag));
+        emit(Instruction::LinkArgExamples(flag.to_owned()));
+    }
+
+    /// Fallible version of [`Rustc::link_arg_examples`] that rejects arguments containing line breaks.
+    pub fn try_link_arg_examples(flag: impl AsRef<str>) -> Result<(), InstructionError> {
+        let flag = flag.as_ref();
+        validation::line(flag)?;
+        Self::link_arg_examples(flag);
+        Ok(())
     }
 
     /// Specifies a directory for the Rust compiler to search for libraries.
     pub fn link_search(path: impl AsRef<Path>, kind: impl Into<Option<LinkSearchKind>>) {
-        let path = path.as_ref().display();
+        let path = path.as_ref();
         let kind = kind.into();
+        validation::debug_check(|| validation::line(&path.display().to_string()));
+        emit(Instruction::LinkSearch {
+            kind,
+            path: path.to_owned(),
+        });
+    }
 
-        match kind {
-            Some(kind) => println!("cargo::rustc-link-search={kind}={path}"),
-            None => println!("carg::rustc-link-search={path}"),
-        }
+    /// Fallible version of [`Rustc::link_search`] that rejects paths containing line breaks
+    /// or that aren't valid unicode.
+    pub fn try_link_search(
+        path: impl AsRef<Path>,
+        kind: impl Into<Option<LinkSearchKind>>,
+    ) -> Result<(), InstructionError> {
+        let path = path.as_ref();
+        validation::path(path)?;
+        Self::link_search(path, kind);
+        Ok(())
+    }
+
+    /// Specifies `OUT_DIR` as a directory for the Rust compiler to search for libraries,
+    /// for libraries built by the build script itself. `kind` is usually [`LinkSearchKind::Native`].
+    pub fn link_search_out_dir(kind: LinkSearchKind) -> Result<(), EnvVarError> {
+        Self::link_search(Cargo::out_dir()?, kind);
+        Ok(())
     }
 
     /// Passes additional compiler flags to Rust compiler.
+    ///
+    /// Unstable `-Z` flags should be passed with [`Rustc::unstable_flag`] instead,
+    /// which skips them with a warning unless [`RustcInfo::allows_unstable`].
     pub fn flags(flags: impl AsRef<str>) {
         let flags = flags.as_ref();
-        println!("cargo::rustc-flags={flags}");
+        validation::debug_check(|| validation::line(flags));
+        emit(Instruction::Flags(flags.to_owned()));
+    }
+
+    /// Fallible version of [`Rustc::flags`] that rejects flags containing line breaks.
+    pub fn try_flags(flags: impl AsRef<str>) -> Result<(), InstructionError> {
+        let flags = flags.as_ref();
+        validation::line(flags)?;
+        Self::flags(flags);
+        Ok(())
+    }
+
+    /// Passes the unstable `-Z` flag `flag` to the compiler through [`Rustc::flags`] when [`RustcInfo::allows_unstable`],
+    /// and prints a warning that it was skipped otherwise. Returns whether the flag was passed.
+    /// Changes to `RUSTC_BOOTSTRAP` rerun the build script.
+    pub fn unstable_flag(flag: impl AsRef<str>) -> bool {
+        Cargo::rerun_if_env_changed(vars::RUSTC_BOOTSTRAP);
+        apply_unstable_flag(flag.as_ref(), RustcInfo::allows_unstable())
+    }
+
+    /// Passes the codegen option `-C key=value` to the compiler.
+    ///
+    /// Cargo only accepts `-l` and `-L` in `rustc-flags`, so most codegen options can't be passed from a build script.
+    /// `link-arg` and `link-args` are passed through [`Rustc::link_arg`], with `link-args` split on whitespace,
+    /// while any other option is rejected with an error suggesting where to set it instead.
+    pub fn codegen_opt(
+        key: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<(), InstructionError> {
+        let key = key.as_ref();
+        let value = value.as_ref();
+
+        match key.replace('_', "-").as_str() {
+            "link-arg" => Self::try_link_arg(value),
+            "link-args" => value.split_whitespace().try_for_each(Self::try_link_arg),
+            _ => Err(InstructionError::UnsupportedCodegenOption(key.to_owned())),
+        }
+    }
+
+    /// Sets whether every conditional compilation flag configured afterwards is automatically declared as expected,
+    /// preventing the `unexpected_cfgs` lint. While enabled, identical declarations are emitted only once per process,
+    /// including ones made explicitly with [`Rustc::check_cfg`]. Every declaration is remembered until the process exits,
+    /// even while the option is disabled, so re-enabling it doesn't repeat the declarations already emitted.
+    pub fn auto_check_cfg(enabled: bool) {
+        AUTO_CHECK_CFG.store(enabled, Ordering::Relaxed);
     }
 
     /// Configures a conditional compilation flag with an optional value.
     pub fn cfg<'a>(key: impl AsRef<str>, value: impl Into<Option<&'a str>>) {
         let key = key.as_ref();
         let value = value.into();
+        validation::debug_check(|| validation::cfg(key, value));
+
+        if AUTO_CHECK_CFG.load(Ordering::Relaxed) {
+            match value {
+                Some(value) => Self::check_cfg(format!("cfg({key}, values({}))", quote(value))),
+                None => Self::check_cfg(format!("cfg({key})")),
+            }
+        }
+
+        emit(Instruction::Cfg {
+            key: key.to_owned(),
+            value: value.map(str::to_owned),
+        });
+    }
+
+    /// Fallible version of [`Rustc::cfg`] that rejects keys which aren't identifiers and values containing line breaks.
+    pub fn try_cfg<'a>(
+        key: impl AsRef<str>,
+        value: impl Into<Option<&'a str>>,
+    ) -> Result<(), InstructionError> {
+        let key = key.as_ref();
+        let value = value.into();
+        validation::cfg(key, value)?;
+        Self::cfg(key, value);
+        Ok(())
+    }
 
-        match value {
-            Some(value) => println!("cargo::rustc-cfg={key}=\"{value}\""),
-            None => println!("cargo::rustc-cfg={key}"),
+    /// Configures a conditional compilation flag once for every value, in iteration order.
+    /// Repeated values are emitted only once. A buffered [`BuildScript`](crate::BuildScript) doesn't report
+    /// the values of the flag as conflicting.
+    pub fn cfg_values(key: impl AsRef<str>, values: impl IntoIterator<Item = impl AsRef<str>>) {
+        let key = key.as_ref();
+        build_script::allow_multiple_values(key);
+        let mut emitted = BTreeSet::new();
+
+        for value in values {
+            let value = value.as_ref();
+            if emitted.insert(value.to_owned()) {
+                Self::cfg(key, value);
+            }
         }
     }
 
+    /// Fallible version of [`Rustc::cfg_values`] that validates every value before emitting any of them.
+    pub fn try_cfg_values(
+        key: impl AsRef<str>,
+        values: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), InstructionError> {
+        let key = key.as_ref();
+        let values = values.into_iter().collect::<Vec<_>>();
+        for value in &values {
+            validation::cfg(key, Some(value.as_ref()))?;
+        }
+        Self::cfg_values(key, values);
+        Ok(())
+    }
+
     /// Checks the validity of a conditional compilation flag.
     pub fn check_cfg(cfg: impl AsRef<str>) {
         let cfg = cfg.as_ref();
-        println!("cargo::rustc-check-cfg={cfg}");
+        validation::debug_check(|| validation::line(cfg));
+
+        let newly_declared = DECLARED_CFGS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(cfg.to_owned());
+        if newly_declared || !AUTO_CHECK_CFG.load(Ordering::Relaxed) {
+            emit(Instruction::CheckCfg(cfg.to_owned()));
+        }
+    }
+
+    /// Fallible version of [`Rustc::check_cfg`] that rejects declarations containing line breaks.
+    pub fn try_check_cfg(cfg: impl AsRef<str>) -> Result<(), InstructionError> {
+        let cfg = cfg.as_ref();
+        validation::line(cfg)?;
+        Self::check_cfg(cfg);
+        Ok(())
+    }
+
+    /// Declares the full set of values a conditional compilation flag is allowed to take.
+    pub fn check_cfg_values(
+        key: impl AsRef<str>,
+        values: impl IntoIterator<Item = impl AsRef<str>>,
+    ) {
+        let key = key.as_ref();
+        validation::debug_check(|| validation::identifier(key));
+        let values = values
+            .into_iter()
+            .map(|value| quote(value.as_ref()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        validation::debug_check(|| validation::line(&values));
+        emit(Instruction::CheckCfg(format!(
+            "cfg({key}, values({values}))"
+        )));
+    }
+
+    /// Fallible version of [`Rustc::check_cfg_values`] that validates the key and every value before emitting.
+    pub fn try_check_cfg_values(
+        key: impl AsRef<str>,
+        values: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), InstructionError> {
+        let key = key.as_ref();
+        let values = values.into_iter().collect::<Vec<_>>();
+        validation::identifier(key)?;
+        for value in &values {
+            validation::line(value.as_ref())?;
+        }
+        Self::check_cfg_values(key, values);
+        Ok(())
+    }
+
+    /// Configures every `(key, value)` conditional compilation flag in `entries`, declaring each key as expected
+    /// with a single declaration covering all of its values. Declarations are emitted first, in order of first appearance.
+    /// A buffered [`BuildScript`](crate::BuildScript) doesn't report keys listed with several values as conflicting.
+    pub fn cfg_table(entries: &[(&str, Option<&str>)]) {
+        let mut keys = Vec::<(&str, Vec<Option<&str>>)>::new();
+        for &(key, value) in entries {
+            match keys.iter_mut().find(|(declared, _)| *declared == key) {
+                Some((_, values)) if !values.contains(&value) => values.push(value),
+                Some(_) => {}
+                None => keys.push((key, vec![value])),
+            }
+        }
+
+        for (key, values) in keys {
+            if values.len() > 1 {
+                build_script::allow_multiple_values(key);
+            }
+            if values == [None] {
+                Self::check_cfg(format!("cfg({key})"));
+            } else {
+                let values = values
+                    .into_iter()
+                    .map(|value| value.map_or_else(|| "none()".to_owned(), quote))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Self::check_cfg(format!("cfg({key}, values({values}))"));
+            }
+        }
+
+        for &(key, value) in entries {
+            Self::cfg(key, value);
+        }
+    }
+
+    /// Configures the `alias` conditional compilation flag when any of the listed Cargo features is enabled,
+    /// as reported by the `CARGO_FEATURE_*` environment variables. The flag is always declared as expected.
+    pub fn cfg_alias(alias: &str, features: &[&str]) {
+        Self::check_cfg(format!("cfg({alias})"));
+
+        if features.iter().any(|feature| features::is_enabled(feature)) {
+            Self::cfg(alias, None);
+        }
+    }
+
+    /// Mirrors Cargo features as conditional compilation flags named `prefix` followed by the feature name,
+    /// with `-` replaced by `_`, so `simd-json` is mirrored as `has_simd_json` with the `has_` prefix.
+    ///
+    /// Every feature of `all_features` is declared as expected with `rustc-check-cfg`, and the enabled ones
+    /// are configured with `rustc-cfg`, so enabling a feature later doesn't trigger the `unexpected_cfgs` lint.
+    /// Cargo doesn't tell build scripts which features the package declares, only the enabled ones,
+    /// so `all_features` must list the keys of the `[features]` table of `Cargo.toml`, along with the optional
+    /// dependencies that aren't referenced as `dep:name`, which form implicit features. Pass them as a constant
+    /// kept next to the manifest, or, with the `toml` crate, parse them from the manifest at [`Cargo::manifest_path`].
+    ///
+    /// ```
+    /// use build_instructions::{BuildScript, InstructionKind, Rustc};
+    ///
+    /// std::env::set_var("CARGO_FEATURE_SIMD_JSON", "1");
+    /// let instructions = BuildScript::collect(|| {
+    ///     Rustc::emit_cfg_for_enabled_features_with_checkcfg("has_", &["serde", "simd-json", "std"]);
+    /// });
+    ///
+    /// assert_eq!(instructions.cfgs(), [("has_simd_json", None)]);
+    /// assert_eq!(instructions.filter_kind(InstructionKind::CheckCfg).len(), 3);
+    /// ```
+    pub fn emit_cfg_for_enabled_features_with_checkcfg(prefix: &str, all_features: &[&str]) {
+        for feature in all_features {
+            let cfg_name = format!("{prefix}{}", feature.replace('-', "_"));
+            validation::debug_check(|| validation::identifier(&cfg_name));
+            Self::check_cfg(format!("cfg({cfg_name})"));
+
+            if features::is_enabled(feature) {
+                Self::cfg(&cfg_name, None);
+            }
+        }
+    }
+
+    /// Configures the `cfg_name` conditional compilation flag when the environment variable `var` is set and non-empty.
+    /// The flag is always declared as expected, and the build script is re-run when the variable changes.
+    pub fn cfg_from_env(var: &str, cfg_name: &str) {
+        Self::cfg_from_env_with(var, cfg_name, EmptyEnv::Unset);
+    }
+
+    /// Like [`Rustc::cfg_from_env`], with `empty` determining how an empty variable is treated.
+    pub fn cfg_from_env_with(var: &str, cfg_name: &str, empty: EmptyEnv) {
+        Cargo::rerun_if_env_changed(var);
+        Self::check_cfg(format!("cfg({cfg_name})"));
+
+        let is_set =
+            std::env::var_os(var).is_some_and(|value| !value.is_empty() || empty == EmptyEnv::Set);
+        if is_set {
+            Self::cfg(cfg_name, None);
+        }
+    }
+
+    /// Configures the `key` conditional compilation flag when the environment variable `var` is set, even to the empty string.
+    /// Shorthand for [`Rustc::cfg_from_env_with`] with [`EmptyEnv::Set`], for opt-ins such as `MYCRATE_NO_SIMD=1`.
+    pub fn cfg_if_env(key: &str, var: &str) {
+        Self::cfg_from_env_with(var, key, EmptyEnv::Set);
+    }
+
+    /// Configures the `cfg_key` conditional compilation flag with the value of the environment variable `var`
+    /// when it's set and non-empty. The flag is always declared as expected with any value,
+    /// and the build script is re-run when the variable changes.
+    pub fn cfg_value_from_env(var: &str, cfg_key: &str) {
+        Self::cfg_value_from_env_with(var, cfg_key, EmptyEnv::Unset);
+    }
+
+    /// Like [`Rustc::cfg_value_from_env`], with `empty` determining how an empty variable is treated.
+    pub fn cfg_value_from_env_with(var: &str, cfg_key: &str, empty: EmptyEnv) {
+        Cargo::rerun_if_env_changed(var);
+        Self::check_cfg(format!("cfg({cfg_key}, values(any()))"));
+
+        match std::env::var(var) {
+            Ok(value) if !value.is_empty() || empty == EmptyEnv::Set => {
+                Self::cfg(cfg_key, value.as_str())
+            }
+            Ok(_) | Err(VarError::NotPresent) => {}
+            Err(VarError::NotUnicode(_)) => Cargo::warning(format!(
+                "`{var}` isn't valid unicode, `{cfg_key}` is left unset"
+            )),
+        }
+    }
+
+    /// Configures the `key` conditional compilation flag when the target aborts on panic,
+    /// as reported by [`TargetCfg::panic_enum`]. The flag is always declared as expected.
+    pub fn cfg_if_panic_abort(key: &str) {
+        Self::check_cfg(format!("cfg({key})"));
+
+        if matches!(TargetCfg::panic_enum(), Ok(PanicStrategy::Abort)) {
+            Self::cfg(key, None);
+        }
+    }
+
+    /// Configures the `key` conditional compilation flag when the target is bare metal,
+    /// as reported by [`TargetCfg::is_bare_metal`]. The flag is always declared as expected.
+    pub fn cfg_if_bare_metal(key: &str) {
+        Self::check_cfg(format!("cfg({key})"));
+
+        if TargetCfg::is_bare_metal() {
+            Self::cfg(key, None);
+        }
+    }
+
+    /// Configures one conditional compilation flag summarizing the pointer width and endianness of the target,
+    /// named `target_{width}_{endian}` with the width in bits and `le` or `be` for the endianness, such as `target_64_le`.
+    /// Every combination of 16, 32 and 64 bits with both endiannesses is declared as expected.
+    /// A warning is printed and no flag is configured if the width or endianness is unknown.
+    pub fn cfg_target_summary() {
+        const WIDTHS: [&str; 3] = ["16", "32", "64"];
+        const ENDIANS: [&str; 2] = ["le", "be"];
+
+        let names = WIDTHS
+            .iter()
+            .flat_map(|width| {
+                ENDIANS
+                    .iter()
+                    .map(move |endian| format!("target_{width}_{endian}"))
+            })
+            .collect::<Vec<_>>();
+        Self::check_cfg(format!("cfg({})", names.join(", ")));
+
+        let width = TargetCfg::target_pointer_width();
+        let endian = TargetCfg::target_endian();
+        let endian = match endian.as_deref() {
+            Ok("little") => Some("le"),
+            Ok("big") => Some("be"),
+            _ => None,
+        };
+
+        match (width, endian) {
+            (Ok(width), Some(endian)) if WIDTHS.contains(&width.as_str()) => {
+                Self::cfg(format!("target_{width}_{endian}"), None);
+            }
+            _ => Cargo::warning(
+                "the pointer width or endianness of the target is unknown, no `target_*_*` cfg is configured",
+            ),
+        }
+    }
+
+    /// Configures the `cfg_name` conditional compilation flag when the Rust compiler is at least `version`,
+    /// given in the `major.minor[.patch]` form. Nightly and beta builds of a version satisfy that version.
+    /// The flag is always declared as expected, and a warning is printed if the compiler version can't be determined.
+    ///
+    /// # Panics
+    /// Panics if `version` isn't a valid version.
+    pub fn cfg_if_rustc_at_least(version: &str, cfg_name: &str) {
+        let required = RustcVersion::parse(version)
+            .unwrap_or_else(|| panic!("invalid Rust compiler version `{version}`"));
+        Self::cfg_if_rustc_version(required, cfg_name);
+    }
+
+    /// Configures the `key` conditional compilation flag when the Rust compiler is at least `1.<minor>.0`,
+    /// such as `cfg_if_rustc_minor_at_least(80, "has_lazy_lock")`. Shorthand for [`Rustc::cfg_if_rustc_at_least`]
+    /// without a version to parse, so it never panics.
+    pub fn cfg_if_rustc_minor_at_least(minor: u32, key: &str) {
+        Self::cfg_if_rustc_version(RustcVersion::new(1, minor.into(), 0), key);
+    }
+
+    /// Configures the `cfg_name` conditional compilation flag when the Rust compiler is at least `required`.
+    fn cfg_if_rustc_version(required: RustcVersion, cfg_name: &str) {
+        gate_on_rustc_version(RustcInfo::version(), required, cfg_name);
+    }
+
+    /// Configures several conditional compilation flags, each gated on a minimum Rust compiler version
+    /// given as `(version, cfg_name)` in the `major.minor[.patch]` form. The compiler version is detected once,
+    /// every flag is declared as expected up front, and a warning is printed if the version can't be determined.
+    ///
+    /// All entries are validated before anything is emitted.
+    pub fn version_gates<'a>(
+        gates: &[(&str, &'a str)],
+    ) -> Result<VersionGateReport<'a>, InstructionError> {
+        let gates = gates
+            .iter()
+            .map(|&(version, cfg_name)| {
+                validation::identifier(cfg_name)?;
+                let required = RustcVersion::parse(version)
+                    .ok_or_else(|| InstructionError::InvalidVersion(version.to_owned()))?;
+                Ok((cfg_name, required))
+            })
+            .collect::<Result<Vec<_>, InstructionError>>()?;
+
+        Ok(apply_version_gates(RustcInfo::version(), &gates))
+    }
+
+    /// Configures the `docsrs` conditional compilation flag when the package is being built by docs.rs,
+    /// as detected by [`BuildEnv::is_docs_rs`]. The flag is always declared as expected.
+    pub fn cfg_docsrs() {
+        Self::check_cfg("cfg(docsrs)");
+
+        if BuildEnv::is_docs_rs() {
+            Self::cfg("docsrs", None);
+        }
+    }
+
+    /// Configures the `nightly_compiler` conditional compilation flag when the Rust compiler is a nightly or a dev build,
+    /// both of which accept unstable features. The flag is always declared as expected.
+    pub fn cfg_nightly() {
+        gate_on_nightly(RustcInfo::channel());
+    }
+
+    /// Configures the `cfg_name` conditional compilation flag when `code` compiles, as checked by a [`Probe`].
+    /// The flag is always declared as expected, and a warning is printed if the probe itself fails.
+    pub fn probe_and_cfg(cfg_name: &str, code: &str) {
+        Self::check_cfg(format!("cfg({cfg_name})"));
+
+        match Probe::new().code(code).try_run() {
+            Ok(true) => Self::cfg(cfg_name, None),
+            Ok(false) => {}
+            Err(err) => Cargo::warning(format!("couldn't probe for `{cfg_name}`: {err}")),
+        }
     }
 
     /// Sets an environment variable for the build script.
     pub fn env(var: impl AsRef<str>, value: impl AsRef<str>) {
         let var = var.as_ref();
         let value = value.as_ref();
-        println!("cargo::rustc-env={var}={value}");
+        validation::debug_check(|| validation::key(var).and_then(|_| validation::line(value)));
+        emit(Instruction::Env {
+            var: var.to_owned(),
+            value: value.to_owned(),
+        });
+    }
+
+    /// Fallible version of [`Rustc::env`] that rejects invalid variable names and values containing line breaks,
+    /// as well as variables Cargo sets itself, such as `OUT_DIR`, `TARGET` or any starting with `CARGO`.
+    pub fn try_env(var: impl AsRef<str>, value: impl AsRef<str>) -> Result<(), InstructionError> {
+        let var = var.as_ref();
+        let value = value.as_ref();
+        validation::env_var(var)?;
+        validation::line(value)?;
+        Self::env(var, value);
+        Ok(())
+    }
+
+    /// Sets an environment variable for the build script and re-runs the build script when `watch` changes.
+    /// Use it for values derived from a file, so that the compile-time value doesn't go stale when the file is edited.
+    pub fn env_tracked(var: impl AsRef<str>, value: impl AsRef<str>, watch: impl AsRef<Path>) {
+        Self::env(var, value);
+        Cargo::rerun_if_changed(watch);
     }
 
     /// Passes a linker argument specifically for `cdylib` builds.
     pub fn cdylib_link_arg(flag: impl AsRef<str>) {
         let flag = flag.as_ref();
-        println!("cargo::rustc-cdylib-link-arg={flag}");
+        validation::debug_check(|| validation::line(flag));
+        emit(Instruction::CdylibLinkArg(flag.to_owned()));
+    }
+
+    /// Fallible version of [`Rustc::cdylib_link_arg`] that rejects arguments containing line breaks.
+    pub fn try_cdylib_link_arg(flag: impl AsRef<str>) -> Result<(), InstructionError> {
+        let flag = flag.as_ref();
+        validation::line(flag)?;
+        Self::cdylib_link_arg(flag);
+        Ok(())
+    }
+}
+
+/// Splits the file name of a library into the name to link it by and the way it's linked, or `None` for an unrecognized extension.
+fn library_name_and_preference(file_name: &str, is_msvc: bool) -> Option<(&str, LinkPreference)> {
+    let (stem, pref) = [
+        (".dll.a", LinkPreference::Dynamic),
+        (".a", LinkPreference::Static),
+        (".lib", LinkPreference::Static),
+        (".so", LinkPreference::Dynamic),
+        (".dylib", LinkPreference::Dynamic),
+        (".dll", LinkPreference::Dynamic),
+    ]
+    .into_iter()
+    .find_map(|(extension, pref)| Some((file_name.strip_suffix(extension)?, pref)))?;
+
+    let name = if is_msvc {
+        stem
+    } else {
+        stem.strip_prefix("lib").unwrap_or(stem)
+    };
+
+    Some((name, pref)).filter(|(name, _)| !name.is_empty())
+}
+
+/// Configures the `cfg_name` conditional compilation flag when the compiler of version `actual` is at least `required`.
+fn gate_on_rustc_version(actual: Option<RustcVersion>, required: RustcVersion, cfg_name: &str) {
+    Rustc::check_cfg(format!("cfg({cfg_name})"));
+
+    match actual {
+        Some(actual) if actual >= required => Rustc::cfg(cfg_name, None),
+        Some(_) => {}
+        None => Cargo::warning(format!(
+            "couldn't determine the Rust compiler version, `{cfg_name}` is left unset"
+        )),
+    }
+}
+
+/// Passes the unstable flag `flag` when `allowed`, warning that it was skipped otherwise.
+fn apply_unstable_flag(flag: &str, allowed: bool) -> bool {
+    if allowed {
+        Rustc::flags(flag);
+    } else {
+        Cargo::warning(format!(
+            "skipping unstable flag `{flag}` as the compiler doesn't allow unstable features"
+        ));
+    }
+
+    allowed
+}
+
+/// Configures the `nightly_compiler` conditional compilation flag when `channel` accepts unstable features.
+fn gate_on_nightly(channel: Option<RustcChannel>) {
+    Rustc::check_cfg("cfg(nightly_compiler)");
+
+    if matches!(channel, Some(RustcChannel::Nightly | RustcChannel::Dev)) {
+        Rustc::cfg("nightly_compiler", None);
+    }
+}
+
+/// Emits the instructions of [`Rustc::version_gates`] for the detected compiler version.
+fn apply_version_gates<'a>(
+    rustc_version: Option<RustcVersion>,
+    gates: &[(&'a str, RustcVersion)],
+) -> VersionGateReport<'a> {
+    for (cfg_name, _) in gates {
+        Rustc::check_cfg(format!("cfg({cfg_name})"));
+    }
+
+    if rustc_version.is_none() {
+        Cargo::warning(
+            "couldn't determine the Rust compiler version, version gated cfgs are left unset",
+        );
+    }
+
+    let gates = gates
+        .iter()
+        .map(|&(cfg_name, required)| {
+            let passed = rustc_version.is_some_and(|version| version >= required);
+            if passed {
+                Rustc::cfg(cfg_name, None);
+            }
+            (cfg_name, required, passed)
+        })
+        .collect();
+
+    VersionGateReport {
+        rustc_version,
+        gates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_env::TestEnv, BuildScript, InstructionKind};
+
+    #[test]
+    fn version_gate_with_stubbed_versions() {
+        let _env = TestEnv::new();
+        let required = RustcVersion::new(1, 80, 0);
+        let gate = |actual| {
+            BuildScript::collect(|| gate_on_rustc_version(actual, required, "has_lazy_lock"))
+        };
+
+        let newer = gate(RustcVersion::parse("1.82.0-nightly"));
+        assert_eq!(newer.cfgs(), [("has_lazy_lock", None)]);
+        assert_eq!(newer.filter_kind(InstructionKind::CheckCfg).len(), 1);
+
+        let equal = gate(RustcVersion::parse("1.80"));
+        assert_eq!(equal.cfgs(), [("has_lazy_lock", None)]);
+
+        let older = gate(RustcVersion::parse("1.79.2"));
+        assert!(older.cfgs().is_empty());
+        assert_eq!(older.filter_kind(InstructionKind::CheckCfg).len(), 1);
+
+        let unknown = gate(None);
+        assert!(unknown.cfgs().is_empty());
+        assert_eq!(unknown.filter_kind(InstructionKind::Warning).len(), 1);
+    }
+
+    #[test]
+    fn library_kind_from_file_name() {
+        assert_eq!(
+            library_name_and_preference("libz.a", false),
+            Some(("z", LinkPreference::Static))
+        );
+        assert_eq!(
+            library_name_and_preference("libssl.so", false),
+            Some(("ssl", LinkPreference::Dynamic))
+        );
+        assert_eq!(
+            library_name_and_preference("zlib.lib", true),
+            Some(("zlib", LinkPreference::Static))
+        );
+        assert_eq!(
+            library_name_and_preference("libfoo.dll.a", false),
+            Some(("foo", LinkPreference::Dynamic))
+        );
+        assert_eq!(library_name_and_preference("libz.so.1", false), None);
+        assert_eq!(library_name_and_preference("lib.a", false), None);
+    }
+
+    #[test]
+    fn cfg_values_in_order_without_duplicates() {
+        let _env = TestEnv::new();
+        assert!(BuildScript::collect(|| Rustc::cfg_values("io_backend", [""; 0])).is_empty());
+
+        let instructions =
+            BuildScript::collect(|| Rustc::cfg_values("io_backend", ["epoll", "uring", "epoll"]));
+        assert_eq!(
+            instructions.cfgs(),
+            [("io_backend", Some("epoll")), ("io_backend", Some("uring"))]
+        );
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-cfg=io_backend=\"epoll\"\ncargo::rustc-cfg=io_backend=\"uring\"\n"
+        );
+    }
+
+    #[test]
+    fn link_args_for_the_target_os() {
+        let entries = [
+            (TargetOs::Linux, "-Wl,--as-needed"),
+            (TargetOs::MacOs, "-framework"),
+            (TargetOs::MacOs, "Security"),
+            (TargetOs::Windows, "ws2_32.lib"),
+        ];
+        let mut env = TestEnv::new();
+
+        env.set(vars::CARGO_CFG_TARGET_OS, "macos");
+        let mut result = None;
+        let instructions =
+            BuildScript::collect(|| result = Some(Rustc::link_arg_for_target_os(entries.clone())));
+        assert_eq!(result, Some(Ok(())));
+        assert_eq!(
+            *instructions,
+            [
+                Instruction::LinkArg("-framework".to_owned()),
+                Instruction::LinkArg("Security".to_owned()),
+            ]
+        );
+
+        env.set(vars::CARGO_CFG_TARGET_OS, "freebsd");
+        let instructions =
+            BuildScript::collect(|| result = Some(Rustc::link_arg_for_target_os(entries.clone())));
+        assert!(instructions.is_empty());
+
+        env.remove(vars::CARGO_CFG_TARGET_OS);
+        BuildScript::collect(|| result = Some(Rustc::link_arg_for_target_os(entries.clone())));
+        assert!(result.unwrap().is_err_and(|err| err.is_not_present()));
+    }
+
+    #[test]
+    fn try_variants_reject_invalid_input_without_emitting() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Rustc::try_link_lib(""), Err(InstructionError::Empty));
+            assert_eq!(
+                Rustc::try_link_arg("-a\n-b"),
+                Err(InstructionError::LineBreak("-a\n-b".to_owned()))
+            );
+            assert_eq!(
+                Rustc::try_link_arg_bin("cli=x", "-a"),
+                Err(InstructionError::ContainsEquals("cli=x".to_owned()))
+            );
+            assert_eq!(
+                Rustc::try_cfg("has-dash", None),
+                Err(InstructionError::InvalidIdentifier("has-dash".to_owned()))
+            );
+            assert_eq!(
+                Rustc::try_cfg_values("backend", ["gl", "vk\n"]),
+                Err(InstructionError::LineBreak("vk\n".to_owned()))
+            );
+            assert_eq!(
+                Rustc::try_check_cfg("cfg(a)\n"),
+                Err(InstructionError::LineBreak("cfg(a)\n".to_owned()))
+            );
+        });
+
+        assert!(instructions.is_empty());
+    }
+
+    #[test]
+    fn nightly_gate_with_stubbed_channels() {
+        let _env = TestEnv::new();
+        for (channel, set) in [
+            (Some(RustcChannel::Nightly), true),
+            (Some(RustcChannel::Dev), true),
+            (Some(RustcChannel::Beta), false),
+            (Some(RustcChannel::Stable), false),
+            (None, false),
+        ] {
+            let instructions = BuildScript::collect(|| gate_on_nightly(channel));
+
+            assert_eq!(
+                *instructions.filter_kind(InstructionKind::CheckCfg),
+                [&Instruction::CheckCfg("cfg(nightly_compiler)".to_owned())]
+            );
+            assert_eq!(!instructions.cfgs().is_empty(), set, "{channel:?}");
+        }
+    }
+
+    #[test]
+    fn cfg_alias_of_features() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_FEATURE_PREFIX);
+
+        let alias = || BuildScript::collect(|| Rustc::cfg_alias("tls", &["native-tls", "rustls"]));
+        assert_eq!(*alias(), [Instruction::CheckCfg("cfg(tls)".to_owned())]);
+
+        env.set("CARGO_FEATURE_RUSTLS", "1");
+        assert_eq!(
+            *alias(),
+            [
+                Instruction::CheckCfg("cfg(tls)".to_owned()),
+                Instruction::Cfg {
+                    key: "tls".to_owned(),
+                    value: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cfg_if_panic_abort() {
+        let mut env = TestEnv::new();
+
+        env.set(vars::CARGO_CFG_PANIC, "abort");
+        let instructions = BuildScript::collect(|| Rustc::cfg_if_panic_abort("panic_abort"));
+        assert_eq!(instructions.cfgs(), [("panic_abort", None)]);
+
+        env.remove(vars::CARGO_CFG_PANIC);
+        let instructions = BuildScript::collect(|| Rustc::cfg_if_panic_abort("panic_abort"));
+        assert!(instructions.cfgs().is_empty());
+        assert_eq!(
+            *instructions,
+            [Instruction::CheckCfg("cfg(panic_abort)".to_owned())]
+        );
+    }
+
+    #[test]
+    fn cfg_from_env_set_empty_and_unset() {
+        const VAR: &str = "BUILD_INSTRUCTIONS_TEST_CFG_FROM_ENV";
+        let mut env = TestEnv::new();
+        let cfgs = |f: fn()| {
+            let instructions = BuildScript::collect(f);
+            assert!(instructions.contains(&Instruction::CheckCfg("cfg(from_env)".to_owned())));
+            instructions
+                .cfgs()
+                .iter()
+                .map(|&(key, value)| (key.to_owned(), value.map(str::to_owned)))
+                .collect::<Vec<_>>()
+        };
+        let set = vec![("from_env".to_owned(), None)];
+
+        env.set(VAR, "1");
+        assert_eq!(cfgs(|| Rustc::cfg_from_env(VAR, "from_env")), set);
+
+        env.set(VAR, "");
+        assert!(cfgs(|| Rustc::cfg_from_env(VAR, "from_env")).is_empty());
+        assert_eq!(
+            cfgs(|| Rustc::cfg_from_env_with(VAR, "from_env", EmptyEnv::Set)),
+            set
+        );
+
+        env.remove(VAR);
+        assert!(cfgs(|| Rustc::cfg_from_env(VAR, "from_env")).is_empty());
+        assert!(cfgs(|| Rustc::cfg_from_env_with(VAR, "from_env", EmptyEnv::Set)).is_empty());
+    }
+
+    #[test]
+    fn cfg_value_from_env_escapes_the_value() {
+        const VAR: &str = "BUILD_INSTRUCTIONS_TEST_CFG_VALUE_FROM_ENV";
+        let mut env = TestEnv::new();
+
+        env.set(VAR, r#"a"b"#);
+        let instructions = BuildScript::collect(|| Rustc::cfg_value_from_env(VAR, "mode"));
+        assert!(instructions
+            .to_string()
+            .contains("cargo::rustc-cfg=mode=\"a\\\"b\"\n"));
+
+        env.set(VAR, "");
+        let instructions = BuildScript::collect(|| Rustc::cfg_value_from_env(VAR, "mode"));
+        assert!(instructions.cfgs().is_empty());
+    }
+
+    #[test]
+    fn version_gates_with_stubbed_versions() {
+        let _env = TestEnv::new();
+        let gates = [
+            ("has_lazy_lock", RustcVersion::new(1, 80, 0)),
+            ("has_isqrt", RustcVersion::new(1, 84, 0)),
+        ];
+
+        let mut report = None;
+        let instructions = BuildScript::collect(|| {
+            report = Some(apply_version_gates(RustcVersion::parse("1.82.0"), &gates));
+        });
+        assert_eq!(instructions.cfgs(), [("has_lazy_lock", None)]);
+        assert_eq!(instructions.filter_kind(InstructionKind::CheckCfg).len(), 2);
+        assert_eq!(
+            report.take().unwrap().to_string(),
+            "rustc 1.82.0: has_lazy_lock (>= 1.80.0) set, has_isqrt (>= 1.84.0) unset"
+        );
+
+        let instructions = BuildScript::collect(|| {
+            report = Some(apply_version_gates(None, &gates));
+        });
+        assert!(instructions.cfgs().is_empty());
+        assert_eq!(instructions.filter_kind(InstructionKind::Warning).len(), 1);
+        assert_eq!(
+            report.unwrap().to_string(),
+            "unknown rustc version: has_lazy_lock (>= 1.80.0) unset, has_isqrt (>= 1.84.0) unset"
+        );
+    }
+
+    #[test]
+    fn version_gates_reject_invalid_entries() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(
+                Rustc::version_gates(&[("1.80", "ok"), ("1.x", "has_x")]),
+                Err(InstructionError::InvalidVersion("1.x".to_owned()))
+            );
+            assert_eq!(
+                Rustc::version_gates(&[("1.80", "has-dash")]),
+                Err(InstructionError::InvalidIdentifier("has-dash".to_owned()))
+            );
+        });
+
+        assert!(instructions.is_empty());
+    }
+
+    #[test]
+    fn auto_check_cfg_declares_each_flag_once() {
+        let _env = TestEnv::new();
+        Rustc::auto_check_cfg(true);
+
+        let first = BuildScript::collect(|| Rustc::cfg("auto_declared", None));
+        let second = BuildScript::collect(|| Rustc::cfg("auto_declared", None));
+        let valued = BuildScript::collect(|| Rustc::cfg("auto_declared_value", "a"));
+        Rustc::auto_check_cfg(false);
+
+        assert_eq!(
+            first.to_string(),
+            "cargo::rustc-check-cfg=cfg(auto_declared)\ncargo::rustc-cfg=auto_declared\n"
+        );
+        assert_eq!(second.to_string(), "cargo::rustc-cfg=auto_declared\n");
+        assert_eq!(
+            valued.to_string(),
+            "cargo::rustc-check-cfg=cfg(auto_declared_value, values(\"a\"))\n\
+             cargo::rustc-cfg=auto_declared_value=\"a\"\n"
+        );
+
+        let disabled = BuildScript::collect(|| Rustc::cfg("auto_declared", None));
+        assert_eq!(disabled.cfgs(), [("auto_declared", None)]);
+        assert!(disabled.filter_kind(InstructionKind::CheckCfg).is_empty());
+    }
+
+    #[test]
+    fn env_tracked_watches_its_source() {
+        let instructions =
+            BuildScript::collect(|| Rustc::env_tracked("GIT_HASH", "abc123", ".git/HEAD"));
+
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-env=GIT_HASH=abc123\ncargo::rerun-if-changed=.git/HEAD\n"
+        );
+    }
+
+    #[test]
+    fn cfg_table_declares_each_key_once() {
+        let _env = TestEnv::new();
+        let instructions = BuildScript::collect(|| {
+            Rustc::cfg_table(&[
+                ("backend", Some("gl")),
+                ("has_simd", None),
+                ("backend", Some("vulkan")),
+                ("backend", Some("gl")),
+            ])
+        });
+
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-check-cfg=cfg(backend, values(\"gl\", \"vulkan\"))\n\
+             cargo::rustc-check-cfg=cfg(has_simd)\n\
+             cargo::rustc-cfg=backend=\"gl\"\n\
+             cargo::rustc-cfg=has_simd\n\
+             cargo::rustc-cfg=backend=\"vulkan\"\n\
+             cargo::rustc-cfg=backend=\"gl\"\n"
+        );
+
+        let instructions =
+            BuildScript::collect(|| Rustc::cfg_table(&[("tls", None), ("tls", Some("rustls"))]));
+        assert_eq!(
+            instructions.filter_kind(InstructionKind::CheckCfg),
+            [&Instruction::CheckCfg(
+                "cfg(tls, values(none(), \"rustls\"))".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn unstable_flag_with_stubbed_channels() {
+        let mut passed = None;
+        let nightly =
+            BuildScript::collect(|| passed = Some(apply_unstable_flag("-Zshare-generics", true)));
+        assert_eq!(passed, Some(true));
+        assert_eq!(nightly.to_string(), "cargo::rustc-flags=-Zshare-generics\n");
+
+        let stable =
+            BuildScript::collect(|| passed = Some(apply_unstable_flag("-Zshare-generics", false)));
+        assert_eq!(passed, Some(false));
+        assert_eq!(
+            stable.to_string(),
+            "cargo::warning=skipping unstable flag `-Zshare-generics` \
+             as the compiler doesn't allow unstable features\n"
+        );
+    }
+
+    #[test]
+    fn unstable_flag_follows_rustc_bootstrap() {
+        let mut env = TestEnv::new();
+        env.set(vars::RUSTC_BOOTSTRAP, "1");
+        let mut passed = None;
+        let instructions =
+            BuildScript::collect(|| passed = Some(Rustc::unstable_flag("-Zshare-generics")));
+        assert_eq!(passed, Some(true));
+        assert_eq!(
+            instructions.filter_kind(InstructionKind::Flags),
+            [&Instruction::Flags("-Zshare-generics".to_owned())]
+        );
+
+        env.set(vars::RUSTC_BOOTSTRAP, "-1");
+        let instructions =
+            BuildScript::collect(|| passed = Some(Rustc::unstable_flag("-Zshare-generics")));
+        assert_eq!(passed, Some(false));
+        assert!(instructions.filter_kind(InstructionKind::Flags).is_empty());
+        assert_eq!(instructions.filter_kind(InstructionKind::Warning).len(), 1);
+    }
+
+    #[test]
+    fn codegen_options() {
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Rustc::codegen_opt("link-arg", "-Wl,--as-needed"), Ok(()));
+            assert_eq!(Rustc::codegen_opt("link_args", "-lfoo -lbar"), Ok(()));
+            assert_eq!(
+                Rustc::codegen_opt("target-cpu", "native"),
+                Err(InstructionError::UnsupportedCodegenOption(
+                    "target-cpu".to_owned()
+                ))
+            );
+        });
+
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-link-arg=-Wl,--as-needed\n\
+             cargo::rustc-link-arg=-lfoo\n\
+             cargo::rustc-link-arg=-lbar\n"
+        );
+        assert!(
+            InstructionError::UnsupportedCodegenOption("target-cpu".to_owned())
+                .to_string()
+                .starts_with("`-C target-cpu` can't be passed from a build script")
+        );
+    }
+
+    #[test]
+    fn link_search_in_out_dir() {
+        let mut env = TestEnv::new();
+
+        env.set(vars::OUT_DIR, "/target/out");
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Rustc::link_search_out_dir(LinkSearchKind::Native), Ok(()));
+        });
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-link-search=native=/target/out\n"
+        );
+
+        env.remove(vars::OUT_DIR);
+        let instructions = BuildScript::collect(|| {
+            assert!(Rustc::link_search_out_dir(LinkSearchKind::Native)
+                .unwrap_err()
+                .is_not_present());
+        });
+        assert!(instructions.is_empty());
+    }
+
+    #[test]
+    fn target_summary_cfg() {
+        let mut env = TestEnv::new();
+        env.set(vars::CARGO_CFG_TARGET_POINTER_WIDTH, "64")
+            .set(vars::CARGO_CFG_TARGET_ENDIAN, "little");
+
+        let instructions = BuildScript::collect(Rustc::cfg_target_summary);
+        assert_eq!(instructions.cfgs(), [("target_64_le", None)]);
+        assert_eq!(
+            instructions.filter_kind(InstructionKind::CheckCfg),
+            [&Instruction::CheckCfg(
+                "cfg(target_16_le, target_16_be, target_32_le, target_32_be, target_64_le, target_64_be)"
+                    .to_owned()
+            )]
+        );
+
+        env.set(vars::CARGO_CFG_TARGET_POINTER_WIDTH, "32")
+            .set(vars::CARGO_CFG_TARGET_ENDIAN, "big");
+        let instructions = BuildScript::collect(Rustc::cfg_target_summary);
+        assert_eq!(instructions.cfgs(), [("target_32_be", None)]);
+
+        env.set(vars::CARGO_CFG_TARGET_POINTER_WIDTH, "128");
+        let instructions = BuildScript::collect(Rustc::cfg_target_summary);
+        assert!(instructions.cfgs().is_empty());
+        assert_eq!(instructions.filter_kind(InstructionKind::Warning).len(), 1);
+
+        env.set(vars::CARGO_CFG_TARGET_POINTER_WIDTH, "64")
+            .remove(vars::CARGO_CFG_TARGET_ENDIAN);
+        let instructions = BuildScript::collect(Rustc::cfg_target_summary);
+        assert!(instructions.cfgs().is_empty());
+        assert_eq!(instructions.filter_kind(InstructionKind::Warning).len(), 1);
+    }
+
+    #[test]
+    fn cfg_if_env_set_and_unset() {
+        const VAR: &str = "BUILD_INSTRUCTIONS_TEST_CFG_IF_ENV";
+        let mut env = TestEnv::new();
+        let check_cfg = Instruction::CheckCfg("cfg(opt_in)".to_owned());
+
+        env.set(VAR, "");
+        let instructions = BuildScript::collect(|| Rustc::cfg_if_env("opt_in", VAR));
+        assert_eq!(
+            *instructions,
+            [
+                Instruction::RerunIfEnvChanged(VAR.to_owned()),
+                check_cfg.clone(),
+                Instruction::Cfg {
+                    key: "opt_in".to_owned(),
+                    value: None,
+                },
+            ]
+        );
+
+        // The variable is only declared once per build script run
+        env.remove(VAR);
+        let instructions = BuildScript::collect(|| Rustc::cfg_if_env("opt_in", VAR));
+        assert_eq!(*instructions, [check_cfg]);
+    }
+
+    #[test]
+    fn link_whole_archive_sets_the_modifier() {
+        let instructions = BuildScript::collect(|| Rustc::link_whole_archive("plugins"));
+        assert_eq!(
+            instructions.to_string(),
+            "cargo::rustc-link-lib=static:+whole-archive=plugins\n"
+        );
+    }
+
+    #[test]
+    fn try_env_rejects_variables_set_by_cargo() {
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(
+                Rustc::try_env("OUT_DIR", "x"),
+                Err(InstructionError::ReservedEnv("OUT_DIR".to_owned()))
+            );
+            assert_eq!(Rustc::try_env("MY_VAR", "x"), Ok(()));
+        });
+
+        assert_eq!(instructions.to_string(), "cargo::rustc-env=MY_VAR=x\n");
+    }
+
+    #[test]
+    fn link_system_lib_on_listed_platforms() {
+        let mut env = TestEnv::new();
+        let platforms = [TargetOs::Linux, TargetOs::MacOs];
+
+        env.set(vars::CARGO_CFG_TARGET_OS, "linux");
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Rustc::link_system_lib("dl", &platforms), Ok(()));
+        });
+        assert_eq!(instructions.to_string(), "cargo::rustc-link-lib=dl\n");
+
+        env.set(vars::CARGO_CFG_TARGET_OS, "windows");
+        let instructions = BuildScript::collect(|| {
+            assert_eq!(Rustc::link_system_lib("dl", &platforms), Ok(()));
+        });
+        assert!(instructions.is_empty());
+
+        env.remove(vars::CARGO_CFG_TARGET_OS);
+        let instructions = BuildScript::collect(|| {
+            assert!(Rustc::link_system_lib("dl", &platforms)
+                .unwrap_err()
+                .is_not_present());
+        });
+        assert!(instructions.is_empty());
+    }
+
+    #[test]
+    fn cfgs_for_enabled_features() {
+        let mut env = TestEnv::new();
+        env.remove_prefixed(vars::CARGO_FEATURE_PREFIX)
+            .set("CARGO_FEATURE_SIMD_JSON", "1");
+
+        let instructions = BuildScript::collect(|| {
+            Rustc::emit_cfg_for_enabled_features_with_checkcfg(
+                "has_",
+                &["serde", "simd-json", "std"],
+            );
+        });
+        assert_eq!(instructions.cfgs(), [("has_simd_json", None)]);
+        assert_eq!(
+            instructions.filter_kind(InstructionKind::CheckCfg),
+            [
+                &Instruction::CheckCfg("cfg(has_serde)".to_owned()),
+                &Instruction::CheckCfg("cfg(has_simd_json)".to_owned()),
+                &Instruction::CheckCfg("cfg(has_std)".to_owned()),
+            ]
+        );
     }
 }
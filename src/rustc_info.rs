@@ -0,0 +1,682 @@
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    ffi::OsString,
+    fmt::{Display, Formatter},
+    path::PathBuf,
+    process::Command,
+    sync::OnceLock,
+};
+
+use crate::{vars, Cargo};
+
+/// Represents a `major.minor.patch` version of the Rust compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RustcVersion {
+    /// The major version, `1` for every Rust release so far
+    pub major: u64,
+    /// The minor version
+    pub minor: u64,
+    /// The patch version
+    pub patch: u64,
+}
+
+impl RustcVersion {
+    /// Creates a version from its components.
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses a version in the `major.minor[.patch]` form, with a missing patch treated as `0`.
+    /// A pre-release suffix such as `-nightly` or `-beta.3` is ignored, so a nightly or beta build
+    /// of a version is considered to be that version.
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let version = version
+            .split_once('-')
+            .map_or(version, |(version, _)| version);
+        let mut components = version.split('.').map(|component| component.parse().ok());
+
+        let major = components.next()??;
+        let minor = components.next()??;
+        let patch = components.next().unwrap_or(Some(0))?;
+
+        if components.next().is_some() {
+            return None;
+        }
+
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl Display for RustcVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Represents the release channel of the Rust compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RustcChannel {
+    /// A stable release
+    Stable,
+    /// A beta release
+    Beta,
+    /// A nightly release
+    Nightly,
+    /// A compiler built from source, or any other build whose version doesn't name a known channel
+    Dev,
+}
+
+impl RustcChannel {
+    /// Determines the channel from the output of `rustc --version --verbose`, or `None` if it names no release.
+    fn from_version_verbose(version_verbose: &str) -> Option<Self> {
+        let release = release(version_verbose)?;
+        Some(Self::from_release(release, commit_hash(version_verbose)))
+    }
+
+    /// Determines the channel from a release string such as `1.80.0`, `1.81.0-beta.3` or `1.82.0-nightly`,
+    /// and the hash of the commit the compiler was built from, if it's known.
+    /// A release without a channel suffix is only a stable release when the commit is known,
+    /// as builds from source without git information omit both.
+    fn from_release(release: &str, commit_hash: Option<&str>) -> Self {
+        match release.trim().split_once('-') {
+            None if commit_hash.is_some() => RustcChannel::Stable,
+            None => RustcChannel::Dev,
+            Some((_, suffix)) if suffix.starts_with("beta") => RustcChannel::Beta,
+            Some((_, suffix)) if suffix.starts_with("nightly") => RustcChannel::Nightly,
+            Some(_) => RustcChannel::Dev,
+        }
+    }
+}
+
+impl Display for RustcChannel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RustcChannel::Stable => "stable",
+            RustcChannel::Beta => "beta",
+            RustcChannel::Nightly => "nightly",
+            RustcChannel::Dev => "dev",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+/// Represents the set of cfgs active for a target, as printed by `rustc --print cfg`.
+///
+/// A cfg can be set as a bare name, such as `unix`, or with one or more values, such as `target_feature="sse2"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CfgSet {
+    cfgs: BTreeMap<String, Vec<String>>,
+}
+
+impl CfgSet {
+    /// Parses the output of `rustc --print cfg`, with one `name` or `name="value"` cfg per line.
+    /// Lines that are neither, such as ones added by future compilers, are skipped.
+    pub fn parse(output: &str) -> Self {
+        let mut cfgs = BTreeMap::<String, Vec<String>>::new();
+
+        for line in output.lines().map(str::trim) {
+            match line.split_once('=') {
+                Some((name, value)) => {
+                    let Some(value) = unquote(value) else {
+                        continue;
+                    };
+                    if is_cfg_name(name) {
+                        cfgs.entry(name.to_owned()).or_default().push(value);
+                    }
+                }
+                None if is_cfg_name(line) => {
+                    cfgs.entry(line.to_owned()).or_default();
+                }
+                None => {}
+            }
+        }
+
+        Self { cfgs }
+    }
+
+    /// Checks whether the cfg `name` is set, either as a bare name or with any value.
+    pub fn contains(&self, name: &str) -> bool {
+        self.cfgs.contains_key(name)
+    }
+
+    /// Checks whether the cfg `name` is set with `value`.
+    pub fn contains_value(&self, name: &str, value: &str) -> bool {
+        self.values(name).any(|v| v == value)
+    }
+
+    /// Returns the values the cfg `name` is set with, in the order they were printed.
+    pub fn values(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.cfgs
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Returns the names of all set cfgs.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.cfgs.keys().map(String::as_str)
+    }
+}
+
+/// Provides information about the Rust compiler that compiles the crate being built.
+///
+/// The compiler is taken from the `RUSTC` environment variable set by Cargo, falling back to `rustc` from `PATH`.
+/// It is invoked at most once per process and its output is cached.
+pub struct RustcInfo(Infallible);
+
+impl RustcInfo {
+    /// Returns the version of the Rust compiler, or `None` if it couldn't be invoked or its output couldn't be parsed.
+    pub fn version() -> Option<RustcVersion> {
+        RustcVersion::parse(Self::release()?)
+    }
+
+    /// Returns the release channel of the Rust compiler, or `None` if it couldn't be invoked.
+    /// Versions without a recognized channel suffix, as produced by custom builds from source, are reported as [`RustcChannel::Dev`],
+    /// and so are versions without a suffix whose commit hash is unknown, as produced by builds from source without git information.
+    pub fn channel() -> Option<RustcChannel> {
+        RustcChannel::from_version_verbose(Self::version_verbose()?)
+    }
+
+    /// Checks whether unstable features can be used, either because the compiler is a nightly or dev build,
+    /// or because `RUSTC_BOOTSTRAP` enables them for every crate (`1`) or for the library crate of the package by name.
+    /// The library crate is assumed to be named like the package with `-` replaced by `_`, as it is
+    /// unless the manifest renames it with `lib.name`. `RUSTC_BOOTSTRAP=-1` makes even a nightly compiler
+    /// behave like a stable one.
+    pub fn allows_unstable() -> bool {
+        let bootstrap = std::env::var(vars::RUSTC_BOOTSTRAP).ok();
+        let lib_name = std::env::var(vars::CARGO_PKG_NAME)
+            .unwrap_or_default()
+            .replace('-', "_");
+
+        allows_unstable(bootstrap.as_deref(), &lib_name, Self::channel())
+    }
+
+    /// Returns the cfgs active for the target from the `TARGET` environment variable,
+    /// taking the flags from `CARGO_ENCODED_RUSTFLAGS` into account, or `None` if the compiler couldn't be invoked.
+    /// Unlike the `CARGO_CFG_*` environment variables, this includes every cfg exactly as the compiler sees it.
+    pub fn print_cfg() -> Option<&'static CfgSet> {
+        static PRINT_CFG: OnceLock<Option<CfgSet>> = OnceLock::new();
+
+        PRINT_CFG
+            .get_or_init(|| {
+                let mut command = Command::new(rustc_path());
+                command.args(["--print", "cfg"]);
+                if let Some(target) = std::env::var_os(vars::TARGET) {
+                    command.arg("--target").arg(target);
+                }
+                let output = command
+                    .args(Cargo::encoded_rustflags().unwrap_or_default())
+                    .output()
+                    .ok()?;
+
+                if !output.status.success() {
+                    return None;
+                }
+
+                Some(CfgSet::parse(&String::from_utf8(output.stdout).ok()?))
+            })
+            .as_ref()
+    }
+
+    /// Returns the release string of the compiler, such as `1.80.0` or `1.82.0-nightly`.
+    fn release() -> Option<&'static str> {
+        release(Self::version_verbose()?)
+    }
+
+    /// Returns the output of `rustc --version --verbose`, invoking the compiler on the first call.
+    pub(crate) fn version_verbose() -> Option<&'static str> {
+        static VERSION_VERBOSE: OnceLock<Option<String>> = OnceLock::new();
+
+        VERSION_VERBOSE
+            .get_or_init(|| {
+                let output = Command::new(rustc_path())
+                    .args(["--version", "--verbose"])
+                    .output()
+                    .ok()?;
+
+                if !output.status.success() {
+                    return None;
+                }
+
+                String::from_utf8(output.stdout).ok()
+            })
+            .as_deref()
+    }
+}
+
+/// Describes how Cargo invokes the Rust compiler, to let build scripts invoke it the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustcInvocation {
+    /// The Rust compiler
+    pub rustc: PathBuf,
+    /// The wrapper the compiler is run through, from `RUSTC_WRAPPER`
+    pub wrapper: Option<PathBuf>,
+    /// The wrapper the compiler is run through for workspace members, from `RUSTC_WORKSPACE_WRAPPER`
+    pub workspace_wrapper: Option<PathBuf>,
+}
+
+impl RustcInvocation {
+    /// Reads the invocation from the environment variables Cargo sets for build scripts,
+    /// falling back to `rustc` from `PATH` when `RUSTC` isn't set.
+    pub fn from_env() -> Self {
+        Self {
+            rustc: rustc_path().into(),
+            wrapper: Cargo::rustc_wrapper(),
+            workspace_wrapper: Cargo::rustc_workspace_wrapper(),
+        }
+    }
+
+    /// Builds a command running the compiler the way Cargo does: through the wrappers, if any,
+    /// with each wrapper receiving the rest of the invocation as its arguments.
+    /// `RUSTC_WRAPPER` is the outermost, so with both wrappers set the command is `wrapper workspace_wrapper rustc`.
+    pub fn command(&self) -> Command {
+        let mut programs = [&self.wrapper, &self.workspace_wrapper]
+            .into_iter()
+            .flatten()
+            .chain([&self.rustc]);
+        let mut command = Command::new(programs.next().unwrap_or(&self.rustc));
+        command.args(programs);
+        command
+    }
+}
+
+/// Finds the release string in the output of `rustc --version --verbose`, falling back to the version
+/// in its first line for compilers that don't print a `release` line.
+fn release(version_verbose: &str) -> Option<&str> {
+    version_verbose
+        .lines()
+        .find_map(|line| line.strip_prefix("release:"))
+        .or_else(|| version_verbose.split_whitespace().nth(1))
+        .map(str::trim)
+}
+
+/// Finds the hash of the commit the compiler was built from in the output of `rustc --version --verbose`,
+/// or `None` if it's unknown. Compilers that don't print a `commit-hash` line print it in parentheses in the first line.
+fn commit_hash(version_verbose: &str) -> Option<&str> {
+    let hash = match version_verbose
+        .lines()
+        .find_map(|line| line.strip_prefix("commit-hash:"))
+    {
+        Some(hash) => hash.trim(),
+        None => {
+            let (_, details) = version_verbose.lines().next()?.split_once('(')?;
+            details.split_whitespace().next()?
+        }
+    };
+
+    Some(hash).filter(|hash| !hash.is_empty() && *hash != "unknown")
+}
+
+/// Checks whether unstable features can be used by the crate `crate_name` with the compiler of channel `channel`,
+/// given the value of `RUSTC_BOOTSTRAP`.
+fn allows_unstable(
+    bootstrap: Option<&str>,
+    crate_name: &str,
+    channel: Option<RustcChannel>,
+) -> bool {
+    match bootstrap {
+        Some("1") => true,
+        Some("-1") => false,
+        Some(crates) if crates.split(',').any(|name| name.trim() == crate_name) => true,
+        _ => matches!(channel, Some(RustcChannel::Nightly | RustcChannel::Dev)),
+    }
+}
+
+/// Checks whether `name` looks like a cfg name, an identifier optionally containing `::` for tool cfgs.
+fn is_cfg_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .split("::")
+            .all(|part| part.chars().all(|c| c == '_' || c.is_alphanumeric()) && !part.is_empty())
+}
+
+/// Parses a double-quoted cfg value, resolving backslash escapes.
+fn unquote(value: &str) -> Option<String> {
+    let value = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unquoted = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.push(chars.next()?),
+            c => unquoted.push(c),
+        }
+    }
+
+    Some(unquoted)
+}
+
+/// Returns the Rust compiler selected by Cargo through the `RUSTC` environment variable, falling back to `rustc`.
+pub(crate) fn rustc_path() -> OsString {
+    std::env::var_os(vars::RUSTC).unwrap_or_else(|| OsString::from("rustc"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::TestEnv;
+
+    const STABLE: &str = "\
+rustc 1.80.0 (051478957 2024-07-21)
+binary: rustc
+commit-hash: 051478957371ee0084a7c0913941d2a8c4757bb9
+commit-date: 2024-07-21
+host: x86_64-unknown-linux-gnu
+release: 1.80.0
+LLVM version: 18.1.7
+";
+
+    const BETA: &str = "\
+rustc 1.81.0-beta.3 (4e3ad3d8e 2024-08-05)
+binary: rustc
+commit-hash: 4e3ad3d8e0c1df3b07dc4b5d5ab2f7e0a5b9dd24
+commit-date: 2024-08-05
+host: x86_64-unknown-linux-gnu
+release: 1.81.0-beta.3
+LLVM version: 18.1.7
+";
+
+    const NIGHTLY: &str = "\
+rustc 1.82.0-nightly (13a52890d 2024-08-14)
+binary: rustc
+commit-hash: 13a52890dde8cfeb95069d77c5ea4b4d5b7e8e8e
+commit-date: 2024-08-14
+host: aarch64-apple-darwin
+release: 1.82.0-nightly
+LLVM version: 19.1.0
+";
+
+    const DEV: &str = "\
+rustc 1.82.0-dev
+binary: rustc
+commit-hash: unknown
+commit-date: unknown
+host: x86_64-unknown-linux-gnu
+release: 1.82.0-dev
+LLVM version: 19.1.0
+";
+
+    const SOURCE_WITHOUT_CHANNEL: &str = "\
+rustc 1.80.0
+binary: rustc
+commit-hash: unknown
+commit-date: unknown
+host: x86_64-unknown-linux-gnu
+release: 1.80.0
+LLVM version: 18.1.7
+";
+
+    const LINUX_GNU_CFG: &str = r#"debug_assertions
+panic="unwind"
+target_abi=""
+target_arch="x86_64"
+target_endian="little"
+target_env="gnu"
+target_family="unix"
+target_feature="fxsr"
+target_feature="sse"
+target_feature="sse2"
+target_has_atomic="16"
+target_has_atomic="32"
+target_has_atomic="64"
+target_has_atomic="8"
+target_has_atomic="ptr"
+target_os="linux"
+target_pointer_width="64"
+target_vendor="unknown"
+unix
+"#;
+
+    const WINDOWS_MSVC_CFG: &str = r#"debug_assertions
+panic="unwind"
+target_abi=""
+target_arch="x86_64"
+target_endian="little"
+target_env="msvc"
+target_family="windows"
+target_feature="cmpxchg16b"
+target_feature="fxsr"
+target_feature="sse"
+target_feature="sse2"
+target_feature="sse3"
+target_has_atomic="128"
+target_has_atomic="16"
+target_has_atomic="32"
+target_has_atomic="64"
+target_has_atomic="8"
+target_has_atomic="ptr"
+target_os="windows"
+target_pointer_width="64"
+target_vendor="pc"
+windows
+"#;
+
+    const WASM32_CFG: &str = r#"debug_assertions
+panic="abort"
+target_abi=""
+target_arch="wasm32"
+target_endian="little"
+target_env=""
+target_family="wasm"
+target_feature="bulk-memory"
+target_feature="multivalue"
+target_feature="mutable-globals"
+target_feature="nontrapping-fptoint"
+target_feature="reference-types"
+target_feature="sign-ext"
+target_has_atomic="16"
+target_has_atomic="32"
+target_has_atomic="64"
+target_has_atomic="8"
+target_has_atomic="ptr"
+target_os="unknown"
+target_pointer_width="32"
+target_vendor="unknown"
+"#;
+
+    #[test]
+    fn channel_of_captured_versions() {
+        let channel = RustcChannel::from_version_verbose;
+
+        assert_eq!(channel(STABLE), Some(RustcChannel::Stable));
+        assert_eq!(channel(BETA), Some(RustcChannel::Beta));
+        assert_eq!(channel(NIGHTLY), Some(RustcChannel::Nightly));
+        assert_eq!(channel(DEV), Some(RustcChannel::Dev));
+        assert_eq!(channel(SOURCE_WITHOUT_CHANNEL), Some(RustcChannel::Dev));
+        assert_eq!(channel(""), None);
+    }
+
+    #[test]
+    fn channel_of_short_versions() {
+        let channel = RustcChannel::from_version_verbose;
+
+        assert_eq!(
+            channel("rustc 1.80.0 (051478957 2024-07-21)"),
+            Some(RustcChannel::Stable)
+        );
+        assert_eq!(channel("rustc 1.80.0"), Some(RustcChannel::Dev));
+    }
+
+    #[test]
+    fn version_of_captured_versions() {
+        assert_eq!(
+            release(STABLE).and_then(RustcVersion::parse),
+            Some(RustcVersion::new(1, 80, 0))
+        );
+        assert_eq!(
+            release(BETA).and_then(RustcVersion::parse),
+            Some(RustcVersion::new(1, 81, 0))
+        );
+        assert_eq!(
+            release(NIGHTLY).and_then(RustcVersion::parse),
+            Some(RustcVersion::new(1, 82, 0))
+        );
+        assert_eq!(
+            release(DEV).and_then(RustcVersion::parse),
+            Some(RustcVersion::new(1, 82, 0))
+        );
+    }
+
+    #[test]
+    fn unstable_allowed_on_nightly_and_dev() {
+        assert!(allows_unstable(None, "foo", Some(RustcChannel::Nightly)));
+        assert!(allows_unstable(None, "foo", Some(RustcChannel::Dev)));
+        assert!(!allows_unstable(None, "foo", Some(RustcChannel::Stable)));
+        assert!(!allows_unstable(None, "foo", Some(RustcChannel::Beta)));
+        assert!(!allows_unstable(None, "foo", None));
+    }
+
+    #[test]
+    fn rustc_bootstrap_overrides_channel() {
+        assert!(allows_unstable(
+            Some("1"),
+            "foo",
+            Some(RustcChannel::Stable)
+        ));
+        assert!(!allows_unstable(
+            Some("-1"),
+            "foo",
+            Some(RustcChannel::Nightly)
+        ));
+    }
+
+    #[test]
+    fn rustc_bootstrap_names_library_crate() {
+        assert!(allows_unstable(
+            Some("bar, foo_bar"),
+            "foo_bar",
+            Some(RustcChannel::Stable)
+        ));
+        assert!(!allows_unstable(
+            Some("build_script_build"),
+            "foo_bar",
+            Some(RustcChannel::Stable)
+        ));
+        assert!(allows_unstable(
+            Some("bar"),
+            "foo_bar",
+            Some(RustcChannel::Nightly)
+        ));
+    }
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(
+            RustcVersion::parse("1.80.1"),
+            Some(RustcVersion::new(1, 80, 1))
+        );
+        assert_eq!(
+            RustcVersion::parse(" 1.80 "),
+            Some(RustcVersion::new(1, 80, 0))
+        );
+        assert_eq!(
+            RustcVersion::parse("1.82.0-nightly"),
+            Some(RustcVersion::new(1, 82, 0))
+        );
+        assert_eq!(
+            RustcVersion::parse("1.81.0-beta.3"),
+            Some(RustcVersion::new(1, 81, 0))
+        );
+
+        for invalid in ["", "1", "1.", "1.x", "1.80.0.1", "v1.80"] {
+            assert_eq!(RustcVersion::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn cfg_set_of_linux_gnu() {
+        let cfgs = CfgSet::parse(LINUX_GNU_CFG);
+
+        assert!(cfgs.contains("unix"));
+        assert!(!cfgs.contains("windows"));
+        assert!(cfgs.contains_value("target_os", "linux"));
+        assert!(cfgs.contains_value("target_env", "gnu"));
+        assert_eq!(
+            cfgs.values("target_feature").collect::<Vec<_>>(),
+            ["fxsr", "sse", "sse2"]
+        );
+        assert_eq!(cfgs.values("unix").count(), 0);
+    }
+
+    #[test]
+    fn cfg_set_of_windows_msvc() {
+        let cfgs = CfgSet::parse(WINDOWS_MSVC_CFG);
+
+        assert!(cfgs.contains("windows"));
+        assert!(cfgs.contains_value("target_env", "msvc"));
+        assert!(cfgs.contains_value("target_has_atomic", "128"));
+        assert!(cfgs.contains_value("target_abi", ""));
+    }
+
+    #[test]
+    fn cfg_set_of_wasm32() {
+        let cfgs = CfgSet::parse(WASM32_CFG);
+
+        assert!(!cfgs.contains("unix") && !cfgs.contains("windows"));
+        assert!(cfgs.contains_value("panic", "abort"));
+        assert!(cfgs.contains_value("target_family", "wasm"));
+        assert!(cfgs.contains_value("target_feature", "bulk-memory"));
+        assert_eq!(cfgs.names().next(), Some("debug_assertions"));
+    }
+
+    #[test]
+    fn cfg_set_skips_unknown_lines() {
+        let cfgs = CfgSet::parse(
+            "warning: unused\nclippy::nursery\nfeature=unquoted\nescaped=\"a\\\"b\"\n-Zflag\n",
+        );
+
+        assert_eq!(
+            cfgs.names().collect::<Vec<_>>(),
+            ["clippy::nursery", "escaped"]
+        );
+        assert!(cfgs.contains_value("escaped", "a\"b"));
+    }
+
+    /// Returns the program and the arguments of the compiler command built from the environment.
+    fn command_line() -> (OsString, Vec<OsString>) {
+        let command = RustcInvocation::from_env().command();
+        (
+            command.get_program().to_owned(),
+            command.get_args().map(ToOwned::to_owned).collect(),
+        )
+    }
+
+    #[test]
+    fn invocation_through_wrappers() {
+        let mut env = TestEnv::new();
+        env.set(vars::RUSTC, "/bin/rustc")
+            .remove(vars::RUSTC_WRAPPER)
+            .set(vars::RUSTC_WORKSPACE_WRAPPER, "");
+        assert_eq!(command_line(), ("/bin/rustc".into(), vec![]));
+
+        env.set(vars::RUSTC_WRAPPER, "/bin/sccache");
+        assert_eq!(
+            command_line(),
+            ("/bin/sccache".into(), vec!["/bin/rustc".into()])
+        );
+
+        env.remove(vars::RUSTC_WRAPPER)
+            .set(vars::RUSTC_WORKSPACE_WRAPPER, "/bin/clippy-driver");
+        assert_eq!(
+            command_line(),
+            ("/bin/clippy-driver".into(), vec!["/bin/rustc".into()])
+        );
+
+        env.set(vars::RUSTC_WRAPPER, "/bin/sccache");
+        assert_eq!(
+            command_line(),
+            (
+                "/bin/sccache".into(),
+                vec!["/bin/clippy-driver".into(), "/bin/rustc".into()]
+            )
+        );
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    convert::Infallible,
+    ffi::OsString,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{vars, EnvVarError, EnvVarErrorKind, FromEnvVar};
+
+/// Represents the operating system of the target being compiled for, as reported by `CARGO_CFG_TARGET_OS`.
+///
+/// Parsing never fails: operating systems without a dedicated variant are kept as [`TargetOs::Other`],
+/// so that targets added to the compiler later are still represented.
+///
+/// ```
+/// use build_instructions::TargetOs;
+///
+/// for os in [
+///     "linux", "windows", "macos", "ios", "tvos", "watchos", "visionos", "android", "freebsd",
+///     "netbsd", "openbsd", "dragonfly", "solaris", "illumos", "fuchsia", "haiku", "redox", "wasi",
+///     "none",
+/// ] {
+///     let parsed = os.parse::<TargetOs>().unwrap();
+///     assert!(!matches!(parsed, TargetOs::Other(_)));
+///     assert_eq!(parsed.to_string(), os);
+/// }
+///
+/// assert_eq!("hermit".parse::<TargetOs>().unwrap(), TargetOs::Other("hermit".to_owned()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TargetOs {
+    /// Linux
+    Linux,
+    /// Windows
+    Windows,
+    /// macOS
+    MacOs,
+    /// iOS
+    Ios,
+    /// tvOS
+    TvOs,
+    /// watchOS
+    WatchOs,
+    /// visionOS
+    VisionOs,
+    /// Android
+    Android,
+    /// FreeBSD
+    FreeBsd,
+    /// NetBSD
+    NetBsd,
+    /// OpenBSD
+    OpenBsd,
+    /// DragonFly BSD
+    DragonFly,
+    /// Solaris
+    Solaris,
+    /// illumos
+    Illumos,
+    /// Fuchsia
+    Fuchsia,
+    /// Haiku
+    Haiku,
+    /// Redox
+    Redox,
+    /// WebAssembly System Interface
+    Wasi,
+    /// Bare-metal targets without an operating system
+    None,
+    /// Any other operating system, named as in `CARGO_CFG_TARGET_OS`
+    Other(String),
+}
+
+impl TargetOs {
+    /// Checks whether the operating system is one of Apple's, which share the Darwin kernel and its toolchain.
+    pub fn is_apple(&self) -> bool {
+        matches!(
+            self,
+            TargetOs::MacOs
+                | TargetOs::Ios
+                | TargetOs::TvOs
+                | TargetOs::WatchOs
+                | TargetOs::VisionOs
+        )
+    }
+
+    /// Checks whether the operating system is one of the BSDs. Apple's operating systems aren't counted,
+    /// as they differ too much from the BSDs in their system libraries.
+    pub fn is_bsd(&self) -> bool {
+        matches!(
+            self,
+            TargetOs::FreeBsd | TargetOs::NetBsd | TargetOs::OpenBsd | TargetOs::DragonFly
+        )
+    }
+}
+
+impl FromStr for TargetOs {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "linux" => TargetOs::Linux,
+            "windows" => TargetOs::Windows,
+            "macos" => TargetOs::MacOs,
+            "ios" => TargetOs::Ios,
+            "tvos" => TargetOs::TvOs,
+            "watchos" => TargetOs::WatchOs,
+            "visionos" => TargetOs::VisionOs,
+            "android" => TargetOs::Android,
+            "freebsd" => TargetOs::FreeBsd,
+            "netbsd" => TargetOs::NetBsd,
+            "openbsd" => TargetOs::OpenBsd,
+            "dragonfly" => TargetOs::DragonFly,
+            "solaris" => TargetOs::Solaris,
+            "illumos" => TargetOs::Illumos,
+            "fuchsia" => TargetOs::Fuchsia,
+            "haiku" => TargetOs::Haiku,
+            "redox" => TargetOs::Redox,
+            "wasi" => TargetOs::Wasi,
+            "none" => TargetOs::None,
+            other => TargetOs::Other(other.to_owned()),
+        })
+    }
+}
+
+impl Display for TargetOs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            TargetOs::Linux => "linux",
+            TargetOs::Windows => "windows",
+            TargetOs::MacOs => "macos",
+            TargetOs::Ios => "ios",
+            TargetOs::TvOs => "tvos",
+            TargetOs::WatchOs => "watchos",
+            TargetOs::VisionOs => "visionos",
+            TargetOs::Android => "android",
+            TargetOs::FreeBsd => "freebsd",
+            TargetOs::NetBsd => "netbsd",
+            TargetOs::OpenBsd => "openbsd",
+            TargetOs::DragonFly => "dragonfly",
+            TargetOs::Solaris => "solaris",
+            TargetOs::Illumos => "illumos",
+            TargetOs::Fuchsia => "fuchsia",
+            TargetOs::Haiku => "haiku",
+            TargetOs::Redox => "redox",
+            TargetOs::Wasi => "wasi",
+            TargetOs::None => "none",
+            TargetOs::Other(os) => os,
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+impl FromEnvVar for TargetOs {
+    fn from_env_var(value: OsString) -> Result<Self, EnvVarErrorKind> {
+        let Ok(os) = String::from_env_var(value)?.parse();
+        Ok(os)
+    }
+}
+
+/// Represents a target triple, such as `x86_64-unknown-linux-gnu`, split into its components.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Triple {
+    /// The CPU architecture, such as `x86_64` or `thumbv7em`
+    pub arch: String,
+    /// The vendor, such as `unknown` or `apple`. `None` for triples that omit it, such as `thumbv7em-none-eabi`
+    pub vendor: Option<String>,
+    /// The operating system, such as `linux`, `darwin`, or `none` for bare-metal targets
+    pub os: String,
+    /// The environment or ABI, such as `gnu`, `msvc` or `eabi`
+    pub env: Option<String>,
+}
+
+impl Triple {
+    /// Vendors known to appear in three-component triples, which distinguish `arch-vendor-os` from `arch-os-env`.
+    const KNOWN_VENDORS: &'static [&'static str] = &[
+        "unknown",
+        "pc",
+        "apple",
+        "nvidia",
+        "fortanix",
+        "uwp",
+        "wrs",
+        "sun",
+        "sony",
+        "nintendo",
+        "espressif",
+        "kmc",
+        "unikraft",
+        "win7",
+        "risc0",
+        "ibm",
+        "amd",
+        "esp",
+    ];
+
+    /// Splits a target triple into its components. Parsing never fails: missing components are left empty.
+    ///
+    /// Four-component triples are read as `arch-vendor-os-env`. Three-component triples are read as
+    /// `arch-vendor-os` when the second component is a known vendor, such as in `aarch64-apple-darwin`,
+    /// and as `arch-os-env` otherwise, such as in `thumbv7em-none-eabi` or `aarch64-linux-android`.
+    /// Two-component triples, such as `wasm32-wasip1`, are read as `arch-os`.
+    pub fn parse(triple: &str) -> Self {
+        let mut components = triple.splitn(4, '-').map(str::to_owned);
+        let arch = components.next().unwrap_or_default();
+        let rest = components.collect::<Vec<_>>();
+
+        let (vendor, os, env) = match rest.as_slice() {
+            [] => (None, String::new(), None),
+            [os] => (None, os.clone(), None),
+            [vendor, os] if Self::KNOWN_VENDORS.contains(&vendor.as_str()) => {
+                (Some(vendor.clone()), os.clone(), None)
+            }
+            [os, env] => (None, os.clone(), Some(env.clone())),
+            [vendor, os, env, ..] => (Some(vendor.clone()), os.clone(), Some(env.clone())),
+        };
+
+        Self {
+            arch,
+            vendor,
+            os,
+            env,
+        }
+    }
+}
+
+impl Display for Triple {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.arch)?;
+        if let Some(vendor) = &self.vendor {
+            write!(f, "-{vendor}")?;
+        }
+        write!(f, "-{}", self.os)?;
+        if let Some(env) = &self.env {
+            write!(f, "-{env}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Represents the panic strategy of the target being compiled for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PanicStrategy {
+    /// Panics unwind the stack
+    Unwind,
+    /// Panics abort the process
+    Abort,
+}
+
+impl Display for PanicStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            PanicStrategy::Unwind => "unwind",
+            PanicStrategy::Abort => "abort",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+/// Provides access to the `CARGO_CFG_*` environment variables describing the target being compiled for.
+pub struct TargetCfg(Infallible);
+
+impl TargetCfg {
+    /// Returns the panic strategy from `CARGO_CFG_PANIC`.
+    /// Defaults to [`PanicStrategy::Unwind`] when the variable is absent, as it is in some contexts.
+    /// Strategies other than `unwind`, such as nightly's `immediate-abort`, are reported as [`PanicStrategy::Abort`].
+    pub fn panic_enum() -> Result<PanicStrategy, EnvVarError> {
+        match Self::panic() {
+            Ok(panic) if panic == "unwind" => Ok(PanicStrategy::Unwind),
+            Ok(_) => Ok(PanicStrategy::Abort),
+            Err(err) if err.is_not_present() => Ok(PanicStrategy::Unwind),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Checks whether the target is bare metal, without an operating system, such as `thumbv7em-none-eabi`.
+    /// That's the case when `target_os` is `none` or not reported at all, and neither the `unix` nor the `windows`
+    /// cfg is set. Targets with an unknown operating system, such as `wasm32-unknown-unknown`, aren't bare metal.
+    pub fn is_bare_metal() -> bool {
+        let os_none = match Self::target_os() {
+            Ok(os) => os == "none",
+            Err(err) => err.is_not_present(),
+        };
+
+        os_none && Self::raw("unix").is_err() && Self::raw("windows").is_err()
+    }
+
+    /// Returns the value of the target cfg `name` from its `CARGO_CFG_*` environment variable,
+    /// such as `relocation_model` from `CARGO_CFG_RELOCATION_MODEL`, for cfgs without a dedicated getter.
+    /// Cfgs that are only exposed by nightly compilers, such as `relocation_model`, are absent on stable ones.
+    /// Cfgs set without a value, such as `unix`, are reported as an empty string.
+    pub fn raw(name: &str) -> Result<String, EnvVarError> {
+        let var = format!("{}{}", vars::CARGO_CFG_PREFIX, name.to_uppercase());
+        String::read_env_var(var)
+    }
+
+    env_getters! {
+        /// The target operating system, such as `linux` or `windows`
+        target_os: String => vars::CARGO_CFG_TARGET_OS;
+        /// The target operating system as a [`TargetOs`]
+        os: TargetOs => vars::CARGO_CFG_TARGET_OS;
+        /// The target family, such as `unix` or `windows`.
+        /// Targets belonging to several families list them separated by commas
+        target_family: String => vars::CARGO_CFG_TARGET_FAMILY;
+        /// The target CPU architecture, such as `x86_64` or `aarch64`
+        target_arch: String => vars::CARGO_CFG_TARGET_ARCH;
+        /// The target environment, such as `gnu` or `msvc`. Empty when the target has none
+        target_env: String => vars::CARGO_CFG_TARGET_ENV;
+        /// The target vendor, such as `apple` or `unknown`
+        target_vendor: String => vars::CARGO_CFG_TARGET_VENDOR;
+        /// The target endianness, either `little` or `big`
+        target_endian: String => vars::CARGO_CFG_TARGET_ENDIAN;
+        /// The target pointer width in bits, such as `32` or `64`
+        target_pointer_width: String => vars::CARGO_CFG_TARGET_POINTER_WIDTH;
+        /// Comma separated list of the enabled target features
+        target_feature: String => vars::CARGO_CFG_TARGET_FEATURE;
+        /// The panic strategy, such as `unwind` or `abort`
+        panic: String => vars::CARGO_CFG_PANIC;
+        /// The target ABI refining the environment, such as `eabihf` or `sim`. Empty when the target has none.
+        /// Only reported since Rust 1.78, so absent with older compilers
+        target_abi | target_abi_opt: String => vars::CARGO_CFG_TARGET_ABI;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_env::TestEnv, vars, Cargo};
+
+    fn triple(arch: &str, vendor: Option<&str>, os: &str, env: Option<&str>) -> Triple {
+        Triple {
+            arch: arch.to_owned(),
+            vendor: vendor.map(str::to_owned),
+            os: os.to_owned(),
+            env: env.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn panic_strategy() {
+        let mut env = TestEnv::new();
+
+        env.remove(vars::CARGO_CFG_PANIC);
+        assert_eq!(TargetCfg::panic_enum(), Ok(PanicStrategy::Unwind));
+        env.set(vars::CARGO_CFG_PANIC, "abort");
+        assert_eq!(TargetCfg::panic_enum(), Ok(PanicStrategy::Abort));
+        env.set(vars::CARGO_CFG_PANIC, "immediate-abort");
+        assert_eq!(TargetCfg::panic_enum(), Ok(PanicStrategy::Abort));
+    }
+
+    #[test]
+    fn parse_triples() {
+        let cases = [
+            (
+                "x86_64-unknown-linux-gnu",
+                triple("x86_64", Some("unknown"), "linux", Some("gnu")),
+            ),
+            (
+                "aarch64-apple-darwin",
+                triple("aarch64", Some("apple"), "darwin", None),
+            ),
+            (
+                "thumbv7em-none-eabi",
+                triple("thumbv7em", None, "none", Some("eabi")),
+            ),
+            (
+                "aarch64-linux-android",
+                triple("aarch64", None, "linux", Some("android")),
+            ),
+            ("wasm32-wasip1", triple("wasm32", None, "wasip1", None)),
+            (
+                "x86_64-pc-windows-msvc",
+                triple("x86_64", Some("pc"), "windows", Some("msvc")),
+            ),
+        ];
+
+        for (s, expected) in cases {
+            assert_eq!(Triple::parse(s), expected, "{s}");
+            assert_eq!(expected.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn host_and_target_triples() {
+        let mut env = TestEnv::new();
+        env.set(vars::HOST, "x86_64-unknown-linux-gnu")
+            .set(vars::TARGET, "thumbv7em-none-eabi");
+
+        assert_eq!(
+            Cargo::host_triple_parsed(),
+            Ok(triple("x86_64", Some("unknown"), "linux", Some("gnu")))
+        );
+        assert_eq!(
+            Cargo::target_triple_parsed(),
+            Ok(triple("thumbv7em", None, "none", Some("eabi")))
+        );
+    }
+
+    #[test]
+    fn target_abi_set_empty_and_unset() {
+        let mut env = TestEnv::new();
+
+        env.set(vars::CARGO_CFG_TARGET_ABI, "eabihf");
+        assert_eq!(TargetCfg::target_abi(), Ok("eabihf".to_owned()));
+        assert_eq!(TargetCfg::target_abi_opt(), Ok(Some("eabihf".to_owned())));
+
+        env.set(vars::CARGO_CFG_TARGET_ABI, "");
+        assert_eq!(TargetCfg::target_abi(), Ok(String::new()));
+        assert_eq!(TargetCfg::target_abi_opt(), Ok(None));
+
+        env.remove(vars::CARGO_CFG_TARGET_ABI);
+        assert!(TargetCfg::target_abi().unwrap_err().is_not_present());
+        assert!(TargetCfg::target_abi_opt().unwrap_err().is_not_present());
+    }
+
+    #[test]
+    fn raw_cfgs() {
+        let mut env = TestEnv::new();
+        env.set("CARGO_CFG_RELOCATION_MODEL", "pic");
+
+        assert_eq!(TargetCfg::raw("relocation_model"), Ok("pic".to_owned()));
+    }
+
+    #[test]
+    fn bare_metal() {
+        let mut env = TestEnv::new();
+
+        env.set(vars::CARGO_CFG_TARGET_OS, "none")
+            .remove("CARGO_CFG_UNIX")
+            .remove("CARGO_CFG_WINDOWS");
+        assert!(TargetCfg::is_bare_metal());
+
+        env.remove(vars::CARGO_CFG_TARGET_OS);
+        assert!(TargetCfg::is_bare_metal());
+
+        env.set(vars::CARGO_CFG_TARGET_OS, "unknown");
+        assert!(!TargetCfg::is_bare_metal());
+
+        env.set(vars::CARGO_CFG_TARGET_OS, "none")
+            .set("CARGO_CFG_UNIX", "");
+        assert!(!TargetCfg::is_bare_metal());
+        assert_eq!(TargetCfg::raw("unix").as_deref(), Ok(""));
+    }
+
+    #[test]
+    fn os_families() {
+        assert!(TargetOs::Ios.is_apple());
+        assert!(!TargetOs::Ios.is_bsd());
+        assert!(TargetOs::DragonFly.is_bsd());
+        assert!(!TargetOs::Linux.is_apple());
+        assert!(!TargetOs::Other("hermit".to_owned()).is_bsd());
+    }
+}
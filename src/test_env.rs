@@ -0,0 +1,100 @@
+//! Helpers for tests reading the environment variables of the process.
+
+use std::{
+    ffi::OsString,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{cargo, Cargo};
+
+/// Serializes the tests changing the environment, as the variables are shared by every thread of the process.
+/// Tests configuring conditional compilation flags take it too, as [`Rustc::auto_check_cfg`](crate::Rustc::auto_check_cfg)
+/// changes what they emit for the whole process.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Holds the environment for one test, restoring the variables it changed when dropped.
+pub(crate) struct TestEnv {
+    saved: Vec<(String, Option<OsString>)>,
+    temp_dirs: Vec<PathBuf>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TestEnv {
+    /// Waits for the other tests changing the environment to finish and takes it over.
+    /// The variables declared with `rerun-if-env-changed` by earlier tests are forgotten, to be declared again.
+    pub(crate) fn new() -> Self {
+        let lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        cargo::forget_declared_envs();
+
+        Self {
+            saved: Vec::new(),
+            temp_dirs: Vec::new(),
+            _lock: lock,
+        }
+    }
+
+    /// Sets the variable `var` to `value` until the end of the test.
+    pub(crate) fn set(&mut self, var: &str, value: impl Into<OsString>) -> &mut Self {
+        self.save(var);
+        std::env::set_var(var, value.into());
+        Cargo::invalidate_cache();
+        self
+    }
+
+    /// Removes the variable `var` until the end of the test.
+    pub(crate) fn remove(&mut self, var: &str) -> &mut Self {
+        self.save(var);
+        std::env::remove_var(var);
+        Cargo::invalidate_cache();
+        self
+    }
+
+    /// Removes every variable whose name starts with `prefix` until the end of the test.
+    pub(crate) fn remove_prefixed(&mut self, prefix: &str) -> &mut Self {
+        let vars = std::env::vars_os()
+            .filter_map(|(var, _)| var.into_string().ok())
+            .filter(|var| var.starts_with(prefix))
+            .collect::<Vec<_>>();
+        for var in vars {
+            self.remove(&var);
+        }
+        self
+    }
+
+    /// Creates an empty directory named after `name`, removed at the end of the test.
+    pub(crate) fn temp_dir(&mut self, name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "build-instructions-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        self.temp_dirs.push(dir.clone());
+        dir
+    }
+
+    /// Records the value of `var` before the test first changes it.
+    fn save(&mut self, var: &str) {
+        if !self.saved.iter().any(|(saved, _)| saved == var) {
+            self.saved.push((var.to_owned(), std::env::var_os(var)));
+        }
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        for (var, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(value) => std::env::set_var(var, value),
+                None => std::env::remove_var(var),
+            }
+        }
+        Cargo::invalidate_cache();
+
+        for dir in self.temp_dirs.drain(..) {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
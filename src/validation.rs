@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use crate::{error::is_set_by_cargo, InstructionError};
+
+/// Checks that `value` fits on a single instruction line.
+pub(crate) fn line(value: &str) -> Result<(), InstructionError> {
+    if value.contains(['\n', '\r']) {
+        return Err(InstructionError::LineBreak(value.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Checks that `path` is valid unicode and fits on a single instruction line, returning it as a string.
+pub(crate) fn path(path: &Path) -> Result<&str, InstructionError> {
+    let value = path
+        .to_str()
+        .ok_or_else(|| InstructionError::NotUnicode(path.to_string_lossy().into_owned()))?;
+    line(value)?;
+
+    Ok(value)
+}
+
+/// Checks that `value` is non-empty and fits on a single instruction line.
+pub(crate) fn non_empty_line(value: &str) -> Result<(), InstructionError> {
+    line(value)?;
+
+    if value.is_empty() {
+        return Err(InstructionError::Empty);
+    }
+
+    Ok(())
+}
+
+/// Checks that `value` can be used as the key of a `key=value` instruction.
+pub(crate) fn key(value: &str) -> Result<(), InstructionError> {
+    non_empty_line(value)?;
+
+    if value.contains('=') {
+        return Err(InstructionError::ContainsEquals(value.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Checks that `value` can be set with `rustc-env` without overriding a variable Cargo sets itself.
+pub(crate) fn env_var(value: &str) -> Result<(), InstructionError> {
+    key(value)?;
+
+    if is_set_by_cargo(value) {
+        return Err(InstructionError::ReservedEnv(value.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Checks that `value` is a valid Rust identifier, as required for cfg names.
+pub(crate) fn identifier(value: &str) -> Result<(), InstructionError> {
+    let mut chars = value.chars();
+    let valid = chars.next().is_some_and(|c| c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+        && value != "_";
+
+    if !valid {
+        return Err(InstructionError::InvalidIdentifier(value.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Checks that `value` is a feature name Cargo accepts: ASCII letters, digits, `_`, `-`, `+` and `.`,
+/// not starting with `-`, `+` or `.`.
+pub(crate) fn feature_name(value: &str) -> Result<(), InstructionError> {
+    let mut chars = value.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphanumeric())
+        && chars.all(|c| matches!(c, '_' | '-' | '+' | '.') || c.is_ascii_alphanumeric());
+
+    if !valid {
+        return Err(InstructionError::InvalidFeatureName(value.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Checks the key and the optional value of a `rustc-cfg` instruction.
+pub(crate) fn cfg(key: &str, value: Option<&str>) -> Result<(), InstructionError> {
+    identifier(key)?;
+    value.map_or(Ok(()), line)
+}
+
+/// Runs `validation` in debug builds only, panicking if it fails.
+/// Used by the infallible instruction methods to catch mistakes early without a cost in release builds.
+#[track_caller]
+pub(crate) fn debug_check(validation: impl FnOnce() -> Result<(), InstructionError>) {
+    if cfg!(debug_assertions) {
+        if let Err(err) = validation() {
+            panic!("invalid build instruction: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_and_keys() {
+        assert_eq!(line("a b"), Ok(()));
+        assert_eq!(
+            line("a\r\nb"),
+            Err(InstructionError::LineBreak("a\r\nb".to_owned()))
+        );
+        assert_eq!(non_empty_line(""), Err(InstructionError::Empty));
+        assert_eq!(
+            key("a=b"),
+            Err(InstructionError::ContainsEquals("a=b".to_owned()))
+        );
+        assert_eq!(key("FOO_BAR"), Ok(()));
+    }
+
+    #[test]
+    fn identifiers() {
+        for valid in ["nightly", "_private", "has_feature2", "ünicode"] {
+            assert_eq!(identifier(valid), Ok(()), "{valid}");
+        }
+        for invalid in ["", "_", "2fast", "with-dash", "a b"] {
+            assert_eq!(
+                identifier(invalid),
+                Err(InstructionError::InvalidIdentifier(invalid.to_owned())),
+                "{invalid}"
+            );
+        }
+
+        assert_eq!(cfg("backend", Some("gl")), Ok(()));
+        assert!(cfg("backend", Some("a\nb")).is_err());
+    }
+
+    #[test]
+    fn feature_names() {
+        for valid in [
+            "std",
+            "foo-bar",
+            "foo_bar",
+            "2d",
+            "_internal",
+            "c++",
+            "v1.2",
+        ] {
+            assert_eq!(feature_name(valid), Ok(()), "{valid}");
+        }
+        for invalid in ["", "-foo", "+foo", "a b", "a=b", "ünicode", "stra\u{df}e"] {
+            assert_eq!(
+                feature_name(invalid),
+                Err(InstructionError::InvalidFeatureName(invalid.to_owned())),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic = "invalid build instruction"]
+    fn debug_check_panics() {
+        debug_check(|| key(""));
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(path(Path::new("/usr/lib")), Ok("/usr/lib"));
+        assert!(path(Path::new("/usr/lib\n")).is_err());
+
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+            let path = Path::new(OsStr::from_bytes(b"/usr/lib/\xff"));
+            assert_eq!(
+                super::path(path),
+                Err(InstructionError::NotUnicode("/usr/lib/\u{FFFD}".to_owned()))
+            );
+        }
+    }
+
+    #[test]
+    fn env_vars_set_by_cargo_are_reserved() {
+        for var in ["CARGO_PKG_VERSION", "OUT_DIR", "RUSTC"] {
+            assert_eq!(
+                env_var(var),
+                Err(InstructionError::ReservedEnv(var.to_owned()))
+            );
+        }
+        assert_eq!(env_var("GIT_HASH"), Ok(()));
+    }
+}
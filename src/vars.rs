@@ -0,0 +1,291 @@
+//! Names of the environment variables the crate reads, as set by Cargo and other tools for build scripts.
+
+/// Path to the `cargo` binary performing the build
+pub const CARGO: &str = "CARGO";
+/// Directory containing the manifest of the package being built
+pub const CARGO_MANIFEST_DIR: &str = "CARGO_MANIFEST_DIR";
+/// Path to the manifest of the package being built
+pub const CARGO_MANIFEST_PATH: &str = "CARGO_MANIFEST_PATH";
+/// Value of the `links` manifest key of the package being built
+pub const CARGO_MANIFEST_LINKS: &str = "CARGO_MANIFEST_LINKS";
+/// Full version of the package
+pub const CARGO_PKG_VERSION: &str = "CARGO_PKG_VERSION";
+/// Major version of the package
+pub const CARGO_PKG_VERSION_MAJOR: &str = "CARGO_PKG_VERSION_MAJOR";
+/// Minor version of the package
+pub const CARGO_PKG_VERSION_MINOR: &str = "CARGO_PKG_VERSION_MINOR";
+/// Patch version of the package
+pub const CARGO_PKG_VERSION_PATCH: &str = "CARGO_PKG_VERSION_PATCH";
+/// Pre-release version of the package
+pub const CARGO_PKG_VERSION_PRE: &str = "CARGO_PKG_VERSION_PRE";
+/// Colon-separated authors of the package
+pub const CARGO_PKG_AUTHORS: &str = "CARGO_PKG_AUTHORS";
+/// Name of the package
+pub const CARGO_PKG_NAME: &str = "CARGO_PKG_NAME";
+/// Description of the package
+pub const CARGO_PKG_DESCRIPTION: &str = "CARGO_PKG_DESCRIPTION";
+/// Home page of the package
+pub const CARGO_PKG_HOMEPAGE: &str = "CARGO_PKG_HOMEPAGE";
+/// Repository of the package
+pub const CARGO_PKG_REPOSITORY: &str = "CARGO_PKG_REPOSITORY";
+/// License of the package
+pub const CARGO_PKG_LICENSE: &str = "CARGO_PKG_LICENSE";
+/// Path to the license file of the package
+pub const CARGO_PKG_LICENSE_FILE: &str = "CARGO_PKG_LICENSE_FILE";
+/// Minimum supported Rust version of the package
+pub const CARGO_PKG_RUST_VERSION: &str = "CARGO_PKG_RUST_VERSION";
+/// Path to the README file of the package
+pub const CARGO_PKG_README: &str = "CARGO_PKG_README";
+/// Name of the crate being compiled
+pub const CARGO_CRATE_NAME: &str = "CARGO_CRATE_NAME";
+/// Name of the binary being compiled
+pub const CARGO_BIN_NAME: &str = "CARGO_BIN_NAME";
+/// Set when the package being built is a primary package, selected on the command line
+pub const CARGO_PRIMARY_PACKAGE: &str = "CARGO_PRIMARY_PACKAGE";
+/// Directory for integration tests and benchmarks to store temporary files in
+pub const CARGO_TARGET_TMPDIR: &str = "CARGO_TARGET_TMPDIR";
+/// Flags of the jobserver Cargo shares with build scripts
+pub const CARGO_MAKEFLAGS: &str = "CARGO_MAKEFLAGS";
+/// Directory the compiler is invoked from
+pub const CARGO_RUSTC_CURRENT_DIR: &str = "CARGO_RUSTC_CURRENT_DIR";
+/// Extra flags passed to the compiler, separated by `0x1f`
+pub const CARGO_ENCODED_RUSTFLAGS: &str = "CARGO_ENCODED_RUSTFLAGS";
+/// Extra flags passed to the compiler, separated by whitespace, as set by the user rather than Cargo
+pub const RUSTFLAGS: &str = "RUSTFLAGS";
+/// Cargo's home directory, passed through from Cargo's environment when set
+pub const CARGO_HOME: &str = "CARGO_HOME";
+/// Target directory set by the user, taking precedence over `CARGO_BUILD_TARGET_DIR`
+pub const CARGO_TARGET_DIR: &str = "CARGO_TARGET_DIR";
+/// Target directory set by the user through the `build.target-dir` config
+pub const CARGO_BUILD_TARGET_DIR: &str = "CARGO_BUILD_TARGET_DIR";
+/// Color preference of Cargo's output
+pub const CARGO_TERM_COLOR: &str = "CARGO_TERM_COLOR";
+
+/// Prefix of the variables holding paths to binaries of the package, followed by the name of the binary
+pub const CARGO_BIN_EXE_PREFIX: &str = "CARGO_BIN_EXE_";
+/// Prefix of the variables holding paths to binaries of artifact dependencies,
+/// followed by the dependency name and optionally `_` and the name of the binary
+pub const CARGO_BIN_FILE_PREFIX: &str = "CARGO_BIN_FILE_";
+/// Prefix of the variables holding paths to dynamic libraries of artifact dependencies, followed by the dependency name
+pub const CARGO_CDYLIB_FILE_PREFIX: &str = "CARGO_CDYLIB_FILE_";
+/// Prefix of the variables holding paths to static libraries of artifact dependencies, followed by the dependency name
+pub const CARGO_STATICLIB_FILE_PREFIX: &str = "CARGO_STATICLIB_FILE_";
+/// Prefix of the variables set for enabled features, followed by the feature name uppercased with `-` replaced by `_`
+pub const CARGO_FEATURE_PREFIX: &str = "CARGO_FEATURE_";
+/// Prefix of the variables holding the metadata of dependencies, followed by their `links` name and the metadata key,
+/// both uppercased with `-` replaced by `_`
+pub const DEP_PREFIX: &str = "DEP_";
+/// Prefix of the variables holding the cfgs of the target, followed by the cfg name uppercased
+pub const CARGO_CFG_PREFIX: &str = "CARGO_CFG_";
+
+/// Operating system of the target
+pub const CARGO_CFG_TARGET_OS: &str = "CARGO_CFG_TARGET_OS";
+/// Comma-separated families of the target
+pub const CARGO_CFG_TARGET_FAMILY: &str = "CARGO_CFG_TARGET_FAMILY";
+/// CPU architecture of the target
+pub const CARGO_CFG_TARGET_ARCH: &str = "CARGO_CFG_TARGET_ARCH";
+/// Environment, such as the C library, of the target
+pub const CARGO_CFG_TARGET_ENV: &str = "CARGO_CFG_TARGET_ENV";
+/// Vendor of the target
+pub const CARGO_CFG_TARGET_VENDOR: &str = "CARGO_CFG_TARGET_VENDOR";
+/// Endianness of the target
+pub const CARGO_CFG_TARGET_ENDIAN: &str = "CARGO_CFG_TARGET_ENDIAN";
+/// Pointer width of the target in bits
+pub const CARGO_CFG_TARGET_POINTER_WIDTH: &str = "CARGO_CFG_TARGET_POINTER_WIDTH";
+/// Comma-separated features enabled for the target
+pub const CARGO_CFG_TARGET_FEATURE: &str = "CARGO_CFG_TARGET_FEATURE";
+/// ABI of the target, refining the environment
+pub const CARGO_CFG_TARGET_ABI: &str = "CARGO_CFG_TARGET_ABI";
+/// Comma-separated features enabled for the package, spelled as in the manifest
+pub const CARGO_CFG_FEATURE: &str = "CARGO_CFG_FEATURE";
+/// Panic strategy of the target
+pub const CARGO_CFG_PANIC: &str = "CARGO_CFG_PANIC";
+/// Set when the crate is compiled by Clippy
+pub const CARGO_CFG_CLIPPY: &str = "CARGO_CFG_CLIPPY";
+/// Set when the crate is interpreted by Miri
+pub const CARGO_CFG_MIRI: &str = "CARGO_CFG_MIRI";
+
+/// Directory for the build script to write its output to
+pub const OUT_DIR: &str = "OUT_DIR";
+/// Target triple being compiled for
+pub const TARGET: &str = "TARGET";
+/// Target triple of the host running the build
+pub const HOST: &str = "HOST";
+/// Number of jobs the build script may run in parallel
+pub const NUM_JOBS: &str = "NUM_JOBS";
+/// Optimization level of the profile being built
+pub const OPT_LEVEL: &str = "OPT_LEVEL";
+/// Whether debug info is enabled for the profile being built
+pub const DEBUG: &str = "DEBUG";
+/// Name of the profile being built, either `debug` or `release`
+pub const PROFILE: &str = "PROFILE";
+/// Rust compiler Cargo uses
+pub const RUSTC: &str = "RUSTC";
+/// Documentation generator Cargo uses
+pub const RUSTDOC: &str = "RUSTDOC";
+/// Wrapper Cargo runs the Rust compiler through
+pub const RUSTC_WRAPPER: &str = "RUSTC_WRAPPER";
+/// Wrapper Cargo runs the Rust compiler through for workspace members, inside of `RUSTC_WRAPPER`
+pub const RUSTC_WORKSPACE_WRAPPER: &str = "RUSTC_WORKSPACE_WRAPPER";
+/// Linker the Rust compiler is configured to use
+pub const RUSTC_LINKER: &str = "RUSTC_LINKER";
+/// Enables unstable features on stable compilers, for every crate or the crates named
+pub const RUSTC_BOOTSTRAP: &str = "RUSTC_BOOTSTRAP";
+
+/// Home directory of the user on Unix
+pub const HOME: &str = "HOME";
+/// Home directory of the user on Windows
+pub const USERPROFILE: &str = "USERPROFILE";
+
+/// Set by docs.rs when building documentation
+pub const DOCS_RS: &str = "DOCS_RS";
+/// Arguments passed to Clippy, set when the crate is linted by it
+pub const CLIPPY_ARGS: &str = "CLIPPY_ARGS";
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_env::TestEnv, Cargo, EnvVarError, TargetCfg};
+    use std::path::PathBuf;
+
+    /// Turns the path read by a getter into a string to compare it to the value of the variable.
+    fn path(path: Result<PathBuf, EnvVarError>) -> Option<String> {
+        path.ok().map(|path| path.display().to_string())
+    }
+
+    #[test]
+    fn getters_read_the_named_variables() {
+        type Getter = fn() -> Option<String>;
+        let getters: [(&str, &str, Getter); 47] = [
+            ("CARGO", "/bin/cargo", || path(Cargo::binary_path())),
+            ("CARGO_MANIFEST_DIR", "/src/pkg", || {
+                path(Cargo::manifest_dir())
+            }),
+            ("CARGO_MANIFEST_PATH", "/src/pkg/Cargo.toml", || {
+                path(Cargo::manifest_path())
+            }),
+            ("CARGO_MANIFEST_LINKS", "z", || {
+                Cargo::manifest_links().ok().flatten()
+            }),
+            ("CARGO_PKG_VERSION", "1.2.3-rc.1", || {
+                Cargo::pkg_version().ok()
+            }),
+            ("CARGO_PKG_VERSION_MAJOR", "1", || {
+                Cargo::pkg_version_major().ok()
+            }),
+            ("CARGO_PKG_VERSION_MINOR", "2", || {
+                Cargo::pkg_version_minor().ok()
+            }),
+            ("CARGO_PKG_VERSION_PATCH", "3", || {
+                Cargo::pkg_version_patch().ok()
+            }),
+            ("CARGO_PKG_VERSION_PRE", "rc.1", || {
+                Cargo::pkg_version_pre().ok()
+            }),
+            ("CARGO_PKG_AUTHORS", "Jane", || Cargo::pkg_authors().ok()),
+            ("CARGO_PKG_NAME", "pkg", || Cargo::pkg_name().ok()),
+            ("CARGO_PKG_DESCRIPTION", "A package", || {
+                Cargo::pkg_description().ok()
+            }),
+            ("CARGO_PKG_HOMEPAGE", "https://example.com", || {
+                Cargo::pkg_homepage().ok()
+            }),
+            ("CARGO_PKG_REPOSITORY", "https://example.com/git", || {
+                Cargo::pkg_repository().ok()
+            }),
+            ("CARGO_PKG_LICENSE", "MIT", || Cargo::pkg_license().ok()),
+            ("CARGO_PKG_LICENSE_FILE", "LICENSE", || {
+                path(Cargo::pkg_license_file())
+            }),
+            ("CARGO_PKG_RUST_VERSION", "1.70", || {
+                Cargo::pkg_rust_version().ok()
+            }),
+            (
+                "CARGO_PKG_README",
+                "README.md",
+                || path(Cargo::pkg_readme()),
+            ),
+            ("CARGO_CRATE_NAME", "pkg", || Cargo::crate_name().ok()),
+            ("CARGO_BIN_NAME", "cli", || Cargo::bin_name().ok()),
+            ("CARGO_TARGET_TMPDIR", "/tmp/pkg", || {
+                path(Cargo::target_tmpdir())
+            }),
+            ("CARGO_MAKEFLAGS", "-j", || {
+                Cargo::makeflags().ok().flatten()
+            }),
+            ("CARGO_RUSTC_CURRENT_DIR", "/src", || {
+                path(Cargo::rustc_current_dir())
+            }),
+            ("CARGO_HOME", "/home/user/.cargo", || {
+                Cargo::home_explicit()
+                    .ok()
+                    .flatten()
+                    .map(|home| home.display().to_string())
+            }),
+            ("CARGO_TERM_COLOR", "always", || {
+                Cargo::color_enabled().then(|| "always".to_owned())
+            }),
+            ("CARGO_CFG_TARGET_OS", "linux", || {
+                TargetCfg::target_os().ok()
+            }),
+            ("CARGO_CFG_TARGET_FAMILY", "unix", || {
+                TargetCfg::target_family().ok()
+            }),
+            ("CARGO_CFG_TARGET_ARCH", "x86_64", || {
+                TargetCfg::target_arch().ok()
+            }),
+            ("CARGO_CFG_TARGET_ENV", "gnu", || {
+                TargetCfg::target_env().ok()
+            }),
+            ("CARGO_CFG_TARGET_VENDOR", "unknown", || {
+                TargetCfg::target_vendor().ok()
+            }),
+            ("CARGO_CFG_TARGET_ENDIAN", "little", || {
+                TargetCfg::target_endian().ok()
+            }),
+            ("CARGO_CFG_TARGET_POINTER_WIDTH", "64", || {
+                TargetCfg::target_pointer_width().ok()
+            }),
+            ("CARGO_CFG_TARGET_FEATURE", "sse2", || {
+                TargetCfg::target_feature().ok()
+            }),
+            ("CARGO_CFG_TARGET_ABI", "eabihf", || {
+                TargetCfg::target_abi().ok()
+            }),
+            ("CARGO_CFG_PANIC", "abort", || TargetCfg::panic().ok()),
+            ("OUT_DIR", "/target/out", || path(Cargo::out_dir())),
+            ("TARGET", "x86_64-unknown-linux-gnu", || {
+                Cargo::target().ok()
+            }),
+            ("HOST", "aarch64-apple-darwin", || Cargo::host().ok()),
+            ("NUM_JOBS", "4", || {
+                Cargo::num_jobs().ok().map(|jobs| jobs.to_string())
+            }),
+            ("OPT_LEVEL", "s", || {
+                Cargo::opt_level().ok().map(|level| level.to_string())
+            }),
+            ("DEBUG", "true", || {
+                Cargo::debug_info().ok().map(|debug| debug.to_string())
+            }),
+            ("PROFILE", "release", || {
+                Cargo::profile().ok().map(|profile| profile.to_string())
+            }),
+            ("RUSTC", "/bin/rustc", || path(Cargo::rustc())),
+            ("RUSTDOC", "/bin/rustdoc", || path(Cargo::rustdoc())),
+            ("RUSTC_LINKER", "/bin/cc", || {
+                Cargo::rustc_linker().map(|linker| linker.display().to_string())
+            }),
+            ("DOCS_RS", "1", || {
+                Cargo::is_docs_rs().then(|| "1".to_owned())
+            }),
+            ("CARGO_PRIMARY_PACKAGE", "1", || {
+                Cargo::is_primary_package().then(|| "1".to_owned())
+            }),
+        ];
+
+        let mut env = TestEnv::new();
+        for (var, value, getter) in getters {
+            env.set(var, value);
+            assert_eq!(getter().as_deref(), Some(value), "{var}");
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::VersionError;
+
+/// Represents a semantic version, such as the version of the package being built.
+///
+/// Versions are ordered by semver precedence, where build metadata is ignored.
+/// Versions differing only in build metadata are therefore neither equal nor ordered.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Version {
+    /// The major version
+    pub major: u64,
+    /// The minor version
+    pub minor: u64,
+    /// The patch version
+    pub patch: u64,
+    /// The pre-release identifiers, such as `alpha.1`
+    pub pre: Option<String>,
+    /// The build metadata, such as `20240101`
+    pub build: Option<String>,
+}
+
+impl Version {
+    /// Creates a release version without pre-release identifiers or build metadata.
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre: None,
+            build: None,
+        }
+    }
+
+    /// Compares two versions by semver precedence, ignoring build metadata.
+    pub fn cmp_precedence(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(pre), Some(other_pre)) => cmp_pre(pre, other_pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.cmp_precedence(other) {
+            Ordering::Equal if self.build != other.build => None,
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = VersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || VersionError::Invalid(s.to_owned());
+
+        let (rest, build) = match s.split_once('+') {
+            Some((rest, build)) => (rest, Some(build)),
+            None => (s, None),
+        };
+        let (core, pre) = match rest.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (rest, None),
+        };
+
+        let mut numbers = core.split('.').map(parse_number);
+        let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) = (
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+        ) else {
+            return Err(invalid());
+        };
+
+        if !pre.is_none_or(is_valid_identifiers) || !build.is_none_or(is_valid_identifiers) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            pre: pre.map(str::to_owned),
+            build: build.map(str::to_owned),
+        })
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{pre}")?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{build}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "semver")]
+impl From<semver::Version> for Version {
+    fn from(version: semver::Version) -> Self {
+        Self {
+            major: version.major,
+            minor: version.minor,
+            patch: version.patch,
+            pre: Some(version.pre.to_string()).filter(|pre| !pre.is_empty()),
+            build: Some(version.build.to_string()).filter(|build| !build.is_empty()),
+        }
+    }
+}
+
+#[cfg(feature = "semver")]
+impl TryFrom<Version> for semver::Version {
+    type Error = semver::Error;
+
+    fn try_from(version: Version) -> Result<Self, Self::Error> {
+        Ok(Self {
+            major: version.major,
+            minor: version.minor,
+            patch: version.patch,
+            pre: version
+                .pre
+                .as_deref()
+                .map_or(Ok(semver::Prerelease::EMPTY), semver::Prerelease::new)?,
+            build: version
+                .build
+                .as_deref()
+                .map_or(Ok(semver::BuildMetadata::EMPTY), semver::BuildMetadata::new)?,
+        })
+    }
+}
+
+/// Parses a version number, rejecting empty numbers and leading zeros.
+fn parse_number(number: &str) -> Option<u64> {
+    if number.len() > 1 && number.starts_with('0') {
+        return None;
+    }
+    if !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    number.parse().ok()
+}
+
+/// Checks that a dot separated list of identifiers is non-empty and only uses `[0-9A-Za-z-]`.
+fn is_valid_identifiers(identifiers: &str) -> bool {
+    identifiers.split('.').all(|identifier| {
+        !identifier.is_empty()
+            && identifier
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    })
+}
+
+/// Compares pre-release identifiers: numeric ones numerically and below alphanumeric ones,
+/// alphanumeric ones lexically, and a shorter list below a longer one it's a prefix of.
+fn cmp_pre(pre: &str, other: &str) -> Ordering {
+    let mut identifiers = pre.split('.');
+    let mut other_identifiers = other.split('.');
+
+    loop {
+        let ordering = match (identifiers.next(), other_identifiers.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(identifier), Some(other)) => {
+                match (identifier.parse::<u64>(), other.parse::<u64>()) {
+                    (Ok(number), Ok(other)) => number.cmp(&other),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => identifier.cmp(other),
+                }
+            }
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let parsed = version("1.2.3-alpha.1+build.5");
+        assert_eq!(
+            parsed,
+            Version {
+                major: 1,
+                minor: 2,
+                patch: 3,
+                pre: Some("alpha.1".to_owned()),
+                build: Some("build.5".to_owned()),
+            }
+        );
+        assert_eq!(parsed.to_string(), "1.2.3-alpha.1+build.5");
+        assert_eq!(version("0.10.0"), Version::new(0, 10, 0));
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        for s in [
+            "",
+            "1",
+            "1.2",
+            "1.2.3.4",
+            "01.2.3",
+            "1.2.x",
+            "1.2.3-",
+            "1.2.3-a..b",
+            "1.2.3+",
+            "1.2.3-a_b",
+        ] {
+            assert_eq!(
+                s.parse::<Version>(),
+                Err(VersionError::Invalid(s.to_owned())),
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.1.0",
+            "2.0.0",
+        ];
+
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn build_metadata_is_unordered() {
+        let a = version("1.0.0+a");
+        let b = version("1.0.0+b");
+
+        assert_eq!(a.cmp_precedence(&b), Ordering::Equal);
+        assert_eq!(a.partial_cmp(&b), None);
+        assert_ne!(a, b);
+    }
+
+    #[cfg(feature = "semver")]
+    #[test]
+    fn semver_round_trip() {
+        for s in [
+            "1.2.3-rc.1+abc",
+            "0.0.0",
+            "1.0.0-alpha.beta.1",
+            "1.0.0-0.3.7",
+            "1.0.0+20130313144700",
+            "1.0.0-x-y-z.--+exp.sha.5114f85",
+        ] {
+            let parsed = version(s);
+            let converted = semver::Version::try_from(parsed.clone()).unwrap();
+
+            assert_eq!(converted.to_string(), s);
+            assert_eq!(Version::from(converted), parsed);
+        }
+    }
+}